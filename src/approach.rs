@@ -0,0 +1,277 @@
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::events::{EventCondition, EventDetector};
+use crate::simulation::Simulation;
+use crate::units::{Length, Time};
+
+/// Two bodies at the moment they are closest to each other.
+#[derive(Debug, Clone)]
+pub struct Approach {
+    pub a: i32,
+    pub b: i32,
+    pub time: Time,
+    pub distance: Length,
+    /// Velocity of `a` relative to `b`, in meters per second.
+    pub relative_velocity: DecimalVector3d,
+}
+
+impl Simulation {
+    fn approach_at(&self, a: i32, b: i32, time: Time) -> Option<Approach> {
+        let snapshot = self.at(time.clone())?;
+        let relative_velocity =
+            snapshot.get_body_by_id(a)?.velocity() - snapshot.get_body_by_id(b)?.velocity();
+        Some(Approach {
+            a,
+            b,
+            distance: snapshot.get_distance(a, b)?,
+            time,
+            relative_velocity,
+        })
+    }
+
+    /// Smallest distance between bodies `a` and `b` between `start` and `end`, which is either a
+    /// local minimum or one of the window's ends. Distances are sampled every `step`, see
+    /// [`EventDetector`].
+    pub fn closest_approach(
+        &self,
+        a: i32,
+        b: i32,
+        start: Time,
+        end: Time,
+        step: Time,
+    ) -> Option<Approach> {
+        let mut detector = EventDetector::new(step);
+        detector.add(EventCondition::closest_approach(a, b));
+
+        let mut closest = self.approach_at(a, b, start.clone())?;
+        let mut candidates = vec![end.clone()];
+        candidates.extend(
+            detector
+                .find(self, start, end)?
+                .into_iter()
+                .map(|event| event.time),
+        );
+        for time in candidates {
+            let approach = self.approach_at(a, b, time)?;
+            if approach.distance < closest.distance {
+                closest = approach;
+            }
+        }
+        Some(closest)
+    }
+
+    /// Whether `ancestor` is `id`'s parent, its parent's parent and so on.
+    fn is_ancestor(&self, ancestor: i32, id: i32) -> bool {
+        let mut current = self.get_body_by_id(id).and_then(|body| body.parent());
+        while let Some(parent) = current {
+            if parent == ancestor {
+                return true;
+            }
+            current = self.get_body_by_id(parent).and_then(|body| body.parent());
+        }
+        false
+    }
+
+    /// Every local minimum of the distance between any two bodies between `start` and `end`
+    /// that comes closer than `threshold`, ordered by time. Bodies are not paired with their
+    /// ancestors, which they circle rather than approach, nor with barycenters. All pairs share
+    /// one [`EventDetector`], `None` if it fails, like for two bodies at the same position.
+    pub fn find_close_approaches(
+        &self,
+        threshold: Length,
+        start: Time,
+        end: Time,
+        step: Time,
+    ) -> Option<Vec<Approach>> {
+        let ids: Vec<i32> = self
+            .bodies
+            .iter()
+            .filter(|body| !body.body().is_barycenter())
+            .map(|body| body.id())
+            .collect();
+        let mut pairs = vec![];
+        let mut detector = EventDetector::new(step);
+        for (index, &a) in ids.iter().enumerate() {
+            for &b in &ids[index + 1..] {
+                if !self.is_ancestor(a, b) && !self.is_ancestor(b, a) {
+                    detector.add(EventCondition::closest_approach(a, b));
+                    pairs.push((a, b));
+                }
+            }
+        }
+
+        let mut approaches = vec![];
+        for event in detector.find(self, start, end)? {
+            let (a, b) = pairs[event.condition];
+            let approach = self.approach_at(a, b, event.time)?;
+            if approach.distance < threshold {
+                approaches.push(approach);
+            }
+        }
+        Some(approaches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::StaticBodyDynamics;
+    use crate::body::{Body, BodyDynamics, LinearBodyDynamics, OrbitingBodyDynamics};
+    use crate::sin_cos::{dbig_to_f64, f64_to_dbig};
+    use crate::units::{Angle, Mass};
+
+    fn body(name: &str, dynamics: BodyDynamics) -> Body {
+        Body::new(
+            name,
+            Mass::from_kilograms(f64_to_dbig(1e20)),
+            Length::from_meters(f64_to_dbig(1e3)),
+            dynamics,
+        )
+    }
+
+    fn seconds(seconds: f64) -> Time {
+        Time::from_seconds(f64_to_dbig(seconds))
+    }
+
+    /// A static body with a satellite, a body passing it 100 km away at t = 1000 s at 1 km/s
+    /// and a body resting 1 km from the first one.
+    fn flyby() -> (Simulation, i32, i32) {
+        let satellite = body(
+            "satellite",
+            BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                orbit_radius: Length::from_meters(f64_to_dbig(1e7)),
+                orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                orbit_period: seconds(1e4),
+                orbit_phase: Angle::zero(),
+            }),
+        );
+        let mut sim = Simulation::new();
+        let a = sim.add_hierarchy(
+            Body {
+                satellites: vec![satellite],
+                ..body(
+                    "a",
+                    BodyDynamics::Static(StaticBodyDynamics {
+                        position: DecimalVector3d::zero(),
+                    }),
+                )
+            },
+            None,
+        );
+        let passing = BodyDynamics::Linear(LinearBodyDynamics {
+            position: DecimalVector3d::from_f64(-1e6, 1e5, 0.0),
+            velocity: DecimalVector3d::from_f64(1e3, 0.0, 0.0),
+        });
+        let b = sim.add_hierarchy(body("b", passing), None);
+        let resting = BodyDynamics::Static(StaticBodyDynamics {
+            position: DecimalVector3d::from_f64(0.0, 0.0, 1e3),
+        });
+        sim.add_hierarchy(body("resting", resting), None);
+        sim.update(Time::zero());
+        (sim, a, b)
+    }
+
+    fn orbiting(name: &str, radius: f64, period: f64) -> Body {
+        Body {
+            rotation_period: seconds(86400.0),
+            ..Body::new(
+                name,
+                Mass::from_kilograms(f64_to_dbig(6e24)),
+                Length::from_meters(f64_to_dbig(6.4e6)),
+                BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                    orbit_radius: Length::from_meters(f64_to_dbig(radius)),
+                    orbit_period: seconds(period),
+                    orbit_phase: Angle::zero(),
+                    orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                }),
+            )
+        }
+    }
+
+    #[test]
+    fn closest_approach_of_a_flyby() {
+        let (sim, a, b) = flyby();
+        let approach = sim
+            .closest_approach(a, b, Time::zero(), seconds(3000.0), seconds(250.0))
+            .unwrap();
+        assert!((dbig_to_f64(&approach.time.to_seconds()) - 1000.0).abs() < 0.05);
+        assert!((dbig_to_f64(&approach.distance.to_meters()) - 1e5).abs() < 1e-3);
+        let expected = DecimalVector3d::from_f64(-1e3, 0.0, 0.0);
+        assert!(dbig_to_f64(&approach.relative_velocity.distance_to(expected)) < 1e-6);
+    }
+
+    #[test]
+    fn scan_skips_satellites() {
+        let (mut sim, a, b) = flyby();
+        let approaches = sim
+            .find_close_approaches(
+                Length::from_meters(f64_to_dbig(2e5)),
+                Time::zero(),
+                seconds(3000.0),
+                seconds(250.0),
+            )
+            .unwrap();
+        // the resting body passes b just like a does
+        let pairs: Vec<(i32, i32)> = approaches
+            .iter()
+            .map(|approach| (approach.a, approach.b))
+            .collect();
+        assert_eq!(pairs, vec![(a, b), (b, b + 1)]);
+        assert!(approaches
+            .iter()
+            .all(|approach| (dbig_to_f64(&approach.time.to_seconds()) - 1000.0).abs() < 0.05));
+
+        // the distance between two bodies at the same position has no direction to follow
+        let at_origin = BodyDynamics::Static(StaticBodyDynamics {
+            position: DecimalVector3d::zero(),
+        });
+        sim.add_hierarchy(body("coinciding", at_origin), None);
+        sim.update(Time::zero());
+        assert!(sim
+            .find_close_approaches(
+                Length::from_meters(f64_to_dbig(2e5)),
+                Time::zero(),
+                seconds(3000.0),
+                seconds(250.0),
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn planets_meet_once_per_synodic_period() {
+        // With periods of 10000 s and 20000 s the planets line up again after 20000 s.
+        let star = Body {
+            satellites: vec![orbiting("inner", 1e7, 1e4), orbiting("outer", 1.2e7, 2e4)],
+            rotation_period: seconds(2e6),
+            ..Body::new(
+                "star",
+                Mass::from_kilograms(f64_to_dbig(2e30)),
+                Length::from_meters(f64_to_dbig(7e8)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            )
+        };
+        let mut sim = Simulation::new();
+        let star = sim.add_hierarchy(star, None);
+        sim.update(Time::zero());
+        let (inner, outer) = (star + 1, star + 2);
+
+        let approach = sim
+            .closest_approach(inner, outer, seconds(1e4), seconds(3e4), seconds(1e3))
+            .unwrap();
+        assert!((dbig_to_f64(&approach.time.to_seconds()) - 2e4).abs() < 1.0);
+        assert!((dbig_to_f64(&approach.distance.to_meters()) - 2e6).abs() < 1.0);
+
+        let approaches = sim
+            .find_close_approaches(
+                Length::from_meters(f64_to_dbig(5e6)),
+                seconds(1e4),
+                seconds(3e4),
+                seconds(1e3),
+            )
+            .unwrap();
+        assert_eq!(approaches.len(), 1);
+        assert_eq!((approaches[0].a, approaches[0].b), (inner, outer));
+        assert!((dbig_to_f64(&approaches[0].time.to_seconds()) - 2e4).abs() < 1.0);
+    }
+}
@@ -0,0 +1,387 @@
+//! Free rigid-body rotation, for bodies that tumble instead of spinning about a fixed axis.
+//!
+//! Without torques the angular momentum stays fixed in space while Euler's equations move it
+//! through the body: a body spinning about its largest or smallest principal axis precesses
+//! around the angular momentum, one spinning near its intermediate axis tumbles over. The
+//! equations are integrated with fixed Runge-Kutta steps, a fraction of a turn each. The
+//! simulation keeps the latest state of every tumbling body and steps on from it, on `update`
+//! and when asked about other times. [`Body::orientation_at`](crate::body::Body::orientation_at)
+//! has no such state and integrates from the epoch.
+
+use crate::body::Body;
+use crate::decimal_matrix_3d::DecimalMatrix3d;
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::rotation::RotationModel;
+use crate::simulation::Simulation;
+use crate::sin_cos::{dbig_to_f64, PIMUL2};
+use crate::units::Time;
+use dashu_float::DBig;
+
+/// Integration steps per turn of the body.
+const STEPS_PER_TURN: f64 = 200.0;
+
+/// Torque-free rotation of a rigid body from `epoch` on.
+#[derive(Debug, Clone)]
+pub struct RigidBodyAttitude {
+    /// In body-fixed coordinates, in kg m^2.
+    inertia: DecimalMatrix3d,
+    inverse_inertia: DecimalMatrix3d,
+    /// Maps body-fixed coordinates at `epoch` into global coordinates.
+    orientation: DecimalMatrix3d,
+    /// In global coordinates, in kg m^2/s.
+    angular_momentum: DecimalVector3d,
+    epoch: Time,
+}
+
+/// Orientation and angular momentum in body-fixed coordinates of a tumbling body at `time`.
+#[derive(Debug, Clone)]
+pub struct AttitudeState {
+    pub time: Time,
+    pub orientation: DecimalMatrix3d,
+    pub body_angular_momentum: DecimalVector3d,
+}
+
+impl RigidBodyAttitude {
+    /// A body with `inertia`, in body-fixed coordinates and kg m^2, and `angular_momentum`, in
+    /// global coordinates and kg m^2/s, with `orientation` at `epoch`. `None` unless `inertia`
+    /// is symmetric and positive definite, as every physical inertia tensor is.
+    pub fn new(
+        inertia: DecimalMatrix3d,
+        orientation: DecimalMatrix3d,
+        angular_momentum: DecimalVector3d,
+        epoch: Time,
+    ) -> Option<RigidBodyAttitude> {
+        let d = &inertia.data;
+        let symmetric = (0..3).all(|i| (0..i).all(|j| d[i][j] == d[j][i]));
+        // Sylvester's criterion on the leading principal minors
+        let minor = d[0][0].clone() * &d[1][1] - d[0][1].clone() * &d[1][0];
+        if !symmetric || d[0][0] <= DBig::ZERO || minor <= DBig::ZERO {
+            return None;
+        }
+        let inverse_inertia = inertia.inverse()?;
+        // the last diagonal entry of the inverse is the minor over the determinant
+        if inverse_inertia.data[2][2] <= DBig::ZERO {
+            return None;
+        }
+        Some(RigidBodyAttitude {
+            inertia,
+            inverse_inertia,
+            orientation,
+            angular_momentum,
+            epoch,
+        })
+    }
+
+    /// A body with `inertia` turning at `angular_velocity`, in global coordinates and radians
+    /// per second, with `orientation` at `epoch`. `None` where [`RigidBodyAttitude::new`] is.
+    pub fn spinning(
+        inertia: DecimalMatrix3d,
+        orientation: DecimalMatrix3d,
+        angular_velocity: &DecimalVector3d,
+        epoch: Time,
+    ) -> Option<RigidBodyAttitude> {
+        let body_rate = orientation.transposed().apply(angular_velocity.clone());
+        let angular_momentum = orientation.apply(inertia.apply(body_rate));
+        RigidBodyAttitude::new(inertia, orientation, angular_momentum, epoch)
+    }
+
+    /// In body-fixed coordinates, in kg m^2.
+    pub fn inertia(&self) -> &DecimalMatrix3d {
+        &self.inertia
+    }
+
+    /// Maps body-fixed coordinates at the epoch into global coordinates.
+    pub fn orientation(&self) -> &DecimalMatrix3d {
+        &self.orientation
+    }
+
+    /// In global coordinates, in kg m^2/s, constant along the motion.
+    pub fn angular_momentum(&self) -> &DecimalVector3d {
+        &self.angular_momentum
+    }
+
+    pub fn epoch(&self) -> &Time {
+        &self.epoch
+    }
+
+    pub fn initial_state(&self) -> AttitudeState {
+        AttitudeState {
+            time: self.epoch.clone(),
+            orientation: self.orientation.clone(),
+            body_angular_momentum: self
+                .orientation
+                .transposed()
+                .apply(self.angular_momentum.clone()),
+        }
+    }
+
+    /// State at `time`, integrated from the epoch.
+    pub fn state_at(&self, time: &Time) -> AttitudeState {
+        self.advance(&self.initial_state(), time)
+    }
+
+    /// Rotational kinetic energy in joules, constant along the motion.
+    pub fn energy(&self, state: &AttitudeState) -> DBig {
+        let rate = self.body_rate(state);
+        rate.dot(state.body_angular_momentum.clone()) / DBig::from(2)
+    }
+
+    /// Spin vector in global coordinates in radians per second.
+    pub fn angular_velocity(&self, state: &AttitudeState) -> DecimalVector3d {
+        state.orientation.apply(self.body_rate(state))
+    }
+
+    fn body_rate(&self, state: &AttitudeState) -> DecimalVector3d {
+        self.inverse_inertia
+            .apply(state.body_angular_momentum.clone())
+    }
+
+    /// `state` carried forward, or back, to `time`.
+    pub fn advance(&self, state: &AttitudeState, time: &Time) -> AttitudeState {
+        let inverse_inertia = &self.inverse_inertia;
+        let elapsed = (time - &state.time).to_seconds();
+        let rate = dbig_to_f64(
+            &inverse_inertia
+                .apply(state.body_angular_momentum.clone())
+                .length(),
+        );
+        let turns = dbig_to_f64(&elapsed).abs() * rate / dbig_to_f64(&PIMUL2);
+        let steps = (turns * STEPS_PER_TURN).ceil().max(1.0) as usize;
+        let step = elapsed / DBig::from(steps);
+
+        let d = &state.orientation.data;
+        let mut y = [
+            DecimalVector3d::new(d[0][0].clone(), d[0][1].clone(), d[0][2].clone()),
+            DecimalVector3d::new(d[1][0].clone(), d[1][1].clone(), d[1][2].clone()),
+            DecimalVector3d::new(d[2][0].clone(), d[2][1].clone(), d[2][2].clone()),
+            state.body_angular_momentum.clone(),
+        ];
+        // body axes turn with the spin, the momentum turns against it in body coordinates
+        let derivative = |y: &[DecimalVector3d; 4]| {
+            let body_rate = inverse_inertia.apply(y[3].clone());
+            let rate = y[0].clone() * &body_rate.x
+                + y[1].clone() * &body_rate.y
+                + y[2].clone() * &body_rate.z;
+            [
+                rate.cross(y[0].clone()),
+                rate.cross(y[1].clone()),
+                rate.cross(y[2].clone()),
+                y[3].cross(body_rate),
+            ]
+        };
+        let half = step.clone() / DBig::from(2);
+        let sixth = step.clone() / DBig::from(6);
+        for _ in 0..steps {
+            let advanced = |k: &[DecimalVector3d; 4], by: &DBig| {
+                std::array::from_fn(|i| y[i].clone() + k[i].clone() * by)
+            };
+            let k1 = derivative(&y);
+            let k2 = derivative(&advanced(&k1, &half));
+            let k3 = derivative(&advanced(&k2, &half));
+            let k4 = derivative(&advanced(&k3, &step));
+            y = std::array::from_fn(|i| {
+                y[i].clone()
+                    + (k1[i].clone()
+                        + (k2[i].clone() + k3[i].clone()) * DBig::from(2)
+                        + k4[i].clone())
+                        * &sixth
+            });
+            // keep the axes orthonormal against the drift of the integration
+            let x = y[0].normalized();
+            let yy = (y[1].clone() - x.clone() * x.dot(y[1].clone())).normalized();
+            y[2] = x.cross(yy.clone());
+            y[0] = x;
+            y[1] = yy;
+        }
+        let [x, y_axis, z, momentum] = y;
+        AttitudeState {
+            time: time.clone(),
+            orientation: DecimalMatrix3d::from_basis(x, y_axis, z),
+            body_angular_momentum: momentum,
+        }
+    }
+}
+
+impl Simulation {
+    /// Orientation of body `id` at `time`, stepping a tumbling body on from its last state.
+    pub(crate) fn advance_orientation(&mut self, id: i32, time: &Time) -> DecimalMatrix3d {
+        let body = self.get_body_by_id(id).unwrap().body();
+        let Some(RotationModel::RigidBody(attitude)) = &body.rotation_model else {
+            return self.body_orientation_at(id, time).unwrap();
+        };
+        let state = self.attitude_at(id, attitude, time);
+        let orientation = state.orientation.clone();
+        self.attitudes.insert(id, state);
+        orientation
+    }
+
+    /// State of tumbling body `id` with `attitude` at `time`, stepped from the last state kept
+    /// by `update` instead of integrated from the epoch.
+    pub(crate) fn attitude_at(
+        &self,
+        id: i32,
+        attitude: &RigidBodyAttitude,
+        time: &Time,
+    ) -> AttitudeState {
+        match self.attitudes.get(&id) {
+            Some(state) if state.time == *time => state.clone(),
+            Some(state) => attitude.advance(state, time),
+            None => attitude.state_at(time),
+        }
+    }
+
+    /// Current rotation state of tumbling body `id`, `None` for other bodies.
+    pub fn attitude(&self, id: i32) -> Option<&AttitudeState> {
+        self.attitudes.get(&id)
+    }
+
+    /// Spin vector of body `id` at the current simulation time in radians per second.
+    pub(crate) fn body_angular_velocity(&self, id: i32) -> Option<DecimalVector3d> {
+        self.body_angular_velocity_at(id, self.time())
+    }
+}
+
+impl Body {
+    /// The body's tumbling motion, if it has one.
+    pub fn rigid_body_attitude(&self) -> Option<&RigidBodyAttitude> {
+        match &self.rotation_model {
+            Some(RotationModel::RigidBody(attitude)) => Some(attitude.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{BodyDynamics, StaticBodyDynamics};
+    use crate::sin_cos::f64_to_dbig;
+    use crate::units::{Length, Mass};
+
+    fn diagonal(x: f64, y: f64, z: f64) -> DecimalMatrix3d {
+        DecimalMatrix3d::from_basis(
+            DecimalVector3d::from_f64(x, 0.0, 0.0),
+            DecimalVector3d::from_f64(0.0, y, 0.0),
+            DecimalVector3d::from_f64(0.0, 0.0, z),
+        )
+    }
+
+    fn asteroid(attitude: RigidBodyAttitude) -> Body {
+        Body {
+            rotation_model: Some(RotationModel::RigidBody(Box::new(attitude))),
+            ..Body::new(
+                "Asteroid",
+                Mass::from_kilograms(f64_to_dbig(1e12)),
+                Length::from_meters(DBig::from(500)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            )
+        }
+    }
+
+    #[test]
+    fn symmetric_top_precesses() {
+        // an oblate body, y the symmetry axis, spun 30 degrees off it
+        let (transverse, axial) = (1.0, 2.0);
+        let tilt = 30f64.to_radians();
+        let momentum = DecimalVector3d::from_f64(tilt.sin(), tilt.cos(), 0.0);
+        let attitude = RigidBodyAttitude::new(
+            diagonal(transverse, axial, transverse),
+            DecimalMatrix3d::identity(),
+            momentum.clone(),
+            Time::zero(),
+        )
+        .unwrap();
+        let mut sim = Simulation::new();
+        let id = sim.add_hierarchy(asteroid(attitude.clone()), None);
+
+        // the symmetry axis circles the momentum at |L| / I_transverse
+        let quarter = std::f64::consts::FRAC_PI_2 * transverse;
+        for step in 1..=4 {
+            sim.update(Time::from_seconds(f64_to_dbig(quarter * step as f64 / 4.0)));
+        }
+        let axis = sim
+            .get_body_by_id(id)
+            .unwrap()
+            .orientation()
+            .apply(DecimalVector3d::from_f64(0.0, 1.0, 0.0));
+        let cone = dbig_to_f64(&axis.dot(momentum.clone())).acos().to_degrees();
+        assert!((cone - 30.0).abs() < 1e-6);
+        // a quarter turn around the momentum takes the axis out of the x-y plane
+        let expected =
+            DecimalMatrix3d::axis_angle(momentum, f64_to_dbig(std::f64::consts::FRAC_PI_2))
+                .apply(DecimalVector3d::from_f64(0.0, 1.0, 0.0));
+        assert!(dbig_to_f64(&axis.distance_to(expected)) < 1e-6);
+
+        // stepping through updates agrees with integrating from the epoch
+        let direct = attitude.state_at(sim.time());
+        let stepped = sim.attitude(id).unwrap();
+        let difference = direct
+            .orientation
+            .apply(DecimalVector3d::from_f64(1.0, 0.0, 0.0))
+            .distance_to(
+                stepped
+                    .orientation
+                    .apply(DecimalVector3d::from_f64(1.0, 0.0, 0.0)),
+            );
+        assert!(dbig_to_f64(&difference) < 1e-6);
+
+        // queries through the simulation read the kept state instead of integrating again
+        let pole = sim.body_pole_at(id, sim.time()).unwrap();
+        let kept = stepped
+            .orientation
+            .apply(DecimalVector3d::from_f64(0.0, 1.0, 0.0));
+        assert_eq!(dbig_to_f64(&pole.distance_to(kept)), 0.0);
+        let spin = sim.body_angular_velocity(id).unwrap();
+        assert_eq!(
+            dbig_to_f64(&spin.distance_to(attitude.angular_velocity(stepped))),
+            0.0
+        );
+    }
+
+    #[test]
+    fn rejects_unphysical_inertia() {
+        let attitude = |inertia| {
+            RigidBodyAttitude::new(
+                inertia,
+                DecimalMatrix3d::identity(),
+                DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                Time::zero(),
+            )
+        };
+        assert!(attitude(diagonal(1.0, 2.0, 3.0)).is_some());
+        assert!(attitude(diagonal(1.0, 0.0, 3.0)).is_none());
+        assert!(attitude(diagonal(1.0, 2.0, -3.0)).is_none());
+        let mut skewed = diagonal(1.0, 2.0, 3.0);
+        skewed.data[0][1] = f64_to_dbig(0.5);
+        assert!(attitude(skewed).is_none());
+    }
+
+    #[test]
+    fn intermediate_axis_tumbles() {
+        // spun about the intermediate axis with a slight wobble, the body flips over and back
+        let attitude = RigidBodyAttitude::spinning(
+            diagonal(1.0, 2.0, 3.0),
+            DecimalMatrix3d::identity(),
+            &DecimalVector3d::from_f64(0.01, 1.0, 0.0),
+            Time::zero(),
+        )
+        .unwrap();
+        let initial = attitude.initial_state();
+        let energy = attitude.energy(&initial);
+        let mut flipped = false;
+        let mut state = initial;
+        for step in 1..=40 {
+            state = attitude.advance(&state, &Time::from_seconds(DBig::from(step)));
+            let alignment =
+                state.body_angular_momentum.y.clone() / attitude.angular_momentum().length();
+            flipped |= dbig_to_f64(&alignment) < -0.9;
+        }
+        assert!(flipped);
+        let drift = (attitude.energy(&state) - &energy) / energy;
+        assert!(dbig_to_f64(&drift).abs() < 1e-6);
+        let momentum = state.orientation.apply(state.body_angular_momentum.clone());
+        assert!(dbig_to_f64(&momentum.distance_to(attitude.angular_momentum().clone())) < 1e-6);
+    }
+}
@@ -2,7 +2,9 @@ use crate::sin_cos::f64_to_dbig;
 use dashu_float::DBig;
 use std::sync::LazyLock;
 
-pub static AU_METERS: LazyLock<DBig> = LazyLock::new(|| f64_to_dbig(149597870691.0));
+/// The astronomical unit as defined by the IAU in 2012, see
+/// [`Constants`](crate::constants::Constants) for the older value.
+pub static AU_METERS: LazyLock<DBig> = LazyLock::new(|| f64_to_dbig(149597870700.0));
 
 pub fn au_to_meters(au: DBig) -> DBig {
     au * AU_METERS.clone()
@@ -0,0 +1,202 @@
+//! Barycenter nodes, massless points of a hierarchy that two or more bodies orbit together:
+//! binary and multiple stars, pairs like Pluto and Charon, and circumbinary planets.
+//!
+//! A barycenter is an ordinary [`Body`] without mass or size marked by its `barycenter` flag, so
+//! it is scheduled, transformed and played back like any other body and can itself be static,
+//! orbit or follow an ephemeris.
+
+use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics};
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::simulation::Simulation;
+use crate::sin_cos::PI;
+use crate::units::{Angle, Length, Mass, Time};
+
+impl Body {
+    /// A barycenter node moving with `dynamics`, orbited by `satellites`.
+    pub fn barycenter(name: &str, dynamics: BodyDynamics, satellites: Vec<Body>) -> Body {
+        Body {
+            satellites,
+            barycenter: true,
+            ..Body::new(name, Mass::zero(), Length::zero(), dynamics)
+        }
+    }
+
+    pub fn is_barycenter(&self) -> bool {
+        self.barycenter
+    }
+
+    /// `primary` and `secondary` on circular orbits `separation` apart around their common
+    /// barycenter, which moves with `dynamics`. Each orbit radius is the separation times the
+    /// other body's share of the total mass, `primary` starts on the reference direction and
+    /// `secondary` opposite it. The bodies keep their satellites, planets orbiting both can be
+    /// added to the barycenter's satellites. `None` if both bodies are massless.
+    pub fn binary(
+        name: &str,
+        mut primary: Body,
+        mut secondary: Body,
+        separation: Length,
+        period: Time,
+        orbit_plane_normal: DecimalVector3d,
+        dynamics: BodyDynamics,
+    ) -> Option<Body> {
+        let total = &primary.mass + &secondary.mass;
+        if total == Mass::zero() {
+            return None;
+        }
+        let orbit = |mass: &Mass, phase: Angle| {
+            BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                orbit_radius: &separation * &(mass / &total),
+                orbit_plane_normal: orbit_plane_normal.clone(),
+                orbit_period: period.clone(),
+                orbit_phase: phase,
+            })
+        };
+        primary.dynamics = orbit(&secondary.mass, Angle::zero());
+        secondary.dynamics = orbit(&primary.mass, Angle::from_radians(PI.clone()));
+        Some(Body::barycenter(name, dynamics, vec![primary, secondary]))
+    }
+}
+
+impl Simulation {
+    /// Id of the star lighting the hierarchy `id` belongs to: its root, or the most massive body
+    /// below the root if that is a barycenter.
+    pub fn get_star_id(&self, id: i32) -> i32 {
+        let root_id = self.get_root_id(id);
+        let Some(root) = self.get_body_by_id(root_id) else {
+            return root_id;
+        };
+        if !root.body().is_barycenter() {
+            return root_id;
+        }
+        self.resolve_hierarchy_down(root)
+            .into_iter()
+            .reduce(|star, body| match body.body().mass > star.body().mass {
+                true => body,
+                false => star,
+            })
+            .map_or(root_id, |star| star.id())
+    }
+
+    /// Mass pulling on the satellites of body `id`: the body's own mass, or for a barycenter
+    /// the mass of everything below it in the hierarchy.
+    pub fn central_mass(&self, id: i32) -> Option<Mass> {
+        let body = self.get_body_by_id(id)?;
+        if !body.body().is_barycenter() {
+            return Some(body.body().mass.clone());
+        }
+        Some(
+            self.resolve_hierarchy_down(body)
+                .into_iter()
+                .fold(Mass::zero(), |total, member| total + &member.body().mass),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::StaticBodyDynamics;
+    use crate::sin_cos::{dbig_to_f64, f64_to_dbig};
+
+    fn body(name: &str, mass: f64, radius: f64) -> Body {
+        Body {
+            rotation_period: Time::from_seconds(f64_to_dbig(86400.0)),
+            ..Body::new(
+                name,
+                Mass::from_kilograms(f64_to_dbig(mass)),
+                Length::from_meters(f64_to_dbig(radius)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            )
+        }
+    }
+
+    fn circular(radius: f64, period: f64) -> BodyDynamics {
+        BodyDynamics::Orbiting(OrbitingBodyDynamics {
+            orbit_radius: Length::from_meters(f64_to_dbig(radius)),
+            orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+            orbit_period: Time::from_seconds(f64_to_dbig(period)),
+            orbit_phase: Angle::zero(),
+        })
+    }
+
+    #[test]
+    fn binary_stars_with_a_circumbinary_planet() {
+        let mut system = Body::binary(
+            "AB",
+            body("A", 2e30, 7e8),
+            body("B", 1e30, 5e8),
+            Length::from_meters(f64_to_dbig(3e10)),
+            Time::from_seconds(f64_to_dbig(3e6)),
+            DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+            BodyDynamics::Static(StaticBodyDynamics {
+                position: DecimalVector3d::from_f64(1e12, 0.0, 0.0),
+            }),
+        )
+        .unwrap();
+        let mut planet = body("planet", 6e24, 6.4e6);
+        planet.dynamics = circular(2e11, 5e7);
+        system.satellites.push(planet);
+        assert!(system.is_barycenter());
+
+        let mut sim = Simulation::new();
+        let root = sim.add_hierarchy(system, None);
+        let (a, b, planet) = (root + 1, root + 2, root + 3);
+        sim.update(Time::from_seconds(f64_to_dbig(1.234e6)));
+
+        let distance = sim.get_distance(a, b).unwrap();
+        assert!((dbig_to_f64(&distance.to_meters()) - 3e10).abs() < 1e-3);
+        let node = sim.get_body_by_id(root).unwrap().position().clone();
+        let weighted = (sim.get_body_by_id(a).unwrap().position() * f64_to_dbig(2.0)
+            + sim.get_body_by_id(b).unwrap().position().clone())
+            / f64_to_dbig(3.0);
+        assert!(dbig_to_f64(&weighted.distance_to(node)) < 1e-3);
+        // the stars always move in opposite directions
+        let (va, vb) = (
+            sim.get_body_by_id(a).unwrap().velocity(),
+            sim.get_body_by_id(b).unwrap().velocity(),
+        );
+        assert!(dbig_to_f64(&(va * f64_to_dbig(2.0) + vb).length()) < 1e-3);
+
+        assert_eq!(sim.get_star_id(planet), a);
+        assert_eq!(sim.get_star_id(b), a);
+        let mass = dbig_to_f64(&sim.central_mass(root).unwrap().to_kilograms());
+        assert!((mass / (3e30 + 6e24) - 1.0).abs() < 1e-12);
+        let soi = dbig_to_f64(&sim.sphere_of_influence(planet).unwrap().to_meters());
+        let expected = 2e11 * (6e24f64 / (3e30 + 6e24)).powf(0.4);
+        assert!((soi / expected - 1.0).abs() < 1e-9, "{soi}");
+    }
+
+    #[test]
+    fn orbiting_double_planet() {
+        let mut star = body("star", 2e30, 7e8);
+        star.satellites.push(
+            Body::binary(
+                "pair",
+                body("pluto", 1.3e22, 1.19e6),
+                body("charon", 1.6e21, 6.06e5),
+                Length::from_meters(f64_to_dbig(1.96e7)),
+                Time::from_seconds(f64_to_dbig(5.5e5)),
+                DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                circular(5.9e12, 7.8e9),
+            )
+            .unwrap(),
+        );
+        let mut sim = Simulation::new();
+        let root = sim.add_hierarchy(star, None);
+        let (pair, pluto, charon) = (root + 1, root + 2, root + 3);
+        sim.update(Time::from_seconds(f64_to_dbig(2e5)));
+
+        let orbit = dbig_to_f64(&sim.get_distance(root, pair).unwrap().to_meters());
+        assert!((orbit - 5.9e12).abs() < 1e-3);
+        let pluto_orbit = dbig_to_f64(&sim.get_distance(pluto, pair).unwrap().to_meters());
+        assert!((pluto_orbit - 1.96e7 * 1.6 / 14.6).abs() < 1e-3);
+        let separation = dbig_to_f64(&sim.get_distance(pluto, charon).unwrap().to_meters());
+        assert!((separation - 1.96e7).abs() < 1e-3);
+        assert!(!sim.get_body_by_id(pluto).unwrap().body().is_barycenter());
+        // a point without physical data is still an ordinary body
+        let point = Body::new("point", Mass::zero(), Length::zero(), circular(1e9, 1e6));
+        assert!(!point.is_barycenter());
+    }
+}
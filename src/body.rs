@@ -1,29 +1,156 @@
+use crate::chebyshev::ChebyshevBody;
+use crate::conic::ConicDynamics;
+use crate::decimal_matrix_3d::DecimalMatrix3d;
 use crate::decimal_vector_3d::DecimalVector3d;
+use crate::galactic::GalacticOrbitDynamics;
+use crate::playback::{check_error_bound, PlaybackError};
+use crate::rotation::RotationModel;
+use crate::sin_cos::{f64_to_dbig, PIMUL2};
+use crate::units::{Angle, Length, Mass, Time};
+use dashu_float::ops::Abs;
 use dashu_float::DBig;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct StaticBodyDynamics {
     pub position: DecimalVector3d,
 }
 
+/// Uniform motion in a straight line, `position` at `time = 0`. Both are relative to the
+/// parent, or the global origin for roots, like the drift of a star system.
+#[derive(Debug, Clone)]
+pub struct LinearBodyDynamics {
+    pub position: DecimalVector3d,
+    pub velocity: DecimalVector3d, // in meters per second
+}
+
 #[derive(Debug, Clone)]
 pub struct OrbitingBodyDynamics {
-    pub orbit_radius: DBig,
+    pub orbit_radius: Length,
     pub orbit_plane_normal: DecimalVector3d,
-    pub orbit_period: DBig,
+    pub orbit_period: Time,
+    /// Angle the body has already turned around the normal at `time = 0`.
+    pub orbit_phase: Angle,
+}
+
+/// Dynamics reading the position relative to the parent from precomputed Chebyshev segments
+/// instead of evaluating an orbit. The segments are shared, cloning a `Simulation` is cheap.
+#[derive(Debug, Clone)]
+pub struct EphemerisBodyDynamics {
+    pub segments: Arc<ChebyshevBody>,
+}
+
+impl EphemerisBodyDynamics {
+    /// Fails if the fit recorded in `segments` is off by more than `max_error` meters anywhere.
+    pub fn new(segments: ChebyshevBody, max_error: f64) -> Result<Self, PlaybackError> {
+        check_error_bound(&segments, max_error)?;
+        Ok(EphemerisBodyDynamics {
+            segments: Arc::new(segments),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum BodyDynamics {
     Static(StaticBodyDynamics),
+    Linear(LinearBodyDynamics),
     Orbiting(OrbitingBodyDynamics),
+    Conic(ConicDynamics),
+    /// A massless craft, see [`crate::patched_conic`].
+    Craft(ConicDynamics),
+    Galactic(GalacticOrbitDynamics),
+    Ephemeris(EphemerisBodyDynamics),
 }
 
 #[derive(Debug, Clone)]
 pub struct Body {
     pub name: String,
+    pub mass: Mass,
+    pub radius: Length, // equatorial
+    /// `(equatorial - polar) / equatorial` radius, `None` for a sphere.
+    pub flattening: Option<DBig>,
     pub rotation_axis: DecimalVector3d,
-    pub rotation_period: DBig, // in seconds
+    pub rotation_period: Time, // sidereal
+    /// Moves the pole and prime meridian over time in place of the fixed `rotation_axis`.
+    pub rotation_model: Option<RotationModel>,
     pub dynamics: BodyDynamics,
     pub satellites: Vec<Body>,
+    /// A massless node the satellites orbit together, see [`Body::barycenter`].
+    pub barycenter: bool,
+}
+
+impl Body {
+    /// A sphere moving with `dynamics` that does not rotate and has no satellites, the other
+    /// fields can be set with struct update syntax.
+    pub fn new(name: &str, mass: Mass, radius: Length, dynamics: BodyDynamics) -> Body {
+        Body {
+            name: name.to_string(),
+            mass,
+            radius,
+            flattening: None,
+            rotation_axis: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+            rotation_period: Time::zero(),
+            rotation_model: None,
+            dynamics,
+            satellites: vec![],
+            barycenter: false,
+        }
+    }
+
+    /// Body-fixed axes at `time = 0`, y is the rotation axis (the pole) and x is the prime
+    /// meridian, picked as the direction perpendicular to the pole closest to the global x axis.
+    pub fn reference_axes(&self) -> (DecimalVector3d, DecimalVector3d, DecimalVector3d) {
+        let pole = self.rotation_axis.normalized();
+        let mut meridian = DecimalVector3d::new(DBig::ONE, DBig::ZERO, DBig::ZERO);
+        if meridian.dot(pole.clone()).abs() > f64_to_dbig(0.999) {
+            meridian = DecimalVector3d::new(DBig::ZERO, DBig::ZERO, DBig::ONE);
+        }
+        let x = (meridian.clone() - pole.clone() * meridian.dot(pole.clone())).normalized();
+        let z = x.cross(pole.clone());
+        (x, pole, z)
+    }
+
+    /// Rotation angle around the pole at `time`.
+    pub fn rotation_angle(&self, time: &Time) -> Angle {
+        if self.rotation_period == Time::zero() {
+            return Angle::zero();
+        }
+        Angle::from_radians(PIMUL2.clone() * (time / &self.rotation_period).fract())
+    }
+
+    /// Maps body-fixed coordinates at `time` into global coordinates.
+    pub fn orientation_at(&self, time: &Time) -> DecimalMatrix3d {
+        if let Some(model) = &self.rotation_model {
+            if let Some(orientation) = model.orientation_at(time, self) {
+                return orientation;
+            }
+        }
+        let (x, y, z) = self.reference_axes();
+        let spin = DecimalMatrix3d::axis_angle(y.clone(), self.rotation_angle(time).to_radians());
+        DecimalMatrix3d::from_basis(spin.apply(x), y, spin.apply(z))
+    }
+
+    /// Direction of the pole at `time`.
+    pub fn pole_at(&self, time: &Time) -> DecimalVector3d {
+        self.rotation_model
+            .as_ref()
+            .and_then(|model| model.pole_at(time, self))
+            .unwrap_or_else(|| self.rotation_axis.normalized())
+    }
+
+    /// Spin vector in radians per second at `time`.
+    pub fn angular_velocity_at(&self, time: &Time) -> DecimalVector3d {
+        self.rotation_model
+            .as_ref()
+            .and_then(|model| model.angular_velocity_at(time, self))
+            .unwrap_or_else(|| self.angular_velocity())
+    }
+
+    /// Spin vector in radians per second around the fixed `rotation_axis`.
+    pub fn angular_velocity(&self) -> DecimalVector3d {
+        if self.rotation_period == Time::zero() {
+            return DecimalVector3d::zero();
+        }
+        self.rotation_axis.normalized() * (PIMUL2.clone() / self.rotation_period.to_seconds())
+    }
 }
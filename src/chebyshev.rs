@@ -0,0 +1,530 @@
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::ephemeris::{
+    read_dbig, read_f64, read_i32, read_magic, read_string, read_u32, read_vector, write_f64,
+    write_i32, write_string, write_u32, write_vector,
+};
+use crate::simulation::Simulation;
+use crate::sin_cos::{dbig_to_f64, f64_to_dbig};
+use crate::units::Time;
+use dashu_float::DBig;
+use std::f64::consts::PI;
+use std::io::{self, Read, Write};
+
+const BINARY_MAGIC: &[u8; 4] = b"PSCB";
+const BINARY_VERSION: u32 = 1;
+
+/// Chebyshev coefficients of x, y and z over one time interval, in meters.
+#[derive(Debug, Clone)]
+pub struct ChebyshevSegment {
+    pub coefficients: [Vec<f64>; 3],
+    /// Largest position error measured against the fitted data, in meters, `None` if the
+    /// segment was read without one.
+    pub max_error: Option<f64>,
+}
+
+/// Position of one body relative to its `center`, the way a JPL SPK type 2 segment stores it:
+/// equally long intervals from `start`, each covered by its own polynomial.
+#[derive(Debug, Clone)]
+pub struct ChebyshevBody {
+    pub id: i32,
+    pub name: String,
+    /// Parent the positions are relative to, `None` for the global origin.
+    pub center: Option<i32>,
+    /// Subtracted from every position before fitting, keeps the f64 coefficients small.
+    pub origin: DecimalVector3d,
+    /// TDB since [`Epoch::j2000`](crate::time::Epoch::j2000) like the times of an SPK file, a
+    /// simulation at another [`Simulation::epoch`] shifts its own times to play them back.
+    pub start: Time,
+    pub interval: Time,
+    pub segments: Vec<ChebyshevSegment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChebyshevEphemeris {
+    pub bodies: Vec<ChebyshevBody>,
+}
+
+/// Chebyshev polynomials T_j(x) and their derivatives for j in `0..count`.
+fn chebyshev_terms(x: f64, count: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut t = vec![1.0, x];
+    // T_j'(x) = j * U_{j-1}(x)
+    let mut u = vec![1.0, 2.0 * x];
+    for j in 2..count {
+        t.push(2.0 * x * t[j - 1] - t[j - 2]);
+        u.push(2.0 * x * u[j - 1] - u[j - 2]);
+    }
+    t.truncate(count);
+    let derivatives = (0..count)
+        .map(|j| if j == 0 { 0.0 } else { j as f64 * u[j - 1] })
+        .collect();
+    (t, derivatives)
+}
+
+impl ChebyshevSegment {
+    /// Position offset and its derivative with respect to `x` in [-1, 1].
+    pub(crate) fn evaluate(&self, x: f64) -> ([f64; 3], [f64; 3]) {
+        let (t, dt) = chebyshev_terms(x, self.coefficients[0].len());
+        let mut position = [0.0; 3];
+        let mut derivative = [0.0; 3];
+        for axis in 0..3 {
+            for (j, c) in self.coefficients[axis].iter().enumerate() {
+                position[axis] += c * t[j];
+                derivative[axis] += c * dt[j];
+            }
+        }
+        (position, derivative)
+    }
+}
+
+impl ChebyshevBody {
+    /// End of the time coverage.
+    pub fn end(&self) -> Time {
+        &self.start + &(&self.interval * &DBig::from(self.segments.len()))
+    }
+
+    pub fn covers(&self, time: &Time) -> bool {
+        *time >= self.start && *time <= self.end()
+    }
+
+    /// Segment covering `time` and the normalized time within it.
+    fn locate(&self, time: &Time) -> Option<(&ChebyshevSegment, f64)> {
+        if !self.covers(time) {
+            return None;
+        }
+        let elapsed = &(time - &self.start) / &self.interval;
+        let index = (elapsed.floor().to_string().parse::<usize>().ok()?)
+            .min(self.segments.len().checked_sub(1)?);
+        let x = (elapsed - DBig::from(index)) * DBig::from(2) - DBig::ONE;
+        Some((&self.segments[index], dbig_to_f64(&x)))
+    }
+
+    /// Position (meters) and velocity (meters per second) relative to `center` at `time`, TDB
+    /// since J2000.0 like [`ChebyshevBody::start`], `None` outside the time coverage.
+    pub fn evaluate(&self, time: &Time) -> Option<(DecimalVector3d, DecimalVector3d)> {
+        let (segment, x) = self.locate(time)?;
+        let (position, derivative) = segment.evaluate(x);
+        let scale = 2.0 / dbig_to_f64(&self.interval.to_seconds());
+        Some((
+            DecimalVector3d::from_f64(position[0], position[1], position[2]) + &self.origin,
+            DecimalVector3d::from_f64(
+                derivative[0] * scale,
+                derivative[1] * scale,
+                derivative[2] * scale,
+            ),
+        ))
+    }
+
+    /// Largest fit error over all segments, in meters, `None` if any segment's is unknown.
+    pub fn max_error(&self) -> Option<f64> {
+        self.segments
+            .iter()
+            .try_fold(0.0, |max, segment| Some(segment.max_error?.max(max)))
+    }
+
+    /// Least-squares fit of polynomials of `degree` to positions relative to `center` sampled at
+    /// the given times, in intervals of `interval` from the first sample until the last one is
+    /// covered. Samples must be sorted by time and every interval needs more than
+    /// `degree` of them, the largest residual is recorded as its error. `None` if `interval` is
+    /// not positive.
+    pub fn fit_samples(
+        id: i32,
+        name: String,
+        center: Option<i32>,
+        samples: &[(Time, DecimalVector3d)],
+        interval: Time,
+        degree: usize,
+    ) -> Option<ChebyshevBody> {
+        if interval <= Time::zero() {
+            return None;
+        }
+        let (start, origin) = samples.first()?;
+        let end = &samples.last()?.0;
+        let count = degree + 1;
+
+        let mut segments = vec![];
+        let mut segment_start = start.clone();
+        loop {
+            let segment_end = segment_start.clone() + &interval;
+            let nodes: Vec<(Vec<f64>, [f64; 3])> = samples
+                .iter()
+                .filter(|(time, _)| *time >= segment_start && *time <= segment_end)
+                .map(|(time, position)| {
+                    let x = &(time - &segment_start) / &interval * DBig::from(2) - DBig::ONE;
+                    let offset = position - origin;
+                    (
+                        chebyshev_terms(dbig_to_f64(&x), count).0,
+                        [
+                            dbig_to_f64(&offset.x),
+                            dbig_to_f64(&offset.y),
+                            dbig_to_f64(&offset.z),
+                        ],
+                    )
+                })
+                .collect();
+            if nodes.len() < count {
+                return None;
+            }
+
+            let mut coefficients: [Vec<f64>; 3] = Default::default();
+            for (axis, axis_coefficients) in coefficients.iter_mut().enumerate() {
+                let mut normal = vec![vec![0.0; count]; count];
+                let mut right = vec![0.0; count];
+                for (terms, offset) in &nodes {
+                    for j in 0..count {
+                        for k in 0..count {
+                            normal[j][k] += terms[j] * terms[k];
+                        }
+                        right[j] += terms[j] * offset[axis];
+                    }
+                }
+                *axis_coefficients = solve_linear(normal, right)?;
+            }
+            let max_error = nodes
+                .iter()
+                .map(|(terms, offset)| {
+                    (0..3)
+                        .map(|axis| {
+                            let fitted: f64 = coefficients[axis]
+                                .iter()
+                                .zip(terms)
+                                .map(|(c, t)| c * t)
+                                .sum();
+                            (fitted - offset[axis]).powi(2)
+                        })
+                        .sum::<f64>()
+                        .sqrt()
+                })
+                .fold(0.0, f64::max);
+            segments.push(ChebyshevSegment {
+                coefficients,
+                max_error: Some(max_error),
+            });
+
+            segment_start = segment_end;
+            if segment_start >= *end {
+                break;
+            }
+        }
+        Some(ChebyshevBody {
+            id,
+            name,
+            center,
+            origin: origin.clone(),
+            start: start.clone(),
+            interval,
+            segments,
+        })
+    }
+}
+
+/// Solves `matrix * x = right` by Gaussian elimination with partial pivoting, `None` if the
+/// matrix is singular.
+fn solve_linear(mut matrix: Vec<Vec<f64>>, mut right: Vec<f64>) -> Option<Vec<f64>> {
+    let n = right.len();
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))?;
+        if matrix[pivot][column] == 0.0 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        right.swap(column, pivot);
+        let (upper, lower) = matrix.split_at_mut(column + 1);
+        let pivot_row = &upper[column];
+        for (offset, row) in lower.iter_mut().enumerate() {
+            let factor = row[column] / pivot_row[column];
+            for (value, pivot_value) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *value -= factor * pivot_value;
+            }
+            right[column + 1 + offset] -= factor * right[column];
+        }
+    }
+    let mut solution = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (right[row] - sum) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+impl Simulation {
+    /// Positions of `ids` relative to their parents at `time`.
+    fn relative_positions(&self, ids: &[i32], time: &Time) -> Option<Vec<DecimalVector3d>> {
+        let snapshot = self.at(time.clone())?;
+        ids.iter()
+            .map(|&id| {
+                let body = snapshot.get_body_by_id(id)?;
+                Some(match body.parent() {
+                    Some(parent) => body.position() - snapshot.get_body_by_id(parent)?.position(),
+                    None => body.position().clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Fits Chebyshev polynomials of `degree` to bodies `ids` (every body if `None`) over
+    /// intervals of `interval` from `start` until `end` is covered. Every interval is
+    /// checked against the simulation at the extrema of the next higher polynomial to record
+    /// its error. `None` if `interval` is not positive.
+    pub fn generate_chebyshev_ephemeris(
+        &self,
+        ids: Option<&[i32]>,
+        start: Time,
+        end: Time,
+        interval: Time,
+        degree: usize,
+    ) -> Option<ChebyshevEphemeris> {
+        if interval <= Time::zero() {
+            return None;
+        }
+        let ids = self.resolve_ids(ids)?;
+        let count = degree + 1;
+        let origins = self.relative_positions(&ids, &start)?;
+        let mut bodies: Vec<ChebyshevBody> = ids
+            .iter()
+            .zip(&origins)
+            .map(|(&id, origin)| {
+                let body = self.get_body_by_id(id).unwrap();
+                ChebyshevBody {
+                    id,
+                    name: body.body().name.clone(),
+                    center: body.parent(),
+                    origin: origin.clone(),
+                    start: self.ephemeris_time(&start),
+                    interval: interval.clone(),
+                    segments: vec![],
+                }
+            })
+            .collect();
+
+        let offsets_at = |segment_start: &Time, x: f64| {
+            let time =
+                segment_start + &(&interval * &((f64_to_dbig(x) + DBig::ONE) / DBig::from(2)));
+            let positions = self.relative_positions(&ids, &time)?;
+            Some(
+                positions
+                    .iter()
+                    .zip(&origins)
+                    .map(|(position, origin)| {
+                        let offset = position - origin;
+                        [
+                            dbig_to_f64(&offset.x),
+                            dbig_to_f64(&offset.y),
+                            dbig_to_f64(&offset.z),
+                        ]
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        let mut segment_start = start.clone();
+        while segment_start < end {
+            let nodes: Vec<f64> = (0..count)
+                .map(|k| (PI * (k as f64 + 0.5) / count as f64).cos())
+                .collect();
+            let values = nodes
+                .iter()
+                .map(|&x| offsets_at(&segment_start, x))
+                .collect::<Option<Vec<_>>>()?;
+
+            let checks: Vec<f64> = (0..=count)
+                .map(|k| (PI * k as f64 / count as f64).cos())
+                .collect();
+            let check_values = checks
+                .iter()
+                .map(|&x| offsets_at(&segment_start, x))
+                .collect::<Option<Vec<_>>>()?;
+
+            for (index, body) in bodies.iter_mut().enumerate() {
+                let mut coefficients: [Vec<f64>; 3] = Default::default();
+                for (axis, axis_coefficients) in coefficients.iter_mut().enumerate() {
+                    for j in 0..count {
+                        let sum: f64 = (0..count)
+                            .map(|k| {
+                                values[k][index][axis]
+                                    * (PI * j as f64 * (k as f64 + 0.5) / count as f64).cos()
+                            })
+                            .sum();
+                        let scale = if j == 0 { 1.0 } else { 2.0 };
+                        axis_coefficients.push(scale * sum / count as f64);
+                    }
+                }
+                let mut segment = ChebyshevSegment {
+                    coefficients,
+                    max_error: Some(0.0),
+                };
+                for (&x, actual) in checks.iter().zip(&check_values) {
+                    let (fitted, _) = segment.evaluate(x);
+                    let error = (0..3)
+                        .map(|axis| (fitted[axis] - actual[index][axis]).powi(2))
+                        .sum::<f64>()
+                        .sqrt();
+                    segment.max_error = segment.max_error.map(|max| max.max(error));
+                }
+                body.segments.push(segment);
+            }
+            segment_start += &interval;
+        }
+        Some(ChebyshevEphemeris { bodies })
+    }
+}
+
+impl ChebyshevEphemeris {
+    pub fn body(&self, id: i32) -> Option<&ChebyshevBody> {
+        self.bodies.iter().find(|body| body.id == id)
+    }
+
+    /// Little-endian binary segments, the format [`ChebyshevEphemeris::read_binary`] reads.
+    pub fn write_binary(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(BINARY_MAGIC)?;
+        write_u32(&mut writer, BINARY_VERSION)?;
+        write_u32(&mut writer, self.bodies.len() as u32)?;
+        for body in &self.bodies {
+            write_i32(&mut writer, body.id)?;
+            write_string(&mut writer, &body.name)?;
+            write_i32(&mut writer, body.center.unwrap_or(-1))?;
+            write_vector(&mut writer, &body.origin)?;
+            write_string(&mut writer, &body.start.to_seconds().to_string())?;
+            write_string(&mut writer, &body.interval.to_seconds().to_string())?;
+            let count = body.segments.first().map_or(0, |s| s.coefficients[0].len());
+            write_u32(&mut writer, body.segments.len() as u32)?;
+            write_u32(&mut writer, count as u32)?;
+            for segment in &body.segments {
+                // NaN marks an unknown error
+                write_f64(&mut writer, segment.max_error.unwrap_or(f64::NAN))?;
+                for axis in &segment.coefficients {
+                    for &coefficient in axis {
+                        write_f64(&mut writer, coefficient)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_binary(mut reader: impl Read) -> io::Result<ChebyshevEphemeris> {
+        read_magic(&mut reader, BINARY_MAGIC, BINARY_VERSION)?;
+        let mut bodies = vec![];
+        for _ in 0..read_u32(&mut reader)? {
+            let id = read_i32(&mut reader)?;
+            let name = read_string(&mut reader)?;
+            let center = Some(read_i32(&mut reader)?).filter(|&center| center >= 0);
+            let origin = read_vector(&mut reader)?;
+            let start = read_dbig(&mut reader)?;
+            let interval = read_dbig(&mut reader)?;
+            let segment_count = read_u32(&mut reader)?;
+            let count = read_u32(&mut reader)? as usize;
+            let mut segments = vec![];
+            for _ in 0..segment_count {
+                let max_error = Some(read_f64(&mut reader)?).filter(|error| !error.is_nan());
+                let mut coefficients: [Vec<f64>; 3] = Default::default();
+                for axis in coefficients.iter_mut() {
+                    for _ in 0..count {
+                        axis.push(read_f64(&mut reader)?);
+                    }
+                }
+                segments.push(ChebyshevSegment {
+                    coefficients,
+                    max_error,
+                });
+            }
+            bodies.push(ChebyshevBody {
+                id,
+                name,
+                center,
+                origin,
+                start: Time::from_seconds(start),
+                interval: Time::from_seconds(interval),
+                segments,
+            });
+        }
+        Ok(ChebyshevEphemeris { bodies })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics};
+    use crate::units::{Angle, Length, Mass};
+
+    fn simulation() -> Simulation {
+        let planet = Body {
+            rotation_period: Time::from_seconds(f64_to_dbig(86400.0)),
+            ..Body::new(
+                "planet",
+                Mass::from_kilograms(f64_to_dbig(6e24)),
+                Length::from_meters(f64_to_dbig(6.4e6)),
+                BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                    orbit_radius: Length::from_meters(f64_to_dbig(1.5e11)),
+                    orbit_period: Time::from_seconds(f64_to_dbig(3.15e7)),
+                    orbit_phase: Angle::zero(),
+                    orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.2).normalized(),
+                }),
+            )
+        };
+        let star = Body {
+            satellites: vec![planet],
+            rotation_period: Time::from_seconds(f64_to_dbig(2e6)),
+            ..Body::new(
+                "star",
+                Mass::from_kilograms(f64_to_dbig(2e30)),
+                Length::from_meters(f64_to_dbig(7e8)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::from_str("64959787070023434667", "0", "29349283489"),
+                }),
+            )
+        };
+        let mut sim = Simulation::new();
+        sim.add_hierarchy(star, None);
+        sim
+    }
+
+    #[test]
+    fn interpolates_between_nodes() {
+        let sim = simulation();
+        let ephemeris = sim
+            .generate_chebyshev_ephemeris(
+                None,
+                Time::zero(),
+                Time::from_seconds(f64_to_dbig(4e6)),
+                Time::from_seconds(f64_to_dbig(2e6)),
+                12,
+            )
+            .unwrap();
+        let planet = ephemeris.body(1).unwrap();
+        assert_eq!(planet.segments.len(), 2);
+        let max_error = planet.max_error().unwrap();
+        assert!(max_error < 1.0, "{max_error}");
+
+        let time = Time::from_seconds(f64_to_dbig(2.345e6));
+        let snapshot = sim.at(time.clone()).unwrap();
+        let (position, velocity) = planet.evaluate(&time).unwrap();
+        let expected = snapshot.get_body_by_id(1).unwrap().position()
+            - snapshot.get_body_by_id(0).unwrap().position();
+        assert!(dbig_to_f64(&position.distance_to(expected)) < 1.0);
+        let speed = dbig_to_f64(&velocity.length());
+        assert!((speed - 2.0 * PI * 1.5e11 / 3.15e7).abs() < 1.0, "{speed}");
+        assert!(planet
+            .evaluate(&Time::from_seconds(f64_to_dbig(4.1e6)))
+            .is_none());
+
+        let mut bytes = vec![];
+        ephemeris.write_binary(&mut bytes).unwrap();
+        let read = ChebyshevEphemeris::read_binary(bytes.as_slice()).unwrap();
+        let (read_position, _) = read.body(1).unwrap().evaluate(&time).unwrap();
+        assert!(dbig_to_f64(&read_position.distance_to(position)) < 1e-6);
+        let star = read.body(0).unwrap();
+        assert_eq!(star.center, None);
+        assert_eq!(star.origin.x, DBig::from(64959787070023434667u128));
+
+        let end = Time::from_seconds(f64_to_dbig(4e6));
+        assert!(sim
+            .generate_chebyshev_ephemeris(None, Time::zero(), end.clone(), Time::zero(), 12)
+            .is_none());
+        let samples = [(Time::zero(), read_position.clone()), (end, read_position)];
+        let fit =
+            ChebyshevBody::fit_samples(1, String::from("planet"), None, &samples, Time::zero(), 1);
+        assert!(fit.is_none());
+    }
+}
@@ -0,0 +1,349 @@
+//! Keplerian motion along any conic around the parent, including the open trajectories of
+//! interstellar visitors and comet flybys that [`crate::body::OrbitingBodyDynamics`], being
+//! periodic, cannot describe.
+//!
+//! The position at a time follows from the time of periapsis passage through Kepler's equation:
+//! `M = E - e sin E` on ellipses, `M = e sinh H - H` on hyperbolas and Barker's equation on
+//! parabolas, which has a closed-form solution.
+
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::orbital_elements::{wrap_signed_angle, ConicKind, OrbitalElements};
+use crate::reference_frame::StateVector;
+use crate::sin_cos::{atan, atan2, atanh, cos, cosh, f64_to_dbig, precise, sin, sinh, PI};
+use crate::units::{Angle, Length, Time};
+use dashu_float::ops::{Abs, SquareRoot};
+use dashu_float::DBig;
+
+/// Newton iterations stop once a step is smaller than this, in radians.
+const KEPLER_TOLERANCE: f64 = 1e-30;
+const KEPLER_MAX_ITERATIONS: usize = 100;
+
+/// Eccentric anomaly `E` solving `M = E - e sin E` for `e < 1`.
+pub fn solve_kepler(mean_anomaly: &DBig, eccentricity: &DBig) -> DBig {
+    let mean_anomaly = precise(wrap_signed_angle(mean_anomaly.clone()));
+    let eccentricity = precise(eccentricity.clone());
+    let tolerance = f64_to_dbig(KEPLER_TOLERANCE);
+    let mut anomaly = if eccentricity > f64_to_dbig(0.8) {
+        if mean_anomaly < DBig::ZERO {
+            -PI.clone()
+        } else {
+            PI.clone()
+        }
+    } else {
+        mean_anomaly.clone()
+    };
+    for _ in 0..KEPLER_MAX_ITERATIONS {
+        let residual =
+            anomaly.clone() - eccentricity.clone() * sin(anomaly.clone(), 32) - &mean_anomaly;
+        let slope = DBig::ONE - eccentricity.clone() * cos(anomaly.clone(), 32);
+        let step = residual / slope;
+        anomaly -= &step;
+        if step.abs() < tolerance {
+            break;
+        }
+    }
+    anomaly
+}
+
+/// Hyperbolic anomaly `H` solving `M = e sinh H - H` for `e > 1`.
+pub fn solve_hyperbolic_kepler(mean_anomaly: &DBig, eccentricity: &DBig) -> DBig {
+    let mean_anomaly = precise(mean_anomaly.clone());
+    let eccentricity = precise(eccentricity.clone());
+    if mean_anomaly == DBig::ZERO {
+        return mean_anomaly;
+    }
+    // Danby's starting value, close for large |M| and safe for small
+    let start =
+        (DBig::from(2) * mean_anomaly.clone().abs() / &eccentricity + f64_to_dbig(1.8)).ln();
+    let mut anomaly = if mean_anomaly < DBig::ZERO {
+        -start
+    } else {
+        start
+    };
+    let tolerance = f64_to_dbig(KEPLER_TOLERANCE);
+    for _ in 0..KEPLER_MAX_ITERATIONS {
+        let residual = eccentricity.clone() * sinh(anomaly.clone()) - &anomaly - &mean_anomaly;
+        let slope = eccentricity.clone() * cosh(anomaly.clone()) - DBig::ONE;
+        let step = residual / slope;
+        anomaly -= &step;
+        if step.abs() < tolerance.clone() * (DBig::ONE + anomaly.clone().abs()) {
+            break;
+        }
+    }
+    anomaly
+}
+
+/// `D = tan(nu / 2)` solving Barker's equation `M = D + D^3 / 3`.
+pub fn solve_barker(mean_anomaly: &DBig) -> DBig {
+    let a = precise(mean_anomaly.clone().abs()) * f64_to_dbig(1.5);
+    // the larger root of B^2 - 2AB - 1 = 0 stays clear of cancellation for every A >= 0
+    let b = (a.clone() + (a.clone() * &a + DBig::ONE).sqrt())
+        .powf(&(DBig::ONE / precise(DBig::from(3))));
+    let d = b.clone() - DBig::ONE / b;
+    if *mean_anomaly < DBig::ZERO {
+        -d
+    } else {
+        d
+    }
+}
+
+/// Keplerian trajectory around the parent given by its shape, orientation and the time of
+/// periapsis passage. Angles follow [`OrbitalElements`].
+#[derive(Debug, Clone)]
+pub struct ConicDynamics {
+    pub periapsis_distance: Length,
+    pub eccentricity: DBig,
+    pub inclination: Angle,
+    pub longitude_of_ascending_node: Angle,
+    pub argument_of_periapsis: Angle,
+    /// Time of the periapsis passage, for ellipses any one of them.
+    pub periapsis_time: Time,
+    /// `G (M + m)` in m^3/s^2, see [`crate::simulation::Simulation::gravitational_parameter`].
+    pub gravitational_parameter: DBig,
+}
+
+impl ConicDynamics {
+    /// Trajectory passing through `elements` at `time`.
+    pub fn from_elements(
+        elements: &OrbitalElements,
+        time: &Time,
+        gravitational_parameter: DBig,
+    ) -> ConicDynamics {
+        let mut conic = ConicDynamics {
+            periapsis_distance: elements.periapsis_distance.clone(),
+            eccentricity: elements.eccentricity.clone(),
+            inclination: elements.inclination.clone(),
+            longitude_of_ascending_node: elements.longitude_of_ascending_node.clone(),
+            argument_of_periapsis: elements.argument_of_periapsis.clone(),
+            periapsis_time: Time::zero(),
+            gravitational_parameter,
+        };
+        conic.periapsis_time = time - &conic.time_since_periapsis(&elements.true_anomaly);
+        conic
+    }
+
+    /// Trajectory through `state`, given relative to the parent, at `time`. `None` where
+    /// [`OrbitalElements::from_state`] has no elements.
+    pub fn from_state(
+        state: &StateVector,
+        time: &Time,
+        gravitational_parameter: DBig,
+    ) -> Option<ConicDynamics> {
+        let elements = OrbitalElements::from_state(state, &gravitational_parameter)?;
+        Some(ConicDynamics::from_elements(
+            &elements,
+            time,
+            gravitational_parameter,
+        ))
+    }
+
+    fn elements(&self, true_anomaly: Angle) -> OrbitalElements {
+        OrbitalElements {
+            periapsis_distance: self.periapsis_distance.clone(),
+            eccentricity: self.eccentricity.clone(),
+            inclination: self.inclination.clone(),
+            longitude_of_ascending_node: self.longitude_of_ascending_node.clone(),
+            argument_of_periapsis: self.argument_of_periapsis.clone(),
+            true_anomaly,
+        }
+    }
+
+    pub fn kind(&self) -> ConicKind {
+        self.elements(Angle::zero()).kind()
+    }
+
+    /// Rate the mean anomaly grows at in radians per second, `sqrt(mu / |a|^3)`; for parabolas
+    /// the rate of Barker's `M`, `sqrt(mu / (2 q^3))`.
+    fn mean_motion(&self) -> DBig {
+        let mu = precise(self.gravitational_parameter.clone());
+        let q = self.periapsis_distance.to_meters();
+        let cube = match self.kind() {
+            ConicKind::Parabolic => DBig::from(2) * &q * &q * q,
+            _ => {
+                let a = q / (DBig::ONE - &self.eccentricity).abs();
+                a.clone() * &a * a
+            }
+        };
+        (mu / cube).sqrt()
+    }
+
+    /// Time from periapsis to `true_anomaly`, negative before periapsis. For hyperbolas
+    /// `true_anomaly` has to lie between the asymptotes.
+    pub fn time_since_periapsis(&self, true_anomaly: &Angle) -> Time {
+        let nu = true_anomaly.to_radians();
+        let half = nu.clone() / DBig::from(2);
+        let e = precise(self.eccentricity.clone());
+        let mean_anomaly = match self.kind() {
+            ConicKind::Parabolic => {
+                let d = sin(half.clone(), 32) / cos(half, 32);
+                d.clone() + d.clone() * &d * d / DBig::from(3)
+            }
+            ConicKind::Hyperbolic => {
+                let ratio = ((e.clone() - DBig::ONE) / (e.clone() + DBig::ONE)).sqrt();
+                let h = DBig::from(2) * atanh(ratio * sin(half.clone(), 32) / cos(half, 32));
+                e * sinh(h.clone()) - h
+            }
+            ConicKind::Circular | ConicKind::Elliptic => {
+                let anomaly = DBig::from(2)
+                    * atan2(
+                        (DBig::ONE - &e).sqrt() * sin(half.clone(), 32),
+                        (DBig::ONE + &e).sqrt() * cos(half, 32),
+                        32,
+                    );
+                anomaly.clone() - e * sin(anomaly, 32)
+            }
+        };
+        Time::from_seconds(mean_anomaly / self.mean_motion())
+    }
+
+    /// True anomaly at `time`, in (-pi, pi].
+    pub fn true_anomaly_at(&self, time: &Time) -> Angle {
+        let mean_anomaly = (time - &self.periapsis_time).to_seconds() * self.mean_motion();
+        let e = precise(self.eccentricity.clone());
+        let two = DBig::from(2);
+        let nu = match self.kind() {
+            ConicKind::Parabolic => two * atan(solve_barker(&mean_anomaly), 32),
+            ConicKind::Hyperbolic => {
+                let h = solve_hyperbolic_kepler(&mean_anomaly, &e);
+                let half = h / &two;
+                let tanh_half = sinh(half.clone()) / cosh(half);
+                let ratio = ((e.clone() + DBig::ONE) / (e - DBig::ONE)).sqrt();
+                two * atan(ratio * tanh_half, 32)
+            }
+            ConicKind::Circular | ConicKind::Elliptic => {
+                let half = solve_kepler(&mean_anomaly, &e) / &two;
+                two * atan2(
+                    (DBig::ONE + &e).sqrt() * sin(half.clone(), 32),
+                    (DBig::ONE - &e).sqrt() * cos(half, 32),
+                    32,
+                )
+            }
+        };
+        Angle::from_radians(wrap_signed_angle(nu))
+    }
+
+    /// Osculating elements at `time`.
+    pub fn elements_at(&self, time: &Time) -> OrbitalElements {
+        self.elements(self.true_anomaly_at(time))
+    }
+
+    /// Position and velocity relative to the parent at `time`.
+    pub fn state_at(&self, time: &Time) -> StateVector {
+        self.elements_at(time)
+            .to_state(&self.gravitational_parameter)
+    }
+
+    /// Position relative to the parent at `time`.
+    pub fn position_at(&self, time: &Time) -> DecimalVector3d {
+        self.state_at(time).position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, BodyDynamics, StaticBodyDynamics};
+    use crate::simulation::Simulation;
+    use crate::sin_cos::dbig_to_f64;
+    use crate::units::Mass;
+
+    const GM: f64 = 1.32712440018e20;
+
+    #[test]
+    fn kepler_solvers_satisfy_their_equations() {
+        for i in -12..=12 {
+            let m = i as f64 / 2.0;
+            let mean_anomaly = f64_to_dbig(m);
+            for e in [0.0, 0.3, 0.9, 0.999] {
+                let anomaly = dbig_to_f64(&solve_kepler(&mean_anomaly, &f64_to_dbig(e)));
+                let wrapped = dbig_to_f64(&wrap_signed_angle(mean_anomaly.clone()));
+                assert!(
+                    (anomaly - e * anomaly.sin() - wrapped).abs() < 1e-12,
+                    "{m} {e}"
+                );
+            }
+            for e in [1.001, 1.5, 10.0] {
+                let anomaly = dbig_to_f64(&solve_hyperbolic_kepler(&mean_anomaly, &f64_to_dbig(e)));
+                assert!((e * anomaly.sinh() - anomaly - m).abs() < 1e-12, "{m} {e}");
+            }
+            let d = dbig_to_f64(&solve_barker(&mean_anomaly));
+            assert!((d + d.powi(3) / 3.0 - m).abs() < 1e-12, "{m}");
+        }
+    }
+
+    #[test]
+    fn interstellar_visitor_flies_by() {
+        let mut sim = Simulation::new();
+        let sun = sim.add_hierarchy(
+            Body::new(
+                "Sun",
+                Mass::from_kilograms(f64_to_dbig(1.989e30)),
+                Length::from_meters(f64_to_dbig(6.9634e8)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            ),
+            None,
+        );
+        // roughly 'Oumuamua: q = 0.26 AU, e = 1.2
+        let conic = ConicDynamics {
+            periapsis_distance: Length::from_au(f64_to_dbig(0.26)),
+            eccentricity: f64_to_dbig(1.2),
+            inclination: Angle::from_degrees(DBig::from(123)),
+            longitude_of_ascending_node: Angle::from_degrees(DBig::from(24)),
+            argument_of_periapsis: Angle::from_degrees(DBig::from(241)),
+            periapsis_time: Time::from_days(DBig::from(10)),
+            gravitational_parameter: f64_to_dbig(GM),
+        };
+        let visitor = sim.add_hierarchy(
+            Body::new(
+                "visitor",
+                Mass::from_kilograms(f64_to_dbig(1e9)),
+                Length::from_meters(DBig::from(100)),
+                BodyDynamics::Conic(conic.clone()),
+            ),
+            Some(sun),
+        );
+
+        let mut last = f64::INFINITY;
+        for days in [-300, -100, 0, 10] {
+            sim.update(Time::from_days(DBig::from(days)));
+            let distance = dbig_to_f64(&sim.get_distance(sun, visitor).unwrap().to_meters());
+            assert!(distance < last);
+            last = distance;
+        }
+        let q = dbig_to_f64(&conic.periapsis_distance.to_meters());
+        assert!((last / q - 1.0).abs() < 1e-12, "{last}");
+        sim.update(Time::from_days(DBig::from(400)));
+        assert!(
+            dbig_to_f64(
+                &sim.get_radial_velocity(visitor)
+                    .unwrap()
+                    .to_meters_per_second()
+            ) > 0.0
+        );
+
+        // energy stays at mu / 2|a| above zero and the trajectory comes back from its own state
+        let time = Time::from_days(DBig::from(-50));
+        let state = conic.state_at(&time);
+        let r = dbig_to_f64(&state.position.length());
+        let v = dbig_to_f64(&state.velocity.length());
+        let a = q / (1.2 - 1.0);
+        assert!(((v * v / 2.0 - GM / r) / (GM / (2.0 * a)) - 1.0).abs() < 1e-12);
+        let back = ConicDynamics::from_state(&state, &time, f64_to_dbig(GM)).unwrap();
+        let days = dbig_to_f64(&back.periapsis_time.to_days());
+        assert!((days - 10.0).abs() < 1e-9, "{days}");
+
+        let parabola = ConicDynamics {
+            eccentricity: DBig::ONE,
+            ..conic
+        };
+        assert_eq!(parabola.kind(), ConicKind::Parabolic);
+        let state = parabola.state_at(&time);
+        let r = dbig_to_f64(&state.position.length());
+        let v = dbig_to_f64(&state.velocity.length());
+        assert!((v / (2.0 * GM / r).sqrt() - 1.0).abs() < 1e-12);
+        let back = ConicDynamics::from_state(&state, &time, f64_to_dbig(GM)).unwrap();
+        assert!((dbig_to_f64(&back.periapsis_time.to_days()) - 10.0).abs() < 1e-6);
+    }
+}
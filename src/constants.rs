@@ -0,0 +1,177 @@
+//! Astronomical and physical constants, grouped into the sets published by the IAU so results
+//! can be compared against a chosen reference.
+//!
+//! Gravitational parameters are the measured quantities; masses derived from them inherit the
+//! much larger uncertainty of G, see [`Constants::solar_mass`]. Distances given in astronomical
+//! units or parsecs are converted with [`Constants::length_from_au`] and
+//! [`Constants::parsec`] to follow the set; [`Length::from_au`] is fixed to the IAU 2012 au.
+
+use crate::au::AU_METERS;
+use crate::sin_cos::{precise, PI};
+use crate::units::{Length, Mass, Time, Velocity, AU_PER_PARSEC_NUMERATOR};
+use dashu_float::DBig;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConstantSet {
+    /// The IAU (1976) System of Astronomical Constants, used by DE200-era ephemerides.
+    Iau1976,
+    /// The IAU 2012 astronomical unit with the IAU 2015 nominal solar and planetary values
+    /// (Resolution B3) and the CODATA 2018 gravitational constant.
+    #[default]
+    Iau2012,
+}
+
+#[derive(Debug, Clone)]
+pub struct Constants {
+    pub set: ConstantSet,
+    pub astronomical_unit: Length,
+    pub gravitational_constant: DBig, // in m^3 kg^-1 s^-2
+    pub solar_gm: DBig,               // in m^3 s^-2
+    pub earth_gm: DBig,               // in m^3 s^-2
+    pub jupiter_gm: DBig,             // in m^3 s^-2
+    pub solar_radius: Length,
+    pub earth_equatorial_radius: Length,
+    pub earth_polar_radius: Length,
+    pub jupiter_equatorial_radius: Length,
+    pub speed_of_light: Velocity,
+    pub sidereal_day: Time,
+    pub julian_year: Time,
+}
+
+fn value(text: &str) -> DBig {
+    precise(DBig::from_str(text).unwrap())
+}
+
+impl Constants {
+    pub fn new(set: ConstantSet) -> Constants {
+        match set {
+            ConstantSet::Iau1976 => Constants::iau_1976(),
+            ConstantSet::Iau2012 => Constants::iau_2012(),
+        }
+    }
+
+    pub fn iau_1976() -> Constants {
+        let solar_gm = value("1.32712438e20");
+        let earth_equatorial_radius = value("6378140");
+        Constants {
+            set: ConstantSet::Iau1976,
+            astronomical_unit: Length::from_meters(value("149597870000")),
+            gravitational_constant: value("6.672e-11"),
+            // given as the mass ratio Sun / Jupiter, 1047.355
+            jupiter_gm: solar_gm.clone() / value("1047.355"),
+            solar_gm,
+            earth_gm: value("3.986005e14"),
+            solar_radius: Length::from_meters(value("6.96e8")),
+            // flattening 1 / 298.257
+            earth_polar_radius: Length::from_meters(
+                earth_equatorial_radius.clone() * (DBig::ONE - DBig::ONE / value("298.257")),
+            ),
+            earth_equatorial_radius: Length::from_meters(earth_equatorial_radius),
+            jupiter_equatorial_radius: Length::from_kilometers(value("71398")),
+            speed_of_light: Velocity::from_meters_per_second(value("299792458")),
+            sidereal_day: Time::from_seconds(value("86164.09054")),
+            julian_year: Time::from_days(value("365.25")),
+        }
+    }
+
+    pub fn iau_2012() -> Constants {
+        Constants {
+            set: ConstantSet::Iau2012,
+            astronomical_unit: Length::from_meters(precise(AU_METERS.clone())),
+            gravitational_constant: value("6.67430e-11"),
+            solar_gm: value("1.3271244e20"),
+            earth_gm: value("3.986004e14"),
+            jupiter_gm: value("1.2668653e17"),
+            solar_radius: Length::from_meters(value("6.957e8")),
+            earth_equatorial_radius: Length::from_meters(value("6.3781e6")),
+            earth_polar_radius: Length::from_meters(value("6.3568e6")),
+            jupiter_equatorial_radius: Length::from_meters(value("7.1492e7")),
+            speed_of_light: Velocity::from_meters_per_second(value("299792458")),
+            sidereal_day: Time::from_seconds(value("86164.0905")),
+            julian_year: Time::from_days(value("365.25")),
+        }
+    }
+
+    /// Mass of a body with gravitational parameter `gm`.
+    pub fn mass_from_gm(&self, gm: &DBig) -> Mass {
+        Mass::from_kilograms(gm.clone() / &self.gravitational_constant)
+    }
+
+    /// Gravitational parameter of a body of `mass`.
+    pub fn gm_from_mass(&self, mass: &Mass) -> DBig {
+        mass.to_kilograms() * &self.gravitational_constant
+    }
+
+    /// Length of `au` astronomical units of this set.
+    pub fn length_from_au(&self, au: DBig) -> Length {
+        Length::from_meters(au * self.astronomical_unit.to_meters())
+    }
+
+    pub fn length_to_au(&self, length: &Length) -> DBig {
+        length.to_meters() / self.astronomical_unit.to_meters()
+    }
+
+    /// Distance at which one astronomical unit subtends one arcsecond.
+    pub fn parsec(&self) -> Length {
+        Length::from_meters(
+            self.astronomical_unit.to_meters() * DBig::from(AU_PER_PARSEC_NUMERATOR) / PI.clone(),
+        )
+    }
+
+    pub fn solar_mass(&self) -> Mass {
+        self.mass_from_gm(&self.solar_gm)
+    }
+
+    pub fn earth_mass(&self) -> Mass {
+        self.mass_from_gm(&self.earth_gm)
+    }
+
+    pub fn jupiter_mass(&self) -> Mass {
+        self.mass_from_gm(&self.jupiter_gm)
+    }
+}
+
+impl Default for Constants {
+    fn default() -> Self {
+        Constants::new(ConstantSet::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sin_cos::dbig_to_f64;
+
+    #[test]
+    fn selects_constant_sets() {
+        let current = Constants::default();
+        assert_eq!(current.set, ConstantSet::Iau2012);
+        assert_eq!(
+            dbig_to_f64(&current.astronomical_unit.to_meters()),
+            149597870700.0
+        );
+        assert!((dbig_to_f64(&current.solar_mass().to_kilograms()) / 1.9884e30 - 1.0).abs() < 1e-4);
+        assert!(
+            (dbig_to_f64(&current.jupiter_mass().to_kilograms()) / 1.8982e27 - 1.0).abs() < 1e-4
+        );
+
+        let old = Constants::new(ConstantSet::Iau1976);
+        let difference = &current.astronomical_unit - &old.astronomical_unit;
+        assert_eq!(dbig_to_f64(&difference.to_meters()), 700.0);
+        assert!((dbig_to_f64(&old.earth_polar_radius.to_meters()) - 6356755.288).abs() < 1e-3);
+        assert!((dbig_to_f64(&old.jupiter_gm) / 1.26712e17 - 1.0).abs() < 1e-5);
+        assert_eq!(
+            dbig_to_f64(&(&old.speed_of_light * &old.sidereal_day).to_light_seconds()),
+            86164.09054
+        );
+        let au = dbig_to_f64(&old.length_from_au(DBig::from(2)).to_meters());
+        assert_eq!(au, 299195740000.0);
+        let au = old.length_to_au(&Length::from_au(DBig::ONE));
+        assert!((dbig_to_f64(&au) - 1.0 - 700.0 / 149597870000.0).abs() < 1e-15);
+        let parsec = dbig_to_f64(&current.parsec().to_meters());
+        assert!((parsec / dbig_to_f64(&old.parsec().to_meters()) - 1.0) > 4.6e-9);
+        let gm = current.gm_from_mass(&current.earth_mass());
+        assert!((dbig_to_f64(&gm) - 3.986004e14).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,709 @@
+//! Circular restricted three-body problem for a body and its parent, for prototyping
+//! trajectories near moons without the full simulation.
+//!
+//! States live in the canonical rotating frame: the origin is the pair's barycenter, x points
+//! from the parent towards the body, z along the pair's angular momentum, and lengths and times
+//! are in units of their separation and inverse mean motion. The parent sits at `(-mu, 0, 0)`
+//! and the body at `(1 - mu, 0, 0)`, `mu` being the body's share of the total mass.
+
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::events::find_root;
+use crate::lagrange::{collinear_distance, LagrangePoint};
+use crate::reference_frame::StateVector;
+use crate::simulation::Simulation;
+use crate::sin_cos::{cos, dbig_to_f64, f64_to_dbig, precise, sin};
+use crate::units::{Length, Time};
+use dashu_float::ops::{Abs, SquareRoot};
+use dashu_float::DBig;
+
+/// Largest integration step, in canonical time units.
+const STEP: f64 = 0.01;
+/// Longest half revolution searched for a periodic orbit, in canonical time units.
+const MAX_HALF_PERIOD: f64 = 10.0;
+/// Differential correction stops once the targeted velocities at the half period are below this.
+const CORRECTION_TOLERANCE: f64 = 1e-10;
+const MAX_CORRECTIONS: usize = 25;
+/// Newton iterations refining the plane crossing that ends a half period.
+const CROSSING_ITERATIONS: usize = 6;
+
+/// The circular restricted three-body problem of `secondary` orbiting `primary`.
+#[derive(Debug, Clone)]
+pub struct Cr3bp {
+    pub primary: i32,
+    pub secondary: i32,
+    /// The secondary's share of the total mass, `mu`.
+    pub mass_ratio: DBig,
+    /// Separation of the pair.
+    pub length_unit: Length,
+    /// Inverse of the pair's mean motion.
+    pub time_unit: Time,
+    /// Time the rotating x axis points at the secondary along `axes[0]`.
+    pub epoch: Time,
+    /// Rotating frame axes at `epoch`, in simulation coordinates.
+    axes: [DecimalVector3d; 3],
+}
+
+/// A periodic orbit in the rotating frame, symmetric about the x-z plane.
+#[derive(Debug, Clone)]
+pub struct PeriodicOrbit {
+    /// State crossing the x-z plane, with only y and possibly z velocity.
+    pub initial: StateVector,
+    /// In canonical time units.
+    pub period: DBig,
+}
+
+impl Cr3bp {
+    /// `2 U - v^2`, constant along any trajectory, `U` being the
+    /// [`Cr3bp::effective_potential`].
+    pub fn jacobi_constant(&self, state: &StateVector) -> DBig {
+        DBig::from(2) * self.effective_potential(&state.position) - state.velocity.length_squared()
+    }
+
+    /// Gravitational plus centrifugal potential per unit mass at `position`, positive.
+    pub fn effective_potential(&self, position: &DecimalVector3d) -> DBig {
+        let mu = &self.mass_ratio;
+        let (to_primary, to_secondary) = self.distances(position);
+        (position.x.clone() * &position.x + position.y.clone() * &position.y) / DBig::from(2)
+            + (DBig::ONE - mu) / to_primary
+            + mu.clone() / to_secondary
+    }
+
+    /// Acceleration of a massless particle with `state` as seen in the rotating frame,
+    /// including the Coriolis and centrifugal terms.
+    pub fn acceleration(&self, state: &StateVector) -> DecimalVector3d {
+        let mu = &self.mass_ratio;
+        let (position, velocity) = (&state.position, &state.velocity);
+        let (to_primary, to_secondary) = self.distances(position);
+        let primary = (DBig::ONE - mu) / (to_primary.clone() * &to_primary * to_primary);
+        let secondary = mu.clone() / (to_secondary.clone() * &to_secondary * to_secondary);
+        let gravity = primary.clone() + &secondary;
+        DecimalVector3d::new(
+            DBig::from(2) * &velocity.y + &position.x
+                - primary * (position.x.clone() + mu)
+                - secondary * (position.x.clone() - DBig::ONE + mu),
+            -DBig::from(2) * &velocity.x + &position.y - gravity.clone() * &position.y,
+            -gravity * &position.z,
+        )
+    }
+
+    /// Canonical position of Lagrange point `point`.
+    pub fn lagrange_point(&self, point: LagrangePoint) -> Option<DecimalVector3d> {
+        let mu = &self.mass_ratio;
+        let x = match point {
+            LagrangePoint::L4 | LagrangePoint::L5 => {
+                let y = precise(DBig::from(3)).sqrt() / DBig::from(2);
+                let y = if point == LagrangePoint::L4 { y } else { -y };
+                return Some(DecimalVector3d::new(f64_to_dbig(0.5) - mu, y, DBig::ZERO));
+            }
+            _ => collinear_distance(point, mu)? - mu,
+        };
+        Some(DecimalVector3d::new(x, DBig::ZERO, DBig::ZERO))
+    }
+
+    /// State reached from `state` after `duration` canonical time units, which may be negative.
+    pub fn propagate(&self, state: &StateVector, duration: &DBig) -> StateVector {
+        let steps = (dbig_to_f64(&duration.clone().abs()) / STEP)
+            .ceil()
+            .max(1.0) as usize;
+        let step = duration.clone() / DBig::from(steps);
+        let mut y = flatten(state);
+        for _ in 0..steps {
+            y = self.rk4(&y, &step);
+        }
+        unflatten(&y)
+    }
+
+    /// Planar orbit around collinear point `point` whose x extent from the point is about
+    /// `amplitude`, in units of the separation. Starts from the linearised motion around the
+    /// point and corrects the y velocity until the orbit closes. `None` for L4 and L5 or if the
+    /// correction diverges.
+    pub fn lyapunov_orbit(&self, point: LagrangePoint, amplitude: &DBig) -> Option<PeriodicOrbit> {
+        let position = self.lagrange_point(point)?;
+        if matches!(point, LagrangePoint::L4 | LagrangePoint::L5) {
+            return None;
+        }
+        let (to_primary, to_secondary) = self.distances(&position);
+        let mu = &self.mass_ratio;
+        let c2 = (DBig::ONE - mu) / (to_primary.clone() * &to_primary * to_primary)
+            + mu.clone() / (to_secondary.clone() * &to_secondary * to_secondary);
+        // in-plane frequency and y to x amplitude ratio of the oscillating linear solution
+        let discriminant = (DBig::from(9) * &c2 * &c2 - DBig::from(8) * &c2).sqrt();
+        let frequency = ((DBig::from(2) - &c2 + discriminant) / DBig::from(2)).sqrt();
+        let ratio = (frequency.clone() * &frequency + DBig::ONE + DBig::from(2) * c2)
+            / (DBig::from(2) * &frequency);
+        let guess = StateVector::new(
+            DecimalVector3d::new(position.x - amplitude, DBig::ZERO, DBig::ZERO),
+            DecimalVector3d::new(DBig::ZERO, ratio * frequency * amplitude, DBig::ZERO),
+        );
+        self.correct(guess, &[4], &[3])
+    }
+
+    /// Three-dimensional orbit around collinear point `point` reaching about `amplitude` out of
+    /// the orbital plane, in units of the separation, above it for positive amplitudes and
+    /// below for negative ones. Starts from Richardson's third-order approximation and corrects
+    /// the x position and y velocity until the orbit closes, keeping z. `None` for L4 and L5,
+    /// if the approximation has no halo orbit of that amplitude or if the correction diverges.
+    pub fn halo_orbit(&self, point: LagrangePoint, amplitude: &DBig) -> Option<PeriodicOrbit> {
+        let guess = self.richardson_halo(point, dbig_to_f64(amplitude))?;
+        self.correct(guess, &[0, 4], &[3, 5])
+    }
+
+    /// Crossing of the x-z plane of the halo orbit around `point` with z amplitude `amplitude`
+    /// after Richardson, "Analytic construction of periodic orbits about the collinear points"
+    /// (1980). The expansion is in units of `gamma`, the distance from the point to the nearer
+    /// primary, with the axes of the rotating frame. It only seeds the correction, so f64 is
+    /// plenty.
+    fn richardson_halo(&self, point: LagrangePoint, amplitude: f64) -> Option<StateVector> {
+        let mu = dbig_to_f64(&self.mass_ratio);
+        let center = dbig_to_f64(&self.lagrange_point(point)?.x);
+        let gamma = match point {
+            LagrangePoint::L1 => 1.0 - mu - center,
+            LagrangePoint::L2 => center - 1.0 + mu,
+            LagrangePoint::L3 => -mu - center,
+            LagrangePoint::L4 | LagrangePoint::L5 => return None,
+        };
+        // Legendre coefficients of the potential expanded around the point
+        let c = |n: i32| {
+            let sign = if n % 2 == 0 { 1.0 } else { -1.0 };
+            let power = n + 1;
+            let value = match point {
+                LagrangePoint::L1 => mu + sign * (1.0 - mu) * (gamma / (1.0 - gamma)).powi(power),
+                LagrangePoint::L2 => sign * (mu + (1.0 - mu) * (gamma / (1.0 + gamma)).powi(power)),
+                _ => 1.0 - mu + mu * (gamma / (1.0 + gamma)).powi(power),
+            };
+            value / gamma.powi(3)
+        };
+        let (c2, c3, c4) = (c(2), c(3), c(4));
+
+        // linear in-plane frequency, y to x amplitude ratio and out-of-plane detuning
+        let lambda = ((2.0 - c2 + (9.0 * c2 * c2 - 8.0 * c2).sqrt()) / 2.0).sqrt();
+        let l2 = lambda * lambda;
+        let k = (l2 + 1.0 + 2.0 * c2) / (2.0 * lambda);
+        let delta = l2 - c2;
+
+        let d1 = 3.0 * l2 / k * (k * (6.0 * l2 - 1.0) - 2.0 * lambda);
+        let d2 = 8.0 * l2 / k * (k * (11.0 * l2 - 1.0) - 2.0 * lambda);
+        let a21 = 3.0 * c3 * (k * k - 2.0) / (4.0 * (1.0 + 2.0 * c2));
+        let a22 = 3.0 * c3 / (4.0 * (1.0 + 2.0 * c2));
+        let a23 = -3.0 * c3 * lambda / (4.0 * k * d1)
+            * (3.0 * k.powi(3) * lambda - 6.0 * k * (k - lambda) + 4.0);
+        let a24 = -3.0 * c3 * lambda / (4.0 * k * d1) * (2.0 + 3.0 * k * lambda);
+        let b21 = -3.0 * c3 * lambda / (2.0 * d1) * (3.0 * k * lambda - 4.0);
+        let b22 = 3.0 * c3 * lambda / d1;
+        let d21 = -c3 / (2.0 * l2);
+
+        let a31 = -9.0 * lambda / (4.0 * d2)
+            * (4.0 * c3 * (k * a23 - b21) + k * c4 * (4.0 + k * k))
+            + (9.0 * l2 + 1.0 - c2) / (2.0 * d2)
+                * (3.0 * c3 * (2.0 * a23 - k * b21) + c4 * (2.0 + 3.0 * k * k));
+        let a32 = -1.0 / d2
+            * (9.0 * lambda / 4.0 * (4.0 * c3 * (k * a24 - b22) + k * c4)
+                + 1.5 * (9.0 * l2 + 1.0 - c2) * (c3 * (k * b22 + d21 - 2.0 * a24) - c4));
+        let b31 = 3.0 / (8.0 * d2)
+            * (8.0 * lambda * (3.0 * c3 * (k * b21 - 2.0 * a23) - c4 * (2.0 + 3.0 * k * k))
+                + (9.0 * l2 + 1.0 + 2.0 * c2)
+                    * (4.0 * c3 * (k * a23 - b21) + k * c4 * (4.0 + k * k)));
+        let b32 = 1.0 / d2
+            * (9.0 * lambda * (c3 * (k * b22 + d21 - 2.0 * a24) - c4)
+                + 3.0 / 8.0 * (9.0 * l2 + 1.0 + 2.0 * c2) * (4.0 * c3 * (k * a24 - b22) + k * c4));
+        let d31 = 3.0 / (64.0 * l2) * (4.0 * c3 * a24 + c4);
+        let d32 = 3.0 / (64.0 * l2) * (4.0 * c3 * (a23 - d21) + c4 * (4.0 + k * k));
+
+        // frequency corrections and the amplitude constraint tying x to z
+        let s = 1.0 / (2.0 * lambda * (lambda * (1.0 + k * k) - 2.0 * k));
+        let s1 = s
+            * (1.5 * c3 * (2.0 * a21 * (k * k - 2.0) - a23 * (k * k + 2.0) - 2.0 * k * b21)
+                - 3.0 / 8.0 * c4 * (3.0 * k.powi(4) - 8.0 * k * k + 8.0));
+        let s2 = s
+            * (1.5
+                * c3
+                * (2.0 * a22 * (k * k - 2.0) + a24 * (k * k + 2.0) + 2.0 * k * b22 + 5.0 * d21)
+                + 3.0 / 8.0 * c4 * (12.0 - k * k));
+        let a1 = -1.5 * c3 * (2.0 * a21 + a23 + 5.0 * d21) - 3.0 / 8.0 * c4 * (12.0 - k * k);
+        let a2 = 1.5 * c3 * (a24 - 2.0 * a22) + 9.0 / 8.0 * c4;
+        let l1 = a1 + 2.0 * l2 * s1;
+        let l2 = a2 + 2.0 * l2 * s2;
+
+        let az = amplitude / gamma;
+        let ax_squared = (-l2 * az * az - delta) / l1;
+        if ax_squared.is_nan() || ax_squared <= 0.0 {
+            return None;
+        }
+        let ax = ax_squared.sqrt();
+        let frequency = lambda * (1.0 + s1 * ax * ax + s2 * az * az);
+
+        // the series at zero phase, on the x-z plane and moving along y only
+        let x = a21 * ax * ax + a22 * az * az - ax
+            + (a23 * ax * ax - a24 * az * az)
+            + (a31 * ax.powi(3) - a32 * ax * az * az);
+        let z = az - 2.0 * d21 * ax * az + (d32 * az * ax * ax - d31 * az.powi(3));
+        let vy = frequency
+            * (k * ax
+                + 2.0 * (b21 * ax * ax - b22 * az * az)
+                + 3.0 * (b31 * ax.powi(3) - b32 * ax * az * az));
+        Some(StateVector::new(
+            DecimalVector3d::from_f64(center + gamma * x, 0.0, gamma * z),
+            DecimalVector3d::from_f64(0.0, gamma * vy, 0.0),
+        ))
+    }
+
+    /// Points of the zero-velocity curves of `jacobi` in the x-y plane, where a particle with
+    /// that Jacobi constant comes to rest. Scans `samples` rows and columns of the square of
+    /// half-width `extent` around the barycenter for sign changes of `2 U - jacobi`, skipping
+    /// the nodes on a primary, where `U` is singular. Empty without samples.
+    pub fn zero_velocity_curve(
+        &self,
+        jacobi: &DBig,
+        extent: &DBig,
+        samples: usize,
+    ) -> Vec<DecimalVector3d> {
+        if samples == 0 {
+            return vec![];
+        }
+        let excess = |x: &DBig, y: &DBig| {
+            let from_primary = x.clone() + &self.mass_ratio;
+            if *y == DBig::ZERO && (from_primary == DBig::ZERO || from_primary == DBig::ONE) {
+                return None;
+            }
+            let position = DecimalVector3d::new(x.clone(), y.clone(), DBig::ZERO);
+            Some(DBig::from(2) * self.effective_potential(&position) - jacobi)
+        };
+        let coordinate = |i: usize| {
+            extent.clone() * DBig::from(2 * i as i64 - samples as i64) / DBig::from(samples)
+        };
+        let tolerance = extent.clone() * f64_to_dbig(1e-9);
+        let mut points = vec![];
+        for row in 0..=samples {
+            let y = coordinate(row);
+            let mut previous: Option<(DBig, DBig)> = None;
+            for column in 0..=samples {
+                let x = coordinate(column);
+                let Some(value) = excess(&x, &y) else {
+                    continue;
+                };
+                if let Some((previous_x, previous_value)) = previous {
+                    if previous_value.clone() * &value < DBig::ZERO {
+                        let root = find_root(
+                            |x| excess(x, &y),
+                            (previous_x, previous_value),
+                            (x.clone(), value.clone()),
+                            &tolerance,
+                        );
+                        if let Some(root) = root {
+                            points.push(DecimalVector3d::new(root, y.clone(), DBig::ZERO));
+                        }
+                    }
+                }
+                previous = Some((x, value));
+            }
+        }
+        points
+    }
+
+    /// Canonical rotating state of `state`, given relative to the primary in simulation
+    /// coordinates at `time`.
+    pub fn to_rotating(&self, time: &Time, state: &StateVector) -> StateVector {
+        let [x, y, z] = self.axes_at(time);
+        let speed_unit = self.length_unit.to_meters() / self.time_unit.to_seconds();
+        let position = state.position.clone() / self.length_unit.to_meters();
+        let velocity = state.velocity.clone() / speed_unit;
+        let (px, py) = (position.dot(x.clone()), position.dot(y.clone()));
+        StateVector::new(
+            DecimalVector3d::new(
+                px.clone() - &self.mass_ratio,
+                py.clone(),
+                position.dot(z.clone()),
+            ),
+            DecimalVector3d::new(velocity.dot(x) + py, velocity.dot(y) - px, velocity.dot(z)),
+        )
+    }
+
+    /// State relative to the primary in simulation coordinates at `time` of canonical rotating
+    /// `state`, the inverse of [`Cr3bp::to_rotating`].
+    pub fn from_rotating(&self, time: &Time, state: &StateVector) -> StateVector {
+        let [x, y, z] = self.axes_at(time);
+        let speed_unit = self.length_unit.to_meters() / self.time_unit.to_seconds();
+        let px = state.position.x.clone() + &self.mass_ratio;
+        let py = state.position.y.clone();
+        let position = x.clone() * &px + y.clone() * &py + z.clone() * &state.position.z;
+        let velocity = x * (state.velocity.x.clone() - py)
+            + y * (state.velocity.y.clone() + px)
+            + z * &state.velocity.z;
+        StateVector::new(
+            position * self.length_unit.to_meters(),
+            velocity * speed_unit,
+        )
+    }
+
+    fn axes_at(&self, time: &Time) -> [DecimalVector3d; 3] {
+        let angle = (time - &self.epoch).to_seconds() / self.time_unit.to_seconds();
+        let (sin, cos) = (sin(angle.clone(), 32), cos(angle, 32));
+        let [x, y, z] = &self.axes;
+        [
+            x.clone() * &cos + y.clone() * &sin,
+            y.clone() * &cos - x.clone() * &sin,
+            z.clone(),
+        ]
+    }
+
+    fn distances(&self, position: &DecimalVector3d) -> (DBig, DBig) {
+        let mu = &self.mass_ratio;
+        let rest = position.y.clone() * &position.y + position.z.clone() * &position.z;
+        let to_primary = position.x.clone() + mu;
+        let to_secondary = position.x.clone() - DBig::ONE + mu;
+        (
+            (to_primary.clone() * &to_primary + &rest).sqrt(),
+            (to_secondary.clone() * &to_secondary + rest).sqrt(),
+        )
+    }
+
+    /// Second derivatives of the effective potential.
+    fn hessian(&self, position: &DecimalVector3d) -> [[DBig; 3]; 3] {
+        let mu = &self.mass_ratio;
+        let (to_primary, to_secondary) = self.distances(position);
+        let cube = |d: &DBig| d.clone() * d * d;
+        let primary = (DBig::ONE - mu) / cube(&to_primary);
+        let secondary = mu.clone() / cube(&to_secondary);
+        let primary5 = DBig::from(3) * &primary / (to_primary.clone() * &to_primary);
+        let secondary5 = DBig::from(3) * &secondary / (to_secondary.clone() * &to_secondary);
+        let offsets = [
+            [
+                position.x.clone() + mu,
+                position.y.clone(),
+                position.z.clone(),
+            ],
+            [
+                position.x.clone() - DBig::ONE + mu,
+                position.y.clone(),
+                position.z.clone(),
+            ],
+        ];
+        std::array::from_fn(|i| {
+            std::array::from_fn(|j| {
+                let mut value = primary5.clone() * &offsets[0][i] * &offsets[0][j]
+                    + secondary5.clone() * &offsets[1][i] * &offsets[1][j];
+                if i == j {
+                    value -= primary.clone() + &secondary;
+                    if i < 2 {
+                        value += DBig::ONE;
+                    }
+                }
+                value
+            })
+        })
+    }
+
+    /// Time derivative of a flattened state, followed by its row-major state transition matrix
+    /// when `y` holds one.
+    fn derivative(&self, y: &[DBig]) -> Vec<DBig> {
+        let state = unflatten(y);
+        let acceleration = self.acceleration(&state);
+        let mut derivative = flatten(&StateVector::new(state.velocity, acceleration));
+        if y.len() == 6 {
+            return derivative;
+        }
+        let stm = |i: usize, j: usize| &y[6 + 6 * i + j];
+        let hessian = self.hessian(&state.position);
+        for i in 0..3 {
+            derivative.extend((0..6).map(|j| stm(i + 3, j).clone()));
+        }
+        for (i, row) in hessian.iter().enumerate() {
+            derivative.extend((0..6).map(|j| {
+                let mut value = (0..3)
+                    .map(|k| row[k].clone() * stm(k, j))
+                    .fold(DBig::ZERO, |sum, term| sum + term);
+                match i {
+                    0 => value += DBig::from(2) * stm(4, j),
+                    1 => value -= DBig::from(2) * stm(3, j),
+                    _ => {}
+                }
+                value
+            }));
+        }
+        derivative
+    }
+
+    fn rk4(&self, y: &[DBig], step: &DBig) -> Vec<DBig> {
+        let half = step.clone() / DBig::from(2);
+        let advance = |k: &[DBig], by: &DBig| -> Vec<DBig> {
+            y.iter()
+                .zip(k)
+                .map(|(y, k)| y.clone() + by.clone() * k)
+                .collect()
+        };
+        let k1 = self.derivative(y);
+        let k2 = self.derivative(&advance(&k1, &half));
+        let k3 = self.derivative(&advance(&k2, &half));
+        let k4 = self.derivative(&advance(&k3, step));
+        let sixth = step.clone() / DBig::from(6);
+        (0..y.len())
+            .map(|i| {
+                y[i].clone()
+                    + sixth.clone()
+                        * (k1[i].clone() + DBig::from(2) * (k2[i].clone() + &k3[i]) + &k4[i])
+            })
+            .collect()
+    }
+
+    /// Integrates `state` with its state transition matrix up to its next crossing of the x-z
+    /// plane, returning the flattened state there and the time taken.
+    fn half_revolution(&self, state: &StateVector) -> Option<(Vec<DBig>, DBig)> {
+        let mut y = flatten(state);
+        y.extend((0..36).map(|i| if i % 7 == 0 { DBig::ONE } else { DBig::ZERO }));
+        let step = f64_to_dbig(STEP);
+        let mut elapsed = DBig::ZERO;
+        while elapsed < f64_to_dbig(MAX_HALF_PERIOD) {
+            let next = self.rk4(&y, &step);
+            if next[1].clone() * &y[1] >= DBig::ZERO {
+                y = next;
+                elapsed += &step;
+                continue;
+            }
+            // Newton's method on the y coordinate from the last step before the crossing
+            let mut partial = step.clone() * &y[1] / (y[1].clone() - &next[1]);
+            let mut end = self.rk4(&y, &partial);
+            for _ in 0..CROSSING_ITERATIONS {
+                partial -= end[1].clone() / &end[4];
+                end = self.rk4(&y, &partial);
+            }
+            return Some((end, elapsed + partial));
+        }
+        None
+    }
+
+    /// Differential correction of `guess`, varying the components `free` of its flattened
+    /// state until the components `targets` vanish at the next x-z plane crossing.
+    fn correct(
+        &self,
+        mut guess: StateVector,
+        free: &[usize],
+        targets: &[usize],
+    ) -> Option<PeriodicOrbit> {
+        for _ in 0..MAX_CORRECTIONS {
+            let (end, half_period) = self.half_revolution(&guess)?;
+            let errors: Vec<DBig> = targets.iter().map(|&t| end[t].clone()).collect();
+            if errors
+                .iter()
+                .all(|error| error.clone().abs() < f64_to_dbig(CORRECTION_TOLERANCE))
+            {
+                return Some(PeriodicOrbit {
+                    initial: guess,
+                    period: half_period * DBig::from(2),
+                });
+            }
+            // sensitivities at the crossing, with the crossing time free to move
+            let rates = self.derivative(&end[..6]);
+            let stm = |i: usize, j: usize| end[6 + 6 * i + j].clone();
+            let jacobian: Vec<Vec<DBig>> = targets
+                .iter()
+                .map(|&t| {
+                    free.iter()
+                        .map(|&f| stm(t, f) - rates[t].clone() / &end[4] * stm(1, f))
+                        .collect()
+                })
+                .collect();
+            let corrections = match free.len() {
+                1 => vec![errors[0].clone() / &jacobian[0][0]],
+                _ => {
+                    let [a, b] = [&jacobian[0][0], &jacobian[0][1]];
+                    let [c, d] = [&jacobian[1][0], &jacobian[1][1]];
+                    let determinant = a.clone() * d - b.clone() * c;
+                    vec![
+                        (d.clone() * &errors[0] - b.clone() * &errors[1]) / &determinant,
+                        (a.clone() * &errors[1] - c.clone() * &errors[0]) / determinant,
+                    ]
+                }
+            };
+            let mut y = flatten(&guess);
+            for (&f, correction) in free.iter().zip(corrections) {
+                y[f] -= correction;
+            }
+            guess = unflatten(&y);
+        }
+        None
+    }
+}
+
+fn flatten(state: &StateVector) -> Vec<DBig> {
+    let (p, v) = (&state.position, &state.velocity);
+    vec![
+        p.x.clone(),
+        p.y.clone(),
+        p.z.clone(),
+        v.x.clone(),
+        v.y.clone(),
+        v.z.clone(),
+    ]
+}
+
+fn unflatten(y: &[DBig]) -> StateVector {
+    StateVector::new(
+        DecimalVector3d::new(y[0].clone(), y[1].clone(), y[2].clone()),
+        DecimalVector3d::new(y[3].clone(), y[4].clone(), y[5].clone()),
+    )
+}
+
+impl Simulation {
+    /// Restricted three-body problem of `secondary` and its parent `primary`, with the rotating
+    /// frame aligned to their positions and the separation measured at the current time.
+    /// `None` unless `primary` is the parent of `secondary` or if both are massless.
+    pub fn cr3bp(&self, primary: i32, secondary: i32) -> Option<Cr3bp> {
+        if self.get_body_by_id(secondary)?.parent()? != primary {
+            return None;
+        }
+        let (mass, primary_mass) = (self.central_mass(secondary)?, self.central_mass(primary)?);
+        let total = mass.clone() + &primary_mass;
+        let gm = self.constants().gm_from_mass(&total);
+        if gm == DBig::ZERO {
+            return None;
+        }
+        let state = self.relative_state_at(self.time(), secondary);
+        let distance = state.position.length();
+        let x = state.position.normalized();
+        let z = state.position.cross(state.velocity).normalized();
+        let y = z.cross(x.clone());
+        let mean_motion = (precise(gm) / (distance.clone() * &distance * &distance)).sqrt();
+        Some(Cr3bp {
+            primary,
+            secondary,
+            mass_ratio: &mass / &total,
+            length_unit: Length::from_meters(distance),
+            time_unit: Time::from_seconds(DBig::ONE / mean_motion),
+            epoch: self.time().clone(),
+            axes: [x, y, z],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics};
+    use crate::units::{Angle, Mass};
+
+    fn earth_moon() -> (Simulation, Cr3bp) {
+        let body = |name: &str, mass: f64, dynamics| {
+            Body::new(
+                name,
+                Mass::from_kilograms(f64_to_dbig(mass)),
+                Length::from_kilometers(DBig::from(1737)),
+                dynamics,
+            )
+        };
+        let moon = body(
+            "Moon",
+            7.342e22,
+            BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                orbit_radius: Length::from_kilometers(DBig::from(384400)),
+                orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                orbit_period: Time::from_days(f64_to_dbig(27.321661)),
+                orbit_phase: Angle::zero(),
+            }),
+        );
+        let earth = body(
+            "Earth",
+            5.972e24,
+            BodyDynamics::Static(StaticBodyDynamics {
+                position: DecimalVector3d::zero(),
+            }),
+        );
+        let mut sim = Simulation::new();
+        let earth = sim.add_hierarchy(
+            Body {
+                satellites: vec![moon],
+                ..earth
+            },
+            None,
+        );
+        sim.update(Time::from_days(DBig::from(3)));
+        let cr3bp = sim.cr3bp(earth, earth + 1).unwrap();
+        (sim, cr3bp)
+    }
+
+    fn distance(a: &StateVector, b: &StateVector) -> f64 {
+        dbig_to_f64(&a.position.distance_to(b.position.clone()))
+            + dbig_to_f64(&a.velocity.distance_to(b.velocity.clone()))
+    }
+
+    #[test]
+    fn rotating_frame_and_zero_velocity_curves() {
+        let (sim, cr3bp) = earth_moon();
+        assert!((dbig_to_f64(&cr3bp.mass_ratio) - 0.012151).abs() < 1e-5);
+        // about 4.34 days per canonical time unit
+        assert!((dbig_to_f64(&cr3bp.time_unit.to_days()) / 4.34 - 1.0).abs() < 1e-2);
+
+        let moon = cr3bp.to_rotating(
+            sim.time(),
+            &sim.relative_state_at(sim.time(), cr3bp.secondary),
+        );
+        assert!((dbig_to_f64(&moon.position.x) - (1.0 - 0.012151)).abs() < 1e-5);
+        assert!(dbig_to_f64(&moon.position.y).abs() < 1e-9);
+        let later = sim.time().clone() + Time::from_days(DBig::from(5));
+        let state = StateVector::new(
+            DecimalVector3d::from_f64(0.5, -0.2, 0.1),
+            DecimalVector3d::from_f64(0.01, 0.3, -0.02),
+        );
+        let round_trip = cr3bp.to_rotating(&later, &cr3bp.from_rotating(&later, &state));
+        assert!(distance(&round_trip, &state) < 1e-20);
+
+        // just above the energy of L1 the curves close the neck around it on both sides
+        let l1 = cr3bp.lagrange_point(LagrangePoint::L1).unwrap();
+        let jacobi = cr3bp.jacobi_constant(&StateVector::new(l1.clone(), DecimalVector3d::zero()))
+            + f64_to_dbig(0.01);
+        let curve = cr3bp.zero_velocity_curve(&jacobi, &f64_to_dbig(1.2), 96);
+        assert!(!curve.is_empty());
+        for point in &curve {
+            let value = DBig::from(2) * cr3bp.effective_potential(point) - &jacobi;
+            assert!(dbig_to_f64(&value).abs() < 1e-6);
+        }
+        let neck = curve
+            .iter()
+            .filter(|point| point.y == DBig::ZERO)
+            .map(|point| dbig_to_f64(&(point.x.clone() - &l1.x)))
+            .filter(|offset| offset.abs() < 0.05)
+            .collect::<Vec<_>>();
+        assert_eq!(neck.len(), 2);
+        assert!(neck[0] < 0.0 && neck[1] > 0.0);
+
+        assert!(cr3bp
+            .zero_velocity_curve(&jacobi, &f64_to_dbig(1.2), 0)
+            .is_empty());
+        // equal masses put both primaries on grid nodes at x = -0.5 and 0.5
+        let equal = Cr3bp {
+            mass_ratio: f64_to_dbig(0.5),
+            ..cr3bp
+        };
+        let curve = equal.zero_velocity_curve(&f64_to_dbig(3.8), &DBig::ONE, 4);
+        assert!(!curve.is_empty());
+    }
+
+    #[test]
+    fn lyapunov_and_halo_orbits() {
+        let (_, cr3bp) = earth_moon();
+        let lyapunov = cr3bp
+            .lyapunov_orbit(LagrangePoint::L1, &f64_to_dbig(0.01))
+            .unwrap();
+        let closed = cr3bp.propagate(&lyapunov.initial, &lyapunov.period);
+        assert!(distance(&closed, &lyapunov.initial) < 1e-6);
+        let drift = cr3bp.jacobi_constant(&closed) - cr3bp.jacobi_constant(&lyapunov.initial);
+        assert!(dbig_to_f64(&drift).abs() < 1e-9);
+        // small planar orbits take about 2 pi / 2.334 time units, lengthening with amplitude
+        assert!((dbig_to_f64(&lyapunov.period) - 2.69).abs() < 0.05);
+
+        // a northern L1 halo, the approximation already close to the corrected orbit
+        let halo = cr3bp
+            .halo_orbit(LagrangePoint::L1, &f64_to_dbig(0.02))
+            .unwrap();
+        assert!((dbig_to_f64(&halo.initial.position.x) - 0.82341).abs() < 1e-3);
+        assert!((dbig_to_f64(&halo.period) - 2.7461).abs() < 1e-2);
+        let guess = cr3bp.richardson_halo(LagrangePoint::L1, 0.02).unwrap();
+        assert!(distance(&guess, &halo.initial) < 2e-3);
+        assert_eq!(guess.position.z, halo.initial.position.z);
+        let southern = cr3bp.richardson_halo(LagrangePoint::L1, -0.02).unwrap();
+        assert_eq!(southern.position.z, -guess.position.z);
+        let half = cr3bp.propagate(&halo.initial, &(halo.period.clone() / DBig::from(2)));
+        assert!(dbig_to_f64(&half.position.y).abs() < 1e-6);
+        assert!(dbig_to_f64(&half.velocity.x).abs() < 1e-6);
+        assert!(dbig_to_f64(&half.velocity.z).abs() < 1e-6);
+    }
+}
@@ -22,6 +22,29 @@ impl DecimalMatrix3d {
         }
     }
 
+    /// Builds the rotation taking the global x, y and z axes onto `x`, `y` and `z`, so
+    /// `apply` maps coordinates expressed in that basis into global coordinates.
+    pub fn from_basis(
+        x: DecimalVector3d,
+        y: DecimalVector3d,
+        z: DecimalVector3d,
+    ) -> DecimalMatrix3d {
+        DecimalMatrix3d {
+            data: [[x.x, x.y, x.z], [y.x, y.y, y.z], [z.x, z.y, z.z]],
+        }
+    }
+
+    pub fn transposed(&self) -> DecimalMatrix3d {
+        let d = &self.data;
+        DecimalMatrix3d {
+            data: [
+                [d[0][0].clone(), d[1][0].clone(), d[2][0].clone()],
+                [d[0][1].clone(), d[1][1].clone(), d[2][1].clone()],
+                [d[0][2].clone(), d[1][2].clone(), d[2][2].clone()],
+            ],
+        }
+    }
+
     pub fn axis_angle(axis: DecimalVector3d, angle: DBig) -> DecimalMatrix3d {
         // angle is negated to match the Three JS behavior, no idea why
         let c = &cos(-angle.clone(), 32);
@@ -67,12 +90,27 @@ impl DecimalMatrix3d {
         }
     }
 
+    /// `None` for singular matrices.
+    pub fn inverse(&self) -> Option<DecimalMatrix3d> {
+        let d = &self.data;
+        let cofactor = |i: usize, j: usize| {
+            let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+            let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+            d[r0][c0].clone() * &d[r1][c1] - d[r0][c1].clone() * &d[r1][c0]
+        };
+        let determinant = (0..3).fold(DBig::ZERO, |sum, j| sum + d[0][j].clone() * cofactor(0, j));
+        if determinant == DBig::ZERO {
+            return None;
+        }
+        Some(DecimalMatrix3d {
+            data: std::array::from_fn(|i| std::array::from_fn(|j| cofactor(j, i) / &determinant)),
+        })
+    }
+
     pub fn as_quat(&self) -> [DBig; 4] {
         let f_trace = self.data[0][0].clone() + self.data[1][1].clone() + self.data[2][2].clone();
-        let mut f_root = DBig::ZERO.clone();
-
-        if (f_trace > DBig::ZERO) {
-            f_root = (f_trace + DBig::ONE.clone()).sqrt();
+        if f_trace > DBig::ZERO {
+            let mut f_root = (f_trace + DBig::ONE.clone()).sqrt();
             let w = DBIGHALF.clone() * f_root.clone();
             f_root = DBIGHALF.clone() / f_root.clone();
             let x = (self.data[1][2].clone() - self.data[2][1].clone()) * f_root.clone();
@@ -81,18 +119,19 @@ impl DecimalMatrix3d {
             [x, y, z, w]
         } else {
             let mut i = 0;
-            if (self.data[1][1] > self.data[0][0]) {
+            if self.data[1][1] > self.data[0][0] {
                 i = 1;
             }
-            if (self.data[2][2] > self.data[i][i]) {
+            if self.data[2][2] > self.data[i][i] {
                 i = 2;
             }
             let j = (i + 1) % 3;
             let k = (i + 2) % 3;
 
-            f_root = (self.data[i][i].clone() - self.data[j][j].clone() - self.data[k][k].clone()
-                + DBig::ONE.clone())
-            .sqrt();
+            let mut f_root =
+                (self.data[i][i].clone() - self.data[j][j].clone() - self.data[k][k].clone()
+                    + DBig::ONE.clone())
+                .sqrt();
             let mut out = [DBig::ZERO, DBig::ZERO, DBig::ZERO, DBig::ZERO];
             out[i] = DBIGHALF.clone() * f_root.clone();
             f_root = DBIGHALF.clone() / f_root.clone();
@@ -1,3 +1,4 @@
+use crate::sin_cos::f64_to_dbig;
 use dashu_float::ops::SquareRoot;
 use dashu_float::DBig;
 use std::fmt;
@@ -39,18 +40,23 @@ impl DecimalVector3d {
 
     pub fn from_f64(x: f64, y: f64, z: f64) -> DecimalVector3d {
         DecimalVector3d {
-            x: DBig::from_str(x.to_string().as_str()).unwrap(),
-            y: DBig::from_str(y.to_string().as_str()).unwrap(),
-            z: DBig::from_str(z.to_string().as_str()).unwrap(),
+            x: f64_to_dbig(x),
+            y: f64_to_dbig(y),
+            z: f64_to_dbig(z),
         }
     }
 
     pub fn length_squared(&self) -> DBig {
-        (self.x.clone() + self.y.clone() + self.z.clone())
+        self.x.clone() * &self.x + self.y.clone() * &self.y + self.z.clone() * &self.z
     }
 
     pub fn length(&self) -> DBig {
-        (self.x.clone() + self.y.clone() + self.z.clone()).sqrt()
+        let length_squared = self.length_squared();
+        // the square root of an exact zero has no precision to work with
+        if length_squared == DBig::ZERO {
+            return DBig::ZERO;
+        }
+        length_squared.sqrt()
     }
 
     pub fn distance_to(&self, rhs: Self) -> DBig {
@@ -232,7 +238,7 @@ macro_rules! impl_binop_assign {
     };
 }
 
-use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 impl_binop!(impl Add, add for DecimalVector3d, DBig);
 impl_binop_assign!(impl AddAssign, add_assign for DecimalVector3d, DBig);
 
@@ -244,3 +250,11 @@ impl_binop_assign!(impl MulAssign, mul_assign for DecimalVector3d, DBig);
 
 impl_binop!(impl Div, div for DecimalVector3d, DBig);
 impl_binop_assign!(impl DivAssign, div_assign for DecimalVector3d, DBig);
+
+impl Neg for DecimalVector3d {
+    type Output = DecimalVector3d;
+
+    fn neg(self) -> DecimalVector3d {
+        DecimalVector3d::new(-self.x, -self.y, -self.z)
+    }
+}
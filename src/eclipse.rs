@@ -0,0 +1,357 @@
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::events::{
+    find_minimum, CrossingDirection, EventCondition, EventDetector, DEFAULT_TOLERANCE,
+};
+use crate::simulation::Simulation;
+use crate::sin_cos::{acos, asin, atan2, f64_to_dbig, precise, PI};
+use crate::units::{Length, Time};
+use dashu_float::ops::{Abs, SquareRoot};
+use dashu_float::DBig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OccultationKind {
+    /// Part of the target's disk is hidden, for an eclipse the observer is in the penumbra.
+    Partial,
+    /// The occulter sits entirely inside the target's disk, for an eclipse the observer is in
+    /// the antumbra.
+    Annular,
+    /// The whole target is hidden, for an eclipse the observer is in the umbra.
+    Total,
+}
+
+/// Time window during which `occulter` hides part of `target` as seen from `observer`.
+#[derive(Debug, Clone)]
+pub struct OccultationEvent {
+    pub observer: i32,
+    pub occulter: i32,
+    pub target: i32,
+    pub start: Time,
+    pub peak: Time,
+    pub end: Time,
+    /// Fraction of the target's disk hidden at `peak`, in [0, 1].
+    pub peak_fraction: DBig,
+    pub kind: OccultationKind,
+}
+
+/// Apparent disks of a target and an occulter as seen from an observer, in radians.
+struct DiskGeometry {
+    separation: DBig,
+    target_radius: DBig,
+    occulter_radius: DBig,
+    occulter_in_front: bool,
+}
+
+impl DiskGeometry {
+    /// Negative while the disks overlap and the occulter is the closer of the two.
+    fn overlap_margin(&self) -> DBig {
+        if !self.occulter_in_front {
+            return DBig::ONE;
+        }
+        self.separation.clone() - &self.target_radius - &self.occulter_radius
+    }
+
+    fn kind(&self) -> OccultationKind {
+        if self.occulter_radius >= self.target_radius
+            && self.separation <= self.occulter_radius.clone() - &self.target_radius
+        {
+            OccultationKind::Total
+        } else if self.separation <= (self.target_radius.clone() - &self.occulter_radius).abs() {
+            OccultationKind::Annular
+        } else {
+            OccultationKind::Partial
+        }
+    }
+
+    /// Fraction of the target's disk covered by the occulter, treating both as flat disks.
+    fn covered_fraction(&self) -> DBig {
+        if self.overlap_margin() >= DBig::ZERO {
+            return DBig::ZERO;
+        }
+        let (d, r1, r2) = (
+            precise(self.separation.clone()),
+            &self.target_radius,
+            &self.occulter_radius,
+        );
+        match self.kind() {
+            OccultationKind::Total => DBig::ONE,
+            OccultationKind::Annular => (r2.clone() * r2) / (r1.clone() * r1),
+            OccultationKind::Partial => {
+                let (d2, r1s, r2s) = (d.clone() * &d, r1.clone() * r1, r2.clone() * r2);
+                let a1 = acos((d2.clone() + &r1s - &r2s) / (DBig::from(2) * &d * r1), 32);
+                let a2 = acos((d2.clone() + &r2s - &r1s) / (DBig::from(2) * &d * r2), 32);
+                let kite = ((r1.clone() + r2 - &d)
+                    * (d.clone() + r1 - r2)
+                    * (d.clone() - r1 + r2)
+                    * (d.clone() + r1 + r2))
+                    .sqrt();
+                let lens = r1s.clone() * a1 + r2s * a2 - kite / DBig::from(2);
+                (lens / (PI.clone() * r1s)).min(DBig::ONE)
+            }
+        }
+    }
+}
+
+fn angle_between(a: &DecimalVector3d, b: &DecimalVector3d) -> DBig {
+    atan2(a.cross(b.clone()).length(), a.dot(b.clone()), 32)
+}
+
+impl Simulation {
+    fn disk_geometry(&self, observer: i32, occulter: i32, target: i32) -> Option<DiskGeometry> {
+        let origin = self.get_body_by_id(observer)?.position();
+        let occulter = self.get_body_by_id(occulter)?;
+        let target = self.get_body_by_id(target)?;
+        let to_occulter = occulter.position() - origin;
+        let to_target = target.position() - origin;
+        let occulter_distance = to_occulter.length();
+        let target_distance = to_target.length();
+        let apparent_radius = |radius: &Length, distance: &DBig| {
+            asin((radius.to_meters() / distance).min(DBig::ONE), 32)
+        };
+
+        Some(DiskGeometry {
+            separation: angle_between(&to_occulter, &to_target),
+            target_radius: apparent_radius(&target.body().radius, &target_distance),
+            occulter_radius: apparent_radius(&occulter.body().radius, &occulter_distance),
+            occulter_in_front: occulter_distance < target_distance,
+        })
+    }
+
+    /// Fraction of `target`'s disk hidden by `occulter` as seen from the center of `observer`.
+    pub fn occulted_fraction(&self, observer: i32, occulter: i32, target: i32) -> Option<DBig> {
+        Some(
+            self.disk_geometry(observer, occulter, target)?
+                .covered_fraction(),
+        )
+    }
+
+    /// Fraction of the star's disk hidden from the center of body `id` by any other body of its
+    /// hierarchy, 1 inside an umbra and between 0 and 1 inside a penumbra or antumbra.
+    pub fn shadow_fraction(&self, id: i32) -> Option<DBig> {
+        let star = self.get_star_id(id);
+        let mut fraction = DBig::ZERO;
+        for occulter in self.get_eclipse_candidates(id) {
+            fraction = fraction.max(self.occulted_fraction(id, occulter, star)?);
+        }
+        Some(fraction)
+    }
+
+    fn get_eclipse_candidates(&self, id: i32) -> Vec<i32> {
+        let star = self.get_star_id(id);
+        let mut members = vec![];
+        if let Some(root) = self.get_body_by_id(self.get_root_id(id)) {
+            members = self
+                .resolve_hierarchy_down(root)
+                .into_iter()
+                .filter(|body| !body.body().is_barycenter())
+                .map(|body| body.id())
+                .filter(|&member| member != id && member != star)
+                .collect();
+        }
+        members
+    }
+
+    /// Occultations of `target` by `occulter` seen from `observer` between `start` and `end`.
+    /// Bodies are sampled every `step`, see [`EventDetector`].
+    pub fn find_occultations(
+        &self,
+        observer: i32,
+        occulter: i32,
+        target: i32,
+        start: Time,
+        end: Time,
+        step: Time,
+    ) -> Option<Vec<OccultationEvent>> {
+        self.find_occultations_of(&[(observer, occulter, target)], start, end, step)
+    }
+
+    /// Every eclipse in the simulation between `start` and `end`: for each body lit by a star,
+    /// the star being hidden from its center by any other body of the same hierarchy.
+    pub fn find_eclipses(
+        &self,
+        start: Time,
+        end: Time,
+        step: Time,
+    ) -> Option<Vec<OccultationEvent>> {
+        let mut triples = vec![];
+        for body in &self.bodies {
+            let star = self.get_star_id(body.id());
+            if star == body.id() || body.body().is_barycenter() {
+                continue;
+            }
+            for occulter in self.get_eclipse_candidates(body.id()) {
+                triples.push((body.id(), occulter, star));
+            }
+        }
+        self.find_occultations_of(&triples, start, end, step)
+    }
+
+    fn find_occultations_of(
+        &self,
+        triples: &[(i32, i32, i32)],
+        start: Time,
+        end: Time,
+        step: Time,
+    ) -> Option<Vec<OccultationEvent>> {
+        let mut detector = EventDetector::new(step);
+        for &(observer, occulter, target) in triples {
+            detector.add(EventCondition::new(
+                move |simulation| {
+                    simulation
+                        .disk_geometry(observer, occulter, target)
+                        .map(|geometry| geometry.overlap_margin())
+                },
+                CrossingDirection::Either,
+            ));
+        }
+
+        // an occultation already underway at `start` begins there
+        let snapshot = self.at(start.clone())?;
+        let mut open = detector
+            .conditions
+            .iter()
+            .map(|condition| {
+                let overlapping = condition.evaluate(&snapshot)? < DBig::ZERO;
+                Some(overlapping.then(|| start.clone()))
+            })
+            .collect::<Option<Vec<Option<Time>>>>()?;
+
+        let mut events = vec![];
+        for event in detector.find(self, start, end.clone())? {
+            match event.direction {
+                CrossingDirection::Falling => open[event.condition] = Some(event.time),
+                _ => {
+                    if let Some(started) = open[event.condition].take() {
+                        let triple = triples[event.condition];
+                        events.push(self.occultation_event(triple, started, event.time)?);
+                    }
+                }
+            }
+        }
+        for (index, started) in open.into_iter().enumerate() {
+            if let Some(started) = started {
+                events.push(self.occultation_event(triples[index], started, end.clone())?);
+            }
+        }
+        events.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+        Some(events)
+    }
+
+    /// Locates the smallest separation between `start` and `end`.
+    fn occultation_event(
+        &self,
+        (observer, occulter, target): (i32, i32, i32),
+        start: Time,
+        end: Time,
+    ) -> Option<OccultationEvent> {
+        let peak = Time::from_seconds(find_minimum(
+            |time| {
+                self.at(Time::from_seconds(time.clone()))?
+                    .disk_geometry(observer, occulter, target)
+                    .map(|geometry| geometry.separation)
+            },
+            start.to_seconds(),
+            end.to_seconds(),
+            &f64_to_dbig(DEFAULT_TOLERANCE),
+        )?);
+        let geometry = self
+            .at(peak.clone())?
+            .disk_geometry(observer, occulter, target)?;
+
+        Some(OccultationEvent {
+            observer,
+            occulter,
+            target,
+            start,
+            end,
+            peak,
+            peak_fraction: geometry.covered_fraction(),
+            kind: geometry.kind(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics};
+    use crate::sin_cos::dbig_to_f64;
+    use crate::units::{Angle, Mass};
+
+    fn simulation() -> (Simulation, i32, i32) {
+        let moon = Body {
+            rotation_period: Time::from_seconds(f64_to_dbig(2.36e6)),
+            ..Body::new(
+                "moon",
+                Mass::from_kilograms(f64_to_dbig(7.3e22)),
+                Length::from_meters(f64_to_dbig(1.737e6)),
+                BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                    orbit_radius: Length::from_meters(f64_to_dbig(3.844e8)),
+                    orbit_period: Time::from_seconds(f64_to_dbig(2.36e6)),
+                    orbit_phase: Angle::zero(),
+                    orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                }),
+            )
+        };
+        let planet = Body {
+            satellites: vec![moon],
+            rotation_period: Time::from_seconds(f64_to_dbig(86400.0)),
+            ..Body::new(
+                "planet",
+                Mass::from_kilograms(f64_to_dbig(6e24)),
+                Length::from_meters(f64_to_dbig(6.4e6)),
+                BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                    orbit_radius: Length::from_meters(f64_to_dbig(1.5e11)),
+                    orbit_period: Time::from_seconds(f64_to_dbig(3.15e7)),
+                    orbit_phase: Angle::zero(),
+                    orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                }),
+            )
+        };
+        let star = Body {
+            satellites: vec![planet],
+            rotation_period: Time::from_seconds(f64_to_dbig(2e6)),
+            ..Body::new(
+                "star",
+                Mass::from_kilograms(f64_to_dbig(2e30)),
+                Length::from_meters(f64_to_dbig(7e8)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            )
+        };
+        let mut sim = Simulation::new();
+        let star_id = sim.add_hierarchy(star, None);
+        sim.update(Time::zero());
+        (sim, star_id + 1, star_id + 2)
+    }
+
+    #[test]
+    fn moon_behind_planet_is_in_the_umbra() {
+        let (sim, planet_id, moon_id) = simulation();
+        assert_eq!(dbig_to_f64(&sim.shadow_fraction(moon_id).unwrap()), 1.0);
+        assert_eq!(dbig_to_f64(&sim.shadow_fraction(planet_id).unwrap()), 0.0);
+    }
+
+    #[test]
+    fn lunar_eclipse_window() {
+        let (sim, planet_id, moon_id) = simulation();
+        let star_id = sim.get_root_id(moon_id);
+        let events = sim
+            .find_occultations(
+                moon_id,
+                planet_id,
+                star_id,
+                Time::from_seconds(f64_to_dbig(-30000.0)),
+                Time::from_seconds(f64_to_dbig(30000.0)),
+                Time::from_hours(DBig::ONE),
+            )
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.kind, OccultationKind::Total);
+        assert!(event.start < event.peak && event.peak < event.end);
+        assert!(dbig_to_f64(&event.peak.to_seconds()).abs() < 1000.0);
+        assert!(dbig_to_f64(&event.start.to_seconds()) > -30000.0);
+        assert!(dbig_to_f64(&event.end.to_seconds()) < 30000.0);
+    }
+}
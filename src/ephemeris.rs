@@ -0,0 +1,514 @@
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::reference_frame::StateVector;
+use crate::simulation::Simulation;
+use crate::sin_cos::{dbig_to_f64, f64_to_dbig};
+use crate::units::{Length, Time};
+use dashu_float::DBig;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+const BINARY_MAGIC: &[u8; 4] = b"PSEP";
+const BINARY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone)]
+pub enum Sampling {
+    /// One sample every `step`, plus one at the end of the range.
+    Fixed(Time),
+    /// Starts from `max_step` and halves an interval while cubic Hermite interpolation between
+    /// its ends is more than `tolerance` off at the midpoint for any body, down to `min_step`.
+    Adaptive {
+        max_step: Time,
+        min_step: Time,
+        tolerance: Length,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct EphemerisSample {
+    pub time: Time,
+    pub state: StateVector, // in the global frame
+}
+
+#[derive(Debug, Clone)]
+pub struct BodyEphemeris {
+    pub id: i32,
+    pub name: String,
+    pub samples: Vec<EphemerisSample>,
+}
+
+/// Position and velocity tables sampled from a [`Simulation`].
+#[derive(Debug, Clone)]
+pub struct Ephemeris {
+    pub bodies: Vec<BodyEphemeris>,
+}
+
+impl Simulation {
+    /// Ids of `ids` or of every body if `None`, `None` if one of them is unknown.
+    pub(crate) fn resolve_ids(&self, ids: Option<&[i32]>) -> Option<Vec<i32>> {
+        match ids {
+            Some(ids) => ids
+                .iter()
+                .map(|&id| self.get_body_by_id(id).map(|body| body.id()))
+                .collect(),
+            None => Some(self.bodies.iter().map(|body| body.id()).collect()),
+        }
+    }
+
+    fn sample_states(&self, ids: &[i32], time: &Time) -> Option<Vec<StateVector>> {
+        let snapshot = self.at(time.clone())?;
+        ids.iter()
+            .map(|&id| {
+                let body = snapshot.get_body_by_id(id)?;
+                Some(StateVector::new(
+                    body.position().clone(),
+                    body.velocity().clone(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Samples bodies `ids` (every body if `None`) between `start` and `end`. `None` if a step
+    /// of `sampling` is not positive.
+    pub fn generate_ephemeris(
+        &self,
+        ids: Option<&[i32]>,
+        start: Time,
+        end: Time,
+        sampling: &Sampling,
+    ) -> Option<Ephemeris> {
+        let ids = self.resolve_ids(ids)?;
+        let mut rows: Vec<(Time, Vec<StateVector>)> = vec![];
+
+        match sampling {
+            Sampling::Fixed(step) => {
+                if *step <= Time::zero() {
+                    return None;
+                }
+                let mut time = start;
+                while time < end {
+                    rows.push((time.clone(), self.sample_states(&ids, &time)?));
+                    time += step;
+                }
+                rows.push((end.clone(), self.sample_states(&ids, &end)?));
+            }
+            Sampling::Adaptive {
+                max_step,
+                min_step,
+                tolerance,
+            } => {
+                if *max_step <= Time::zero() || *min_step <= Time::zero() {
+                    return None;
+                }
+                let mut time = start;
+                let mut states = self.sample_states(&ids, &time)?;
+                rows.push((time.clone(), states.clone()));
+                while time < end {
+                    let next_time = (time.clone() + max_step).min(end.clone());
+                    let next_states = self.sample_states(&ids, &next_time)?;
+                    self.refine_interval(
+                        &ids,
+                        (&time, &states),
+                        (&next_time, &next_states),
+                        min_step,
+                        tolerance,
+                        &mut rows,
+                    )?;
+                    rows.push((next_time.clone(), next_states.clone()));
+                    time = next_time;
+                    states = next_states;
+                }
+            }
+        }
+
+        let bodies = ids
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| BodyEphemeris {
+                id,
+                name: self.get_body_by_id(id).unwrap().body().name.clone(),
+                samples: rows
+                    .iter()
+                    .map(|(time, states)| EphemerisSample {
+                        time: time.clone(),
+                        state: states[index].clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Some(Ephemeris { bodies })
+    }
+
+    /// Pushes the samples strictly between `from` and `to` that adaptive sampling needs.
+    fn refine_interval(
+        &self,
+        ids: &[i32],
+        from: (&Time, &Vec<StateVector>),
+        to: (&Time, &Vec<StateVector>),
+        min_step: &Time,
+        tolerance: &Length,
+        rows: &mut Vec<(Time, Vec<StateVector>)>,
+    ) -> Option<()> {
+        let span = to.0 - from.0;
+        if span <= min_step * &DBig::from(2) {
+            return Some(());
+        }
+        let middle = (from.0 + to.0) / DBig::from(2);
+        let span = span.to_seconds();
+        let limit = tolerance.to_meters();
+        let middle_states = self.sample_states(ids, &middle)?;
+        let accurate =
+            from.1
+                .iter()
+                .zip(to.1)
+                .zip(&middle_states)
+                .all(|((before, after), actual)| {
+                    // cubic Hermite interpolation evaluated at the midpoint
+                    let predicted = (&before.position + &after.position) / DBig::from(2)
+                        + (&before.velocity - &after.velocity) * (span.clone() / DBig::from(8));
+                    predicted.distance_to(actual.position.clone()) <= limit
+                });
+        if accurate {
+            return Some(());
+        }
+        self.refine_interval(
+            ids,
+            from,
+            (&middle, &middle_states),
+            min_step,
+            tolerance,
+            rows,
+        )?;
+        rows.push((middle.clone(), middle_states.clone()));
+        self.refine_interval(
+            ids,
+            (&middle, &middle_states),
+            to,
+            min_step,
+            tolerance,
+            rows,
+        )
+    }
+}
+
+fn escape_json(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub(crate) fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_i32(writer: &mut impl Write, value: i32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_f64(writer: &mut impl Write, value: f64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_string(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u32(writer, value.len() as u32)?;
+    writer.write_all(value.as_bytes())
+}
+
+pub(crate) fn write_vector(writer: &mut impl Write, value: &DecimalVector3d) -> io::Result<()> {
+    write_string(writer, &value.x.to_string())?;
+    write_string(writer, &value.y.to_string())?;
+    write_string(writer, &value.z.to_string())
+}
+
+pub(crate) fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_i32(reader: &mut impl Read) -> io::Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0; read_u32(reader)? as usize];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+pub(crate) fn read_dbig(reader: &mut impl Read) -> io::Result<DBig> {
+    DBig::from_str(&read_string(reader)?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+pub(crate) fn read_vector(reader: &mut impl Read) -> io::Result<DecimalVector3d> {
+    Ok(DecimalVector3d::new(
+        read_dbig(reader)?,
+        read_dbig(reader)?,
+        read_dbig(reader)?,
+    ))
+}
+
+pub(crate) fn read_magic(reader: &mut impl Read, magic: &[u8; 4], version: u32) -> io::Result<()> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    if &bytes != magic || read_u32(reader)? != version {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unknown file format or version",
+        ));
+    }
+    Ok(())
+}
+
+impl Ephemeris {
+    /// One row per body and sample: `id,name,time,x,y,z,vx,vy,vz`, in seconds, meters and meters
+    /// per second, at full precision.
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "id,name,time,x,y,z,vx,vy,vz")?;
+        for body in &self.bodies {
+            let name = escape_csv(&body.name);
+            for sample in &body.samples {
+                let (p, v) = (&sample.state.position, &sample.state.velocity);
+                writeln!(
+                    writer,
+                    "{},{},{},{},{},{},{},{},{}",
+                    body.id,
+                    name,
+                    sample.time.to_seconds(),
+                    p.x,
+                    p.y,
+                    p.z,
+                    v.x,
+                    v.y,
+                    v.z
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// One JSON object per body and sample, numbers are written at full precision.
+    pub fn write_json_lines(&self, mut writer: impl Write) -> io::Result<()> {
+        for body in &self.bodies {
+            let name = escape_json(&body.name);
+            for sample in &body.samples {
+                let (p, v) = (&sample.state.position, &sample.state.velocity);
+                writeln!(
+                    writer,
+                    "{{\"id\":{},\"name\":\"{}\",\"time\":{},\
+                     \"position\":[{},{},{}],\"velocity\":[{},{},{}]}}",
+                    body.id,
+                    name,
+                    sample.time.to_seconds(),
+                    p.x,
+                    p.y,
+                    p.z,
+                    v.x,
+                    v.y,
+                    v.z
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Little-endian binary tables. Each body stores its first position as decimal strings and
+    /// every sample as f64 offsets from it, so far-away bodies keep their precision.
+    pub fn write_binary(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(BINARY_MAGIC)?;
+        write_u32(&mut writer, BINARY_VERSION)?;
+        write_u32(&mut writer, self.bodies.len() as u32)?;
+        for body in &self.bodies {
+            write_i32(&mut writer, body.id)?;
+            write_string(&mut writer, &body.name)?;
+            let origin = body
+                .samples
+                .first()
+                .map(|sample| sample.state.position.clone())
+                .unwrap_or_else(DecimalVector3d::zero);
+            let start = body
+                .samples
+                .first()
+                .map(|sample| sample.time.clone())
+                .unwrap_or_else(Time::zero);
+            write_vector(&mut writer, &origin)?;
+            write_string(&mut writer, &start.to_seconds().to_string())?;
+            write_u32(&mut writer, body.samples.len() as u32)?;
+            for sample in &body.samples {
+                let offset = &sample.state.position - &origin;
+                let v = &sample.state.velocity;
+                for value in [
+                    &(&sample.time - &start).to_seconds(),
+                    &offset.x,
+                    &offset.y,
+                    &offset.z,
+                    &v.x,
+                    &v.y,
+                    &v.z,
+                ] {
+                    write_f64(&mut writer, dbig_to_f64(value))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_binary(mut reader: impl Read) -> io::Result<Ephemeris> {
+        read_magic(&mut reader, BINARY_MAGIC, BINARY_VERSION)?;
+        let mut bodies = vec![];
+        for _ in 0..read_u32(&mut reader)? {
+            let id = read_i32(&mut reader)?;
+            let name = read_string(&mut reader)?;
+            let origin = read_vector(&mut reader)?;
+            let start = Time::from_seconds(read_dbig(&mut reader)?);
+            let mut samples = vec![];
+            for _ in 0..read_u32(&mut reader)? {
+                let mut values = [0.0; 7];
+                for value in values.iter_mut() {
+                    *value = read_f64(&mut reader)?;
+                }
+                let [time, x, y, z, vx, vy, vz] = values;
+                samples.push(EphemerisSample {
+                    time: &start + &Time::from_seconds(f64_to_dbig(time)),
+                    state: StateVector::new(
+                        DecimalVector3d::from_f64(x, y, z) + &origin,
+                        DecimalVector3d::from_f64(vx, vy, vz),
+                    ),
+                });
+            }
+            bodies.push(BodyEphemeris { id, name, samples });
+        }
+        Ok(Ephemeris { bodies })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics};
+    use crate::units::{Angle, Mass};
+
+    #[test]
+    fn tables_round_trip() {
+        let planet = Body {
+            rotation_period: Time::from_seconds(f64_to_dbig(86400.0)),
+            ..Body::new(
+                "planet, b",
+                Mass::from_kilograms(f64_to_dbig(6e24)),
+                Length::from_meters(f64_to_dbig(6.4e6)),
+                BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                    orbit_radius: Length::from_meters(f64_to_dbig(1.5e11)),
+                    orbit_period: Time::from_seconds(f64_to_dbig(3.15e7)),
+                    orbit_phase: Angle::zero(),
+                    orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                }),
+            )
+        };
+        let star = Body {
+            satellites: vec![planet],
+            rotation_period: Time::from_seconds(f64_to_dbig(2e6)),
+            ..Body::new(
+                "star",
+                Mass::from_kilograms(f64_to_dbig(2e30)),
+                Length::from_meters(f64_to_dbig(7e8)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            )
+        };
+        let mut sim = Simulation::new();
+        sim.add_hierarchy(star, None);
+
+        let fixed = sim
+            .generate_ephemeris(
+                Some(&[1]),
+                Time::zero(),
+                Time::from_seconds(f64_to_dbig(1e6)),
+                &Sampling::Fixed(Time::from_seconds(f64_to_dbig(3e5))),
+            )
+            .unwrap();
+        assert_eq!(fixed.bodies[0].samples.len(), 5);
+
+        let mut csv = vec![];
+        fixed.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 6);
+        assert!(csv
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("1,\"planet, b\",0,"));
+
+        let mut bytes = vec![];
+        fixed.write_binary(&mut bytes).unwrap();
+        let read = Ephemeris::read_binary(bytes.as_slice()).unwrap();
+        for (a, b) in fixed.bodies[0].samples.iter().zip(&read.bodies[0].samples) {
+            assert_eq!(a.time, b.time);
+            assert!(dbig_to_f64(&a.state.position.distance_to(b.state.position.clone())) < 1e-3);
+        }
+
+        let adaptive = sim
+            .generate_ephemeris(
+                Some(&[1]),
+                Time::zero(),
+                Time::from_seconds(f64_to_dbig(1e6)),
+                &Sampling::Adaptive {
+                    max_step: Time::from_seconds(f64_to_dbig(1e6)),
+                    min_step: Time::from_seconds(f64_to_dbig(1e4)),
+                    tolerance: Length::from_meters(f64_to_dbig(1e3)),
+                },
+            )
+            .unwrap();
+        let times: Vec<f64> = adaptive.bodies[0]
+            .samples
+            .iter()
+            .map(|sample| dbig_to_f64(&sample.time.to_seconds()))
+            .collect();
+        assert!(times.len() > 2);
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // steps that never advance give up instead of looping
+        let end = Time::from_seconds(f64_to_dbig(1e6));
+        for sampling in [
+            Sampling::Fixed(Time::zero()),
+            Sampling::Fixed(Time::from_seconds(f64_to_dbig(-1.0))),
+            Sampling::Adaptive {
+                max_step: Time::zero(),
+                min_step: Time::from_seconds(f64_to_dbig(1e4)),
+                tolerance: Length::from_meters(f64_to_dbig(1e3)),
+            },
+            Sampling::Adaptive {
+                max_step: end.clone(),
+                min_step: Time::zero(),
+                tolerance: Length::from_meters(f64_to_dbig(1e3)),
+            },
+        ] {
+            assert!(sim
+                .generate_ephemeris(Some(&[1]), Time::zero(), end.clone(), &sampling)
+                .is_none());
+        }
+    }
+}
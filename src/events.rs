@@ -0,0 +1,453 @@
+use crate::simulation::Simulation;
+use crate::sin_cos::{f64_to_dbig, precise};
+use crate::units::{Length, Time};
+use dashu_float::ops::{Abs, SquareRoot};
+use dashu_float::DBig;
+use std::sync::LazyLock;
+
+/// Roots and minima are refined until they are known this tightly, in seconds.
+pub const DEFAULT_TOLERANCE: f64 = 0.01;
+
+const MAX_ITERATIONS: usize = 200;
+
+static GOLDEN_SECTION: LazyLock<DBig> =
+    LazyLock::new(|| (f64_to_dbig(5.0).sqrt() - DBig::ONE) / DBig::from(2));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrossingDirection {
+    /// The condition goes from negative to positive.
+    Rising,
+    /// The condition goes from positive to negative.
+    Falling,
+    Either,
+}
+
+impl CrossingDirection {
+    fn accepts(self, crossing: CrossingDirection) -> bool {
+        self == CrossingDirection::Either || self == crossing
+    }
+}
+
+type ConditionFn = dyn Fn(&Simulation) -> Option<DBig>;
+type GuardFn = dyn Fn(&Simulation) -> bool;
+
+/// Scalar function of the simulation state whose zero crossings are events.
+pub struct EventCondition {
+    function: Box<ConditionFn>,
+    direction: CrossingDirection,
+    guard: Option<Box<GuardFn>>,
+}
+
+impl EventCondition {
+    /// `function` is evaluated on the simulation updated to the probed time, returning `None`
+    /// aborts the search.
+    pub fn new(
+        function: impl Fn(&Simulation) -> Option<DBig> + 'static,
+        direction: CrossingDirection,
+    ) -> EventCondition {
+        EventCondition {
+            function: Box::new(function),
+            direction,
+            guard: None,
+        }
+    }
+
+    /// Only reports crossings where `guard` holds at the refined event time.
+    pub fn with_guard(mut self, guard: impl Fn(&Simulation) -> bool + 'static) -> EventCondition {
+        self.guard = Some(Box::new(guard));
+        self
+    }
+
+    pub fn evaluate(&self, simulation: &Simulation) -> Option<DBig> {
+        (self.function)(simulation)
+    }
+
+    /// Bodies `a` and `b` getting closer than `threshold` meters (falling) or separating again
+    /// (rising).
+    pub fn distance_below(a: i32, b: i32, threshold: Length, direction: CrossingDirection) -> Self {
+        EventCondition::new(
+            move |simulation| Some((simulation.get_distance(a, b)? - &threshold).to_meters()),
+            direction,
+        )
+    }
+
+    /// Body `id` passing the periapsis of its orbit around its parent.
+    pub fn periapsis(id: i32) -> EventCondition {
+        EventCondition::new(
+            move |simulation| Some(simulation.get_radial_velocity(id)?.to_meters_per_second()),
+            CrossingDirection::Rising,
+        )
+    }
+
+    /// Body `id` passing the apoapsis of its orbit around its parent.
+    pub fn apoapsis(id: i32) -> EventCondition {
+        EventCondition::new(
+            move |simulation| Some(simulation.get_radial_velocity(id)?.to_meters_per_second()),
+            CrossingDirection::Falling,
+        )
+    }
+
+    /// Local minima of the distance between bodies `a` and `b`.
+    pub fn closest_approach(a: i32, b: i32) -> EventCondition {
+        EventCondition::new(
+            move |simulation| Some(simulation.get_range_rate(a, b)?.to_meters_per_second()),
+            CrossingDirection::Rising,
+        )
+    }
+
+    /// Body `id` entering (falling) or leaving (rising) the sphere of influence of `body`.
+    pub fn sphere_of_influence(id: i32, body: i32, direction: CrossingDirection) -> Self {
+        EventCondition::new(
+            move |simulation| {
+                let distance = simulation.get_distance(id, body)?;
+                Some((distance - simulation.sphere_of_influence(body)?).to_meters())
+            },
+            direction,
+        )
+    }
+
+    /// Bodies `a` and `b` lining up on the same side of `observer`, the moment the component of
+    /// `a × b` along `a`'s orbit normal around the observer changes sign.
+    pub fn conjunction(observer: i32, a: i32, b: i32) -> EventCondition {
+        let relative = move |simulation: &Simulation, id: i32| {
+            let origin = simulation.get_body_by_id(observer)?;
+            let body = simulation.get_body_by_id(id)?;
+            Some((
+                body.position() - origin.position(),
+                body.velocity() - origin.velocity(),
+            ))
+        };
+        EventCondition::new(
+            move |simulation| {
+                let (position_a, velocity_a) = relative(simulation, a)?;
+                let (position_b, _) = relative(simulation, b)?;
+                let normal = position_a.cross(velocity_a);
+                Some(position_a.cross(position_b).dot(normal))
+            },
+            CrossingDirection::Either,
+        )
+        .with_guard(move |simulation| {
+            match (relative(simulation, a), relative(simulation, b)) {
+                (Some((a, _)), Some((b, _))) => a.dot(b) > DBig::ZERO,
+                _ => false,
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// Index of the condition in the detector that fired.
+    pub condition: usize,
+    pub time: Time,
+    pub direction: CrossingDirection,
+}
+
+/// Finds the zero crossings of a set of conditions. Conditions are sampled every `max_step`
+/// seconds and every sign change is refined with Brent's method, so crossings closer together
+/// than `max_step` can be missed.
+pub struct EventDetector {
+    pub conditions: Vec<EventCondition>,
+    pub max_step: Time,
+    pub tolerance: Time,
+}
+
+impl EventDetector {
+    pub fn new(max_step: Time) -> EventDetector {
+        EventDetector {
+            conditions: vec![],
+            max_step,
+            tolerance: Time::from_seconds(f64_to_dbig(DEFAULT_TOLERANCE)),
+        }
+    }
+
+    /// Adds `condition` and returns its index, as reported in [`Event::condition`].
+    pub fn add(&mut self, condition: EventCondition) -> usize {
+        self.conditions.push(condition);
+        self.conditions.len() - 1
+    }
+
+    /// All events between `start` and `end`, ordered by time. `None` if `max_step` is not
+    /// positive.
+    pub fn find(&self, simulation: &Simulation, start: Time, end: Time) -> Option<Vec<Event>> {
+        if self.max_step <= Time::zero() {
+            return None;
+        }
+        let evaluate_all = |snapshot: &Simulation| {
+            self.conditions
+                .iter()
+                .map(|condition| condition.evaluate(snapshot))
+                .collect::<Option<Vec<DBig>>>()
+        };
+
+        let mut events = vec![];
+        let mut time = start;
+        let mut values = evaluate_all(&simulation.at(time.clone())?)?;
+        while time < end {
+            let next_time = (&time + &self.max_step).min(end.clone());
+            let next_values = evaluate_all(&simulation.at(next_time.clone())?)?;
+
+            for (index, condition) in self.conditions.iter().enumerate() {
+                let crossing = match (values[index] > DBig::ZERO, next_values[index] > DBig::ZERO) {
+                    (false, true) => CrossingDirection::Rising,
+                    (true, false) => CrossingDirection::Falling,
+                    _ => continue,
+                };
+                if !condition.direction.accepts(crossing) {
+                    continue;
+                }
+                let root = Time::from_seconds(find_root(
+                    |t| condition.evaluate(&simulation.at(Time::from_seconds(t.clone()))?),
+                    (time.to_seconds(), values[index].clone()),
+                    (next_time.to_seconds(), next_values[index].clone()),
+                    &self.tolerance.to_seconds(),
+                )?);
+                if let Some(guard) = &condition.guard {
+                    if !guard(&simulation.at(root.clone())?) {
+                        continue;
+                    }
+                }
+                events.push(Event {
+                    condition: index,
+                    time: root,
+                    direction: crossing,
+                });
+            }
+
+            time = next_time;
+            values = next_values;
+        }
+        events.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        Some(events)
+    }
+}
+
+/// Root of `f` between `low` and `high` (each paired with the value of `f` there, which must
+/// differ in sign) by Brent's method, to within `tolerance`.
+pub fn find_root(
+    f: impl Fn(&DBig) -> Option<DBig>,
+    low: (DBig, DBig),
+    high: (DBig, DBig),
+    tolerance: &DBig,
+) -> Option<DBig> {
+    let ((mut a, mut fa), (mut b, mut fb)) = (low, high);
+    if fa.clone() * &fb > DBig::ZERO {
+        return None;
+    }
+    if fa.clone().abs() < fb.clone().abs() {
+        std::mem::swap(&mut a, &mut b);
+        std::mem::swap(&mut fa, &mut fb);
+    }
+    let two = DBig::from(2);
+    let (mut c, mut fc) = (a.clone(), fa.clone());
+    let mut d = c.clone();
+    let mut bisected = true;
+
+    for _ in 0..MAX_ITERATIONS {
+        if fb == DBig::ZERO || (b.clone() - &a).abs() < *tolerance {
+            break;
+        }
+        let mut s = if fa != fc && fb != fc {
+            // inverse quadratic interpolation
+            a.clone() * &fb * &fc / ((fa.clone() - &fb) * (fa.clone() - &fc))
+                + b.clone() * &fa * &fc / ((fb.clone() - &fa) * (fb.clone() - &fc))
+                + c.clone() * &fa * &fb / ((fc.clone() - &fa) * (fc.clone() - &fb))
+        } else {
+            b.clone() - fb.clone() * (b.clone() - &a) / (fb.clone() - &fa)
+        };
+
+        let quarter = (DBig::from(3) * &a + &b) / DBig::from(4);
+        let outside = if quarter < b {
+            s < quarter || s > b
+        } else {
+            s > quarter || s < b
+        };
+        let slow = if bisected {
+            (s.clone() - &b).abs() >= (b.clone() - &c).abs() / &two
+                || (b.clone() - &c).abs() < *tolerance
+        } else {
+            (s.clone() - &b).abs() >= (c.clone() - &d).abs() / &two
+                || (c.clone() - &d).abs() < *tolerance
+        };
+        bisected = outside || slow;
+        if bisected {
+            s = (a.clone() + &b) / &two;
+        }
+
+        let fs = f(&s)?;
+        d = c;
+        (c, fc) = (b.clone(), fb.clone());
+        if fa.clone() * &fs < DBig::ZERO {
+            (b, fb) = (s, fs);
+        } else {
+            (a, fa) = (s, fs);
+        }
+        if fa.clone().abs() < fb.clone().abs() {
+            std::mem::swap(&mut a, &mut b);
+            std::mem::swap(&mut fa, &mut fb);
+        }
+    }
+    Some(b)
+}
+
+/// Minimum of `f` between `low` and `high` by golden-section search, `f` must be unimodal there.
+pub fn find_minimum(
+    f: impl Fn(&DBig) -> Option<DBig>,
+    low: DBig,
+    high: DBig,
+    tolerance: &DBig,
+) -> Option<DBig> {
+    let (mut low, mut high) = (precise(low), precise(high));
+    let mut span = (high.clone() - &low) * GOLDEN_SECTION.clone();
+    let (mut left, mut right) = (high.clone() - &span, low.clone() + &span);
+    let (mut f_left, mut f_right) = (f(&left)?, f(&right)?);
+    while high.clone() - &low > *tolerance {
+        if f_left < f_right {
+            high = right;
+            right = left;
+            f_right = f_left;
+            span = (high.clone() - &low) * GOLDEN_SECTION.clone();
+            left = high.clone() - &span;
+            f_left = f(&left)?;
+        } else {
+            low = left;
+            left = right;
+            f_left = f_right;
+            span = (high.clone() - &low) * GOLDEN_SECTION.clone();
+            right = low.clone() + &span;
+            f_right = f(&right)?;
+        }
+    }
+    Some((low + high) / DBig::from(2))
+}
+
+impl Simulation {
+    /// Moves the simulation to `time`, handing every event `detector` finds on the way to
+    /// `on_event` in order together with the simulation state at that event. `None` if a body
+    /// played back from an ephemeris runs out of coverage on the way.
+    pub fn advance_to(
+        &mut self,
+        time: Time,
+        detector: &EventDetector,
+        mut on_event: impl FnMut(&Event, &Simulation),
+    ) -> Option<()> {
+        for event in detector.find(self, self.time().clone(), time.clone())? {
+            on_event(&event, &self.at(event.time.clone())?);
+        }
+        self.try_update(time).ok()
+    }
+
+    /// [`Simulation::advance_to`] `duration` seconds past the current time.
+    pub fn step(
+        &mut self,
+        duration: Time,
+        detector: &EventDetector,
+        on_event: impl FnMut(&Event, &Simulation),
+    ) -> Option<()> {
+        let time = self.time() + &duration;
+        self.advance_to(time, detector, on_event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics};
+    use crate::decimal_vector_3d::DecimalVector3d;
+    use crate::sin_cos::dbig_to_f64;
+    use crate::units::{Angle, Mass};
+
+    fn orbiting(name: &str, radius: f64, period: f64, satellites: Vec<Body>) -> Body {
+        Body {
+            satellites,
+            rotation_period: Time::from_seconds(f64_to_dbig(86400.0)),
+            ..Body::new(
+                name,
+                Mass::from_kilograms(f64_to_dbig(1e22)),
+                Length::from_meters(f64_to_dbig(1e6)),
+                BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                    orbit_radius: Length::from_meters(f64_to_dbig(radius)),
+                    orbit_period: Time::from_seconds(f64_to_dbig(period)),
+                    orbit_phase: Angle::zero(),
+                    orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                }),
+            )
+        }
+    }
+
+    #[test]
+    fn advance_reports_closest_approach() {
+        let moon = orbiting("moon", 3.844e8, 2.36e6, vec![]);
+        let planet = orbiting("planet", 1.5e11, 3.15e7, vec![moon]);
+        let star = Body {
+            satellites: vec![planet],
+            rotation_period: Time::from_seconds(f64_to_dbig(2e6)),
+            ..Body::new(
+                "star",
+                Mass::from_kilograms(f64_to_dbig(2e30)),
+                Length::from_meters(f64_to_dbig(7e8)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            )
+        };
+        let mut sim = Simulation::new();
+        let star_id = sim.add_hierarchy(star, None);
+        let moon_id = star_id + 2;
+
+        let mut detector = EventDetector::new(Time::from_seconds(f64_to_dbig(2e5)));
+        detector.add(EventCondition::closest_approach(moon_id, star_id));
+        let mut times = vec![];
+        sim.advance_to(
+            Time::from_seconds(f64_to_dbig(2.36e6)),
+            &detector,
+            |event, snapshot| {
+                assert_eq!(snapshot.time(), &event.time);
+                times.push(dbig_to_f64(&event.time.to_seconds()));
+            },
+        )
+        .unwrap();
+
+        // the moon starts out behind the planet and is closest to the star half a synodic
+        // month later
+        let synodic = 1.0 / (1.0 / 2.36e6 - 1.0 / 3.15e7);
+        assert_eq!(times.len(), 1);
+        assert!((times[0] - synodic / 2.0).abs() < 1000.0, "{times:?}");
+        assert_eq!(dbig_to_f64(&sim.time().to_seconds()), 2.36e6);
+
+        let mut stuck = EventDetector::new(Time::zero());
+        stuck.add(EventCondition::closest_approach(moon_id, star_id));
+        assert!(stuck
+            .find(&sim, Time::zero(), Time::from_seconds(f64_to_dbig(1e5)))
+            .is_none());
+        let time = sim.time().clone();
+        assert!(sim
+            .step(Time::from_seconds(f64_to_dbig(1e5)), &stuck, |_, _| ())
+            .is_none());
+        assert_eq!(sim.time(), &time);
+    }
+
+    #[test]
+    fn brent_finds_cubic_root() {
+        let f = |x: &DBig| Some(x.clone() * x * x - DBig::from(2));
+        let tolerance = f64_to_dbig(1e-20);
+        let low = f64_to_dbig(0.0);
+        let high = f64_to_dbig(3.0);
+        let root = find_root(
+            f,
+            (low.clone(), f(&low).unwrap()),
+            (high.clone(), f(&high).unwrap()),
+            &tolerance,
+        )
+        .unwrap();
+        assert!((crate::sin_cos::dbig_to_f64(&root) - 2f64.cbrt()).abs() < 1e-15);
+    }
+
+    #[test]
+    fn golden_section_finds_parabola_minimum() {
+        let f = |x: &DBig| Some((x.clone() - f64_to_dbig(1.25)) * (x.clone() - f64_to_dbig(1.25)));
+        let tolerance = f64_to_dbig(1e-12);
+        let minimum = find_minimum(f, f64_to_dbig(-4.0), f64_to_dbig(7.0), &tolerance).unwrap();
+        assert!((crate::sin_cos::dbig_to_f64(&minimum) - 1.25).abs() < 1e-11);
+    }
+}
@@ -0,0 +1,154 @@
+//! Slow motion of whole star systems: a circular orbit around a galactic center with a
+//! vertical oscillation through the galactic plane, the epicycle-free limit of a flat rotation
+//! curve. Periods are hundreds of millions of years, so systems close together drift apart
+//! almost linearly over the lifetime of a simulation, see [`crate::body::LinearBodyDynamics`].
+
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::sin_cos::{cos, f64_to_dbig, sin, PIMUL2};
+use crate::units::{Angle, Length, Time, Velocity};
+use dashu_float::ops::Abs;
+
+#[derive(Debug, Clone)]
+pub struct GalacticOrbitDynamics {
+    /// Position of the galactic center relative to the parent, or the global origin for roots.
+    pub center: DecimalVector3d,
+    /// Normal of the galactic plane, the orbit turns counter-clockwise around it.
+    pub pole: DecimalVector3d,
+    /// Distance from the galactic center.
+    pub orbit_radius: Length,
+    pub circular_velocity: Velocity,
+    /// Angle along the orbit at `time = 0`, measured from the in-plane direction closest to the
+    /// global x axis.
+    pub orbit_phase: Angle,
+    /// Largest distance from the galactic plane, reached a quarter `vertical_period` after
+    /// crossing it upwards at `time = 0`.
+    pub vertical_amplitude: Length,
+    pub vertical_period: Time,
+}
+
+impl GalacticOrbitDynamics {
+    /// An orbit in the galactic plane without vertical motion.
+    pub fn new(
+        center: DecimalVector3d,
+        pole: DecimalVector3d,
+        orbit_radius: Length,
+        circular_velocity: Velocity,
+        orbit_phase: Angle,
+    ) -> GalacticOrbitDynamics {
+        GalacticOrbitDynamics {
+            center,
+            pole,
+            orbit_radius,
+            circular_velocity,
+            orbit_phase,
+            vertical_amplitude: Length::zero(),
+            vertical_period: Time::zero(),
+        }
+    }
+
+    /// Time one revolution around the galactic center takes.
+    pub fn orbit_period(&self) -> Time {
+        &(&self.orbit_radius * &PIMUL2.clone()) / &self.circular_velocity
+    }
+
+    /// In-plane unit vectors, the reference direction and the one a quarter turn ahead of it.
+    fn plane_axes(&self) -> (DecimalVector3d, DecimalVector3d) {
+        let pole = self.pole.normalized();
+        let mut reference = DecimalVector3d::from_f64(1.0, 0.0, 0.0);
+        if reference.dot(pole.clone()).abs() > f64_to_dbig(0.999) {
+            reference = DecimalVector3d::from_f64(0.0, 0.0, 1.0);
+        }
+        let reference =
+            (reference.clone() - pole.clone() * reference.dot(pole.clone())).normalized();
+        let ahead = pole.cross(reference.clone());
+        (reference, ahead)
+    }
+
+    /// Position relative to the parent at `time`.
+    pub fn position_at(&self, time: &Time) -> DecimalVector3d {
+        let (reference, ahead) = self.plane_axes();
+        let turns = (time / &self.orbit_period()).fract();
+        let angle = PIMUL2.clone() * turns + self.orbit_phase.to_radians();
+        let radius = self.orbit_radius.to_meters();
+        let mut position = self.center.clone()
+            + reference * (radius.clone() * cos(angle.clone(), 32))
+            + ahead * (radius * sin(angle, 32));
+        if self.vertical_period != Time::zero() {
+            let phase = PIMUL2.clone() * (time / &self.vertical_period).fract();
+            position +=
+                self.pole.normalized() * (self.vertical_amplitude.to_meters() * sin(phase, 32));
+        }
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, BodyDynamics, LinearBodyDynamics};
+    use crate::simulation::Simulation;
+    use crate::sin_cos::dbig_to_f64;
+    use crate::units::Mass;
+    use dashu_float::DBig;
+
+    fn star(name: &str, dynamics: BodyDynamics) -> Body {
+        Body {
+            rotation_period: Time::from_days(DBig::from(25)),
+            ..Body::new(
+                name,
+                Mass::from_kilograms(f64_to_dbig(2e30)),
+                Length::from_meters(f64_to_dbig(7e8)),
+                dynamics,
+            )
+        }
+    }
+
+    #[test]
+    fn star_systems_drift_apart() {
+        let mut sim = Simulation::new();
+        let drifting = sim.add_hierarchy(
+            star(
+                "drifting",
+                BodyDynamics::Linear(LinearBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                    velocity: DecimalVector3d::from_f64(2e4, 0.0, 0.0),
+                }),
+            ),
+            None,
+        );
+        let galactic = GalacticOrbitDynamics {
+            vertical_amplitude: Length::from_parsecs(DBig::from(100)),
+            vertical_period: Time::from_julian_years(f64_to_dbig(7e7)),
+            ..GalacticOrbitDynamics::new(
+                DecimalVector3d::from_f64(-2.5e20, 0.0, 0.0),
+                DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                Length::from_meters(f64_to_dbig(2.5e20)),
+                Velocity::from_kilometers_per_second(DBig::from(220)),
+                Angle::zero(),
+            )
+        };
+        let period = dbig_to_f64(&galactic.orbit_period().to_julian_years());
+        assert!((period / 2.27e8 - 1.0).abs() < 0.01, "{period}");
+        let orbiting = sim.add_hierarchy(star("orbiting", BodyDynamics::Galactic(galactic)), None);
+
+        sim.update(Time::zero());
+        assert!(dbig_to_f64(&sim.get_distance(drifting, orbiting).unwrap().to_meters()) < 1e-3);
+
+        let year = Time::from_julian_years(DBig::ONE);
+        sim.update(year);
+        let drifting_state = sim.get_body_by_id(drifting).unwrap();
+        assert!((dbig_to_f64(&drifting_state.position().x) - 2e4 * 3.15576e7).abs() < 1e-3);
+        assert!((dbig_to_f64(&drifting_state.velocity().x) - 2e4).abs() < 1e-9);
+        let orbiting_state = sim.get_body_by_id(orbiting).unwrap();
+        let speed = dbig_to_f64(&orbiting_state.velocity().length());
+        let vertical = 2.0 * std::f64::consts::PI * 3.0857e18 / (7e7 * 3.15576e7);
+        assert!(
+            (speed - (2.2e5f64.powi(2) + vertical.powi(2)).sqrt()).abs() < 1.0,
+            "{speed}"
+        );
+        // moving along -z and y while the other star moves along x
+        let distance = dbig_to_f64(&sim.get_distance(drifting, orbiting).unwrap().to_meters());
+        let expected = (2e4f64.hypot(2.2e5).hypot(vertical)) * 3.15576e7;
+        assert!((distance / expected - 1.0).abs() < 1e-6, "{distance}");
+    }
+}
@@ -0,0 +1,600 @@
+//! Tables printed by JPL Horizons, <https://ssd.jpl.nasa.gov/horizons/>, in the text or CSV
+//! layout of the vector and osculating elements ephemeris types.
+//!
+//! Julian dates are read as TDB and turned into times since [`Epoch::j2000`], the simulation
+//! times at the default epoch, see [`crate::simulation::Simulation::time_of`] for others.
+//! Bodies following the fitted records play back the right instant at any epoch.
+//!
+//! Horizons frames have z up, positions and velocities are turned into the simulation's y-up
+//! frame by mapping (x, y, z) to (x, z, -y). Astronomical units and masses derived from GM
+//! follow the [`Constants`] passed to [`HorizonsTable::parse_with`].
+
+use crate::body::{Body, BodyDynamics, EphemerisBodyDynamics};
+use crate::chebyshev::ChebyshevBody;
+use crate::constants::Constants;
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::playback::PlaybackError;
+use crate::reference_frame::StateVector;
+use crate::sin_cos::{f64_to_dbig, precise};
+use crate::time::{Epoch, TimeScale, J2000_JULIAN_DATE, SECONDS_PER_DAY};
+use crate::units::{Length, Mass, Time};
+use dashu_float::DBig;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HorizonsError {
+    /// The `$$SOE`/`$$EOE` markers or the named header line are missing.
+    MissingHeader(&'static str),
+    /// Line `line`, counted from 1, could not be read as part of a record.
+    InvalidRecord {
+        line: usize,
+    },
+    /// Body `id` has an interval with too few records to fit the requested degree.
+    InsufficientSamples {
+        id: i32,
+    },
+    /// Table of body `id` is relative to `center`, which is neither the root nor another table.
+    UnknownCenter {
+        id: i32,
+        center: i32,
+    },
+    /// Table of body `id` lacks the GM or radius needed to turn it into a body.
+    MissingPhysicalData {
+        id: i32,
+    },
+    /// The `Reference frame` header names a frame other than the ecliptic or the equator of
+    /// J2000, or an equatorial frame for a table of orbital elements.
+    UnsupportedFrame(String),
+    Playback(PlaybackError),
+}
+
+impl fmt::Display for HorizonsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HorizonsError::MissingHeader(header) => write!(f, "missing {header} in table"),
+            HorizonsError::InvalidRecord { line } => write!(f, "invalid record on line {line}"),
+            HorizonsError::InsufficientSamples { id } => {
+                write!(f, "too few records of body {id} to fit an interval")
+            }
+            HorizonsError::UnknownCenter { id, center } => {
+                write!(f, "body {id} is relative to unknown body {center}")
+            }
+            HorizonsError::MissingPhysicalData { id } => {
+                write!(f, "no GM or radius printed for body {id}")
+            }
+            HorizonsError::UnsupportedFrame(frame) => {
+                write!(f, "unsupported reference frame {frame}")
+            }
+            HorizonsError::Playback(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for HorizonsError {}
+
+impl From<PlaybackError> for HorizonsError {
+    fn from(error: PlaybackError) -> Self {
+        HorizonsError::Playback(error)
+    }
+}
+
+/// A body as Horizons names it, `id` is its NAIF id (399 for Earth).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NaifBody {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct VectorRecord {
+    pub julian_date: DBig,
+    pub time: Time, // TDB since J2000.0
+    /// Relative to the table's center, in meters and meters per second.
+    pub state: StateVector,
+}
+
+#[derive(Debug, Clone)]
+pub struct ElementsRecord {
+    pub julian_date: DBig,
+    pub time: Time, // TDB since J2000.0
+    /// Elements by their Horizons label (`EC`, `QR`, `IN`, `OM`, `W`, `Tp`, `N`, `MA`, `TA`,
+    /// `A`, `AD`, `PR`), as printed in the table's output units.
+    pub values: BTreeMap<String, DBig>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HorizonsTable {
+    pub target: NaifBody,
+    pub center: NaifBody,
+    /// Physical data of the target, present when Horizons printed it above the table.
+    pub mass: Option<Mass>, // derived from GM
+    pub radius: Option<Length>, // mean
+    pub flattening: Option<DBig>,
+    pub rotation_period: Option<Time>, // sidereal
+    pub vectors: Vec<VectorRecord>,
+    pub elements: Vec<ElementsRecord>,
+}
+
+fn parse_number(text: &str) -> Option<DBig> {
+    DBig::from_str(text.trim()).ok().map(precise)
+}
+
+fn parse_naif_body(line: &str) -> Option<NaifBody> {
+    let (_, value) = line.split_once(':')?;
+    let value = value.split('{').next()?;
+    let open = value.rfind('(')?;
+    let close = open + value[open..].find(')')?;
+    Some(NaifBody {
+        id: value[open + 1..close].trim().parse().ok()?,
+        name: value[..open].trim().to_string(),
+    })
+}
+
+/// First value after `label` and the next `=` in the header, uncertainties like `+-0.02` and
+/// fractions like `1/298.257` are resolved. Also returns the word following the value, usually
+/// its unit.
+fn header_value<'a>(header: &[&'a str], label: &str) -> Option<(DBig, Option<&'a str>)> {
+    let line = header.iter().find(|line| line.contains(label))?;
+    let after = &line[line.find(label)? + label.len()..];
+    let mut words = after[after.find('=')? + 1..].split_whitespace();
+    let value = words.next()?.split("+-").next()?;
+    let number = match value.split_once('/') {
+        Some((numerator, denominator)) => parse_number(numerator)? / parse_number(denominator)?,
+        None => parse_number(value)?,
+    };
+    Some((number, words.next()))
+}
+
+/// Meters per distance unit and seconds per time unit of the `Output units` header.
+fn output_units(header: &[&str], constants: &Constants) -> (DBig, DBig) {
+    let units = header
+        .iter()
+        .find_map(|line| line.trim().strip_prefix("Output units"))
+        .and_then(|line| line.split_once(':'))
+        .map_or("KM-S", |(_, units)| units.split(',').next().unwrap_or(""))
+        .trim();
+    let (distance, time) = units.split_once('-').unwrap_or(("KM", "S"));
+    let distance = match distance {
+        "AU" => constants.astronomical_unit.to_meters(),
+        _ => DBig::from(1000),
+    };
+    let time = match time {
+        "D" => DBig::from(SECONDS_PER_DAY),
+        _ => DBig::ONE,
+    };
+    (precise(distance), precise(time))
+}
+
+/// Julian date starting a record, the line reads `2451545.000000000 = A.D. 2000-Jan-01 ...` or
+/// `2451545.000000000, A.D. 2000-Jan-01 ...`.
+fn record_start(line: &str) -> Option<DBig> {
+    if !line.contains("A.D.") && !line.contains("B.C.") {
+        return None;
+    }
+    parse_number(line.split(['=', ',']).next()?)
+}
+
+/// `KEY=value` pairs of a text layout line, keys may be padded like `W =`.
+fn labelled_values(line: &str) -> Option<Vec<(String, DBig)>> {
+    let parts: Vec<&str> = line.split('=').collect();
+    let mut values = vec![];
+    for pair in parts.windows(2) {
+        let key = pair[0].split_whitespace().last()?;
+        let value = pair[1].split_whitespace().next()?;
+        values.push((key.to_string(), parse_number(value)?));
+    }
+    Some(values)
+}
+
+/// Obliquity of the ecliptic at J2000 that NAIF uses for ECLIPJ2000 and Horizons for its
+/// ecliptic tables, in arcseconds.
+pub(crate) const J2000_OBLIQUITY_ARCSECONDS: f64 = 84381.448;
+
+/// Turns a z-up Horizons or SPK vector into the simulation's y-up frame.
+pub(crate) fn y_up(x: DBig, y: DBig, z: DBig) -> DecimalVector3d {
+    DecimalVector3d::new(x, z, -y)
+}
+
+impl HorizonsTable {
+    /// Reads one table. Records holding `X`, `Y`, `Z`, `VX`, `VY` and `VZ` become
+    /// [`VectorRecord`]s, records holding `EC` become [`ElementsRecord`]s. Vectors in the ICRF
+    /// or another frame on the equator of J2000 are rotated onto the ecliptic of J2000, which
+    /// tables without a `Reference frame` header are taken to be in. Uses the IAU 2012
+    /// constants.
+    pub fn parse(text: &str) -> Result<HorizonsTable, HorizonsError> {
+        HorizonsTable::parse_with(text, &Constants::default())
+    }
+
+    /// Reads one table, converting astronomical units and GM with `constants`.
+    pub fn parse_with(text: &str, constants: &Constants) -> Result<HorizonsTable, HorizonsError> {
+        let lines: Vec<&str> = text.lines().collect();
+        let start = lines
+            .iter()
+            .position(|line| line.trim() == "$$SOE")
+            .ok_or(HorizonsError::MissingHeader("$$SOE"))?;
+        let end = start
+            + lines[start..]
+                .iter()
+                .position(|line| line.trim() == "$$EOE")
+                .ok_or(HorizonsError::MissingHeader("$$EOE"))?;
+        let header = &lines[..start];
+        let find_body = |label: &'static str| {
+            header
+                .iter()
+                .filter(|line| line.trim().starts_with(label))
+                .find_map(|line| parse_naif_body(line))
+                .ok_or(HorizonsError::MissingHeader(label))
+        };
+        let target = find_body("Target body name")?;
+        let center = find_body("Center body name")?;
+        // CSV columns are named by the last header line starting with JDTDB.
+        let columns: Vec<String> = header
+            .iter()
+            .rev()
+            .find(|line| line.trim().starts_with("JDTDB"))
+            .map(|line| line.split(',').map(|c| c.trim().to_string()).collect())
+            .unwrap_or_default();
+
+        let mut records: Vec<(DBig, BTreeMap<String, DBig>)> = vec![];
+        for (index, line) in lines.iter().enumerate().take(end).skip(start + 1) {
+            let invalid = || HorizonsError::InvalidRecord { line: index + 1 };
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(julian_date) = record_start(line) {
+                let mut values = BTreeMap::new();
+                if line.contains(',') {
+                    for (column, value) in columns.iter().zip(line.split(',')).skip(2) {
+                        if !value.trim().is_empty() {
+                            values.insert(column.clone(), parse_number(value).ok_or_else(invalid)?);
+                        }
+                    }
+                }
+                records.push((julian_date, values));
+            } else {
+                let (_, values) = records.last_mut().ok_or_else(invalid)?;
+                values.extend(labelled_values(line).ok_or_else(invalid)?);
+            }
+        }
+
+        // ICRF tables are rotated onto the ecliptic, the frame bias of a few milliarcseconds
+        // between the ICRF and the equator of J2000 is ignored like NAIF does
+        let frame = header
+            .iter()
+            .find_map(|line| line.trim().strip_prefix("Reference frame"))
+            .and_then(|line| line.split_once(':'))
+            .map(|(_, frame)| frame.trim());
+        let equatorial = match frame {
+            None => false,
+            Some(frame) if frame.starts_with("Ecliptic") => false,
+            Some(frame) if frame.starts_with("ICRF") || frame.contains("Equator") => true,
+            Some(frame) => return Err(HorizonsError::UnsupportedFrame(frame.to_string())),
+        };
+        let (sin, cos) = (J2000_OBLIQUITY_ARCSECONDS / 3600.0).to_radians().sin_cos();
+        let (sin, cos) = (f64_to_dbig(sin), f64_to_dbig(cos));
+
+        let (meters, seconds) = output_units(header, constants);
+        let velocity_scale = meters.clone() / &seconds;
+        let mut vectors = vec![];
+        let mut elements = vec![];
+        for (julian_date, values) in records {
+            let time = Time::from_seconds(
+                (julian_date.clone() - DBig::from(J2000_JULIAN_DATE)) * DBig::from(SECONDS_PER_DAY)
+                    - Epoch::j2000().seconds_since_j2000(TimeScale::Tdb),
+            );
+            let vector = |keys: [&str; 3], scale: &DBig| {
+                let [x, y, z] = keys.map(|key| values.get(key).cloned());
+                let (x, y, z) = (x? * scale, y? * scale, z? * scale);
+                Some(match equatorial {
+                    // around the x axis, which both frames point at the equinox
+                    true => y_up(
+                        x,
+                        cos.clone() * &y + &sin * &z,
+                        cos.clone() * z - sin.clone() * y,
+                    ),
+                    false => y_up(x, y, z),
+                })
+            };
+            if let (Some(position), Some(velocity)) = (
+                vector(["X", "Y", "Z"], &meters),
+                vector(["VX", "VY", "VZ"], &velocity_scale),
+            ) {
+                vectors.push(VectorRecord {
+                    julian_date,
+                    time,
+                    state: StateVector::new(position, velocity),
+                });
+            } else if values.contains_key("EC") {
+                elements.push(ElementsRecord {
+                    julian_date,
+                    time,
+                    values,
+                });
+            }
+        }
+
+        if let (true, Some(frame)) = (equatorial, frame) {
+            if !elements.is_empty() {
+                return Err(HorizonsError::UnsupportedFrame(frame.to_string()));
+            }
+        }
+
+        let gravitational_parameter = header_value(header, "GM, km^3/s^2")
+            .or_else(|| header_value(header, "GM (km^3/s^2)"))
+            .map(|(gm, _)| gm * DBig::from(1_000_000_000));
+        let rotation_period =
+            header_value(header, "Sidereal rot. period").map(|(period, unit)| match unit {
+                Some("hr" | "h") => Time::from_hours(period),
+                Some("d") => Time::from_days(period),
+                _ => Time::from_seconds(period),
+            });
+        Ok(HorizonsTable {
+            target,
+            center,
+            // Horizons prints GM
+            mass: gravitational_parameter.map(|gm| constants.mass_from_gm(&gm)),
+            radius: header_value(header, "Mean Radius").map(|(km, _)| Length::from_kilometers(km)),
+            flattening: header_value(header, "Flattening").map(|(flattening, _)| flattening),
+            rotation_period,
+            vectors,
+            elements,
+        })
+    }
+
+    /// Fits the vector records with polynomials of `degree` over intervals of `interval`, see
+    /// [`ChebyshevBody::fit_samples`]. Ids are NAIF ids.
+    pub fn fit_chebyshev(
+        &self,
+        interval: Time,
+        degree: usize,
+    ) -> Result<ChebyshevBody, HorizonsError> {
+        let samples: Vec<(Time, DecimalVector3d)> = self
+            .vectors
+            .iter()
+            .map(|record| (record.time.clone(), record.state.position.clone()))
+            .collect();
+        ChebyshevBody::fit_samples(
+            self.target.id,
+            self.target.name.clone(),
+            Some(self.center.id),
+            &samples,
+            interval,
+            degree,
+        )
+        .ok_or(HorizonsError::InsufficientSamples { id: self.target.id })
+    }
+
+    /// The target as a body following the fitted vector records, without satellites and with
+    /// the rotation axis along the y axis. NAIF ids 0 to 9, the solar system and planetary
+    /// barycenters, become [`Body::barycenter`] nodes; other targets need the GM and radius
+    /// printed in the header.
+    pub fn to_body(
+        &self,
+        interval: Time,
+        degree: usize,
+        max_error: f64,
+    ) -> Result<Body, HorizonsError> {
+        let segments = self.fit_chebyshev(interval, degree)?;
+        let dynamics = BodyDynamics::Ephemeris(EphemerisBodyDynamics::new(segments, max_error)?);
+        if (0..=9).contains(&self.target.id) {
+            return Ok(Body::barycenter(&self.target.name, dynamics, vec![]));
+        }
+        let (Some(mass), Some(radius)) = (&self.mass, &self.radius) else {
+            return Err(HorizonsError::MissingPhysicalData { id: self.target.id });
+        };
+        Ok(Body {
+            flattening: self.flattening.clone(),
+            rotation_period: self.rotation_period.clone().unwrap_or_else(Time::zero),
+            ..Body::new(&self.target.name, mass.clone(), radius.clone(), dynamics)
+        })
+    }
+}
+
+/// Attaches every table as a satellite of the body its center names, starting at `root` with
+/// NAIF id `root_id` (10 for a heliocentric Sun). See [`HorizonsTable::to_body`].
+pub fn build_hierarchy(
+    mut root: Body,
+    root_id: i32,
+    tables: &[HorizonsTable],
+    interval: Time,
+    degree: usize,
+    max_error: f64,
+) -> Result<Body, HorizonsError> {
+    fn attach(
+        body: &mut Body,
+        id: i32,
+        tables: &[HorizonsTable],
+        attached: &mut HashSet<i32>,
+        fit: &dyn Fn(&HorizonsTable) -> Result<Body, HorizonsError>,
+    ) -> Result<(), HorizonsError> {
+        for table in tables.iter().filter(|table| table.center.id == id) {
+            if !attached.insert(table.target.id) {
+                continue;
+            }
+            let mut satellite = fit(table)?;
+            attach(&mut satellite, table.target.id, tables, attached, fit)?;
+            body.satellites.push(satellite);
+        }
+        Ok(())
+    }
+
+    let mut attached = HashSet::from([root_id]);
+    let fit = |table: &HorizonsTable| table.to_body(interval.clone(), degree, max_error);
+    attach(&mut root, root_id, tables, &mut attached, &fit)?;
+    match tables
+        .iter()
+        .find(|table| !attached.contains(&table.target.id))
+    {
+        Some(table) => Err(HorizonsError::UnknownCenter {
+            id: table.target.id,
+            center: table.center.id,
+        }),
+        None => Ok(root),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::StaticBodyDynamics;
+    use crate::simulation::Simulation;
+    use crate::sin_cos::{dbig_to_f64, f64_to_dbig};
+    use std::f64::consts::PI;
+
+    const EARTH: &str = include_str!("../fixtures/horizons_earth_vectors.txt");
+    const MOON: &str = include_str!("../fixtures/horizons_moon_vectors.csv");
+    const ELEMENTS: &str = include_str!("../fixtures/horizons_earth_elements.txt");
+
+    #[test]
+    fn parses_vector_and_element_tables() {
+        let earth = HorizonsTable::parse(EARTH).unwrap();
+        assert_eq!(
+            earth.center,
+            NaifBody {
+                id: 10,
+                name: String::from("Sun")
+            }
+        );
+        assert_eq!(earth.target.id, 399);
+        assert_eq!(earth.vectors.len(), 21);
+        assert_eq!(
+            dbig_to_f64(&earth.radius.as_ref().unwrap().to_meters()),
+            6371010.0
+        );
+        assert!(
+            (dbig_to_f64(&earth.mass.as_ref().unwrap().to_kilograms()) / 5.972e24 - 1.0).abs()
+                < 1e-3
+        );
+        assert!((dbig_to_f64(earth.flattening.as_ref().unwrap()) - 0.0033528).abs() < 1e-6);
+        assert!(
+            (dbig_to_f64(&earth.rotation_period.as_ref().unwrap().to_seconds()) - 86163.084).abs()
+                < 1e-3
+        );
+        let first = &earth.vectors[0];
+        // J2000.0 is defined in TT, TDB is 71 microseconds behind it
+        assert!(dbig_to_f64(&first.time.to_seconds()).abs() < 1e-4);
+        assert_eq!(dbig_to_f64(&first.state.position.x), 1.496e11);
+        let day = dbig_to_f64(&(&earth.vectors[1].time - &first.time).to_days());
+        assert!((day - 1.0).abs() < 1e-10);
+
+        let moon = HorizonsTable::parse(MOON).unwrap();
+        assert_eq!(moon.center.id, 399);
+        assert_eq!(moon.vectors.len(), 41);
+        assert!((dbig_to_f64(&moon.vectors[1].time.to_seconds()) - 43200.0).abs() < 1e-4);
+        // z up in the table, y up in the simulation
+        let velocity = &moon.vectors[0].state.velocity;
+        assert_eq!(dbig_to_f64(&velocity.y), 91.95989356909781);
+        assert_eq!(dbig_to_f64(&velocity.z), -1019.016307504682);
+
+        let elements = HorizonsTable::parse(ELEMENTS).unwrap();
+        assert!(elements.vectors.is_empty());
+        let values = &elements.elements[0].values;
+        assert_eq!(values.len(), 12);
+        assert_eq!(dbig_to_f64(&values["W"]), 288.0);
+        assert_eq!(dbig_to_f64(&values["Tp"]), 2451547.0);
+
+        // the same table printed in astronomical units moves with the chosen au
+        let in_au = EARTH.replace("KM-S", "AU-D");
+        let x = |constants: &Constants| {
+            let table = HorizonsTable::parse_with(&in_au, constants).unwrap();
+            dbig_to_f64(&table.vectors[0].state.position.x)
+        };
+        let difference = x(&Constants::iau_2012()) - x(&Constants::iau_1976());
+        assert!((difference / (1.496e8 * 700.0) - 1.0).abs() < 1e-6);
+
+        // equatorial tables are turned onto the ecliptic, the x axis stays on the equinox
+        let equatorial = EARTH.replace("Ecliptic of J2000.0", "ICRF");
+        let equatorial = HorizonsTable::parse(&equatorial).unwrap();
+        let (sin, cos) = (J2000_OBLIQUITY_ARCSECONDS / 3600.0).to_radians().sin_cos();
+        let ecliptic = &earth.vectors[5].state.position;
+        let rotated = &equatorial.vectors[5].state.position;
+        assert_eq!(rotated.x, ecliptic.x);
+        assert!((dbig_to_f64(&rotated.y) - sin * dbig_to_f64(&ecliptic.z)).abs() < 1e-3);
+        assert!((dbig_to_f64(&rotated.z) - cos * dbig_to_f64(&ecliptic.z)).abs() < 1e-3);
+        let galactic = EARTH.replace("Ecliptic of J2000.0", "Galactic");
+        assert_eq!(
+            HorizonsTable::parse(&galactic).unwrap_err(),
+            HorizonsError::UnsupportedFrame(String::from("Galactic"))
+        );
+        let equatorial = ELEMENTS.replace("Output units", "Reference frame : ICRF\nOutput units");
+        assert_eq!(
+            HorizonsTable::parse(&equatorial).unwrap_err(),
+            HorizonsError::UnsupportedFrame(String::from("ICRF"))
+        );
+
+        assert_eq!(
+            HorizonsTable::parse("Target body name: Earth (399)").unwrap_err(),
+            HorizonsError::MissingHeader("$$SOE")
+        );
+    }
+
+    #[test]
+    fn builds_ephemeris_hierarchy() {
+        let tables = [
+            HorizonsTable::parse(MOON).unwrap(),
+            HorizonsTable::parse(EARTH).unwrap(),
+        ];
+        let sun = Body {
+            rotation_period: Time::from_seconds(f64_to_dbig(2.19e6)),
+            ..Body::new(
+                "Sun",
+                Mass::from_kilograms(f64_to_dbig(1.989e30)),
+                Length::from_meters(f64_to_dbig(6.957e8)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            )
+        };
+        let interval = Time::from_days(DBig::from(10));
+        assert_eq!(
+            build_hierarchy(sun.clone(), 0, &tables, interval.clone(), 10, 1.0).unwrap_err(),
+            HorizonsError::UnknownCenter {
+                id: 301,
+                center: 399
+            }
+        );
+        // without physical data a body cannot be told apart from a barycenter
+        let mut unknown = tables[1].clone();
+        unknown.mass = None;
+        assert_eq!(
+            unknown.to_body(interval.clone(), 10, 1.0).unwrap_err(),
+            HorizonsError::MissingPhysicalData { id: 399 }
+        );
+        unknown.target.id = 3;
+        assert!(unknown
+            .to_body(interval.clone(), 10, 1.0)
+            .unwrap()
+            .is_barycenter());
+        let root = build_hierarchy(sun, 10, &tables, interval, 10, 1.0).unwrap();
+        assert_eq!(root.satellites[0].name, "Earth");
+        assert_eq!(root.satellites[0].satellites[0].name, "Moon");
+
+        let mut sim = Simulation::new();
+        sim.add_hierarchy(root, None);
+        let time = 7.3 * 86400.0;
+        sim.update(Time::from_seconds(f64_to_dbig(time)));
+        // the fixtures count from JD 2451545.0 TDB
+        let time = time + dbig_to_f64(&Epoch::j2000().seconds_since_j2000(TimeScale::Tdb));
+        let angle = 2.0 * PI * time / (365.25 * 86400.0);
+        let expected =
+            DecimalVector3d::from_f64(1.496e11 * angle.cos(), 0.0, -1.496e11 * angle.sin());
+        let earth = sim.get_body_by_id(1).unwrap().position();
+        assert!(dbig_to_f64(&earth.distance_to(expected)) < 1.0);
+
+        let angle = 2.0 * PI * time / (27.321661 * 86400.0);
+        let (sin, cos) = (angle.sin() * 3.844e8, angle.cos() * 3.844e8);
+        let expected = DecimalVector3d::from_f64(cos, sin * 0.09f64.sin(), -sin * 0.09f64.cos());
+        let moon = sim.get_body_by_id(2).unwrap().position() - earth;
+        assert!(dbig_to_f64(&moon.distance_to(expected)) < 1.0);
+
+        // the same instant a day into a simulation starting a week after J2000.0
+        let earth = earth.clone();
+        let week = Time::from_days(DBig::from(7));
+        sim.set_epoch(Epoch::j2000().add_seconds(week.to_seconds(), TimeScale::Tdb));
+        sim.update(Time::from_seconds(f64_to_dbig(0.3 * 86400.0)));
+        let shifted = sim.get_body_by_id(1).unwrap().position().clone();
+        assert!(dbig_to_f64(&shifted.distance_to(earth)) < 1e-3);
+    }
+}
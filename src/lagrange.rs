@@ -0,0 +1,239 @@
+//! Lagrange points of a body and its parent, the five places where a massless object keeps
+//! its position relative to both.
+//!
+//! The collinear points L1 to L3 come from the quintic of the circular restricted three-body
+//! problem, solved for the mass ratio of the pair, and L4 and L5 complete equilateral triangles
+//! with the two bodies, 60 degrees ahead of and behind the body. The configuration turns and,
+//! on an eccentric orbit, stretches with the body's position relative to its parent, which
+//! keeps the points exact for the elliptic problem as well.
+
+use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics};
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::events::find_root;
+use crate::reference_frame::StateVector;
+use crate::simulation::Simulation;
+use crate::sin_cos::{cos, f64_to_dbig, precise, sin, PI};
+use crate::units::{Angle, Length, Mass, Time};
+use dashu_float::ops::Abs;
+use dashu_float::DBig;
+
+/// Collinear points are refined to this fraction of the distance between the bodies.
+const TOLERANCE: f64 = 1e-25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagrangePoint {
+    /// Between the parent and the body.
+    L1,
+    /// Beyond the body, seen from the parent.
+    L2,
+    /// Opposite the body, beyond the parent.
+    L3,
+    /// 60 degrees ahead of the body along its orbit.
+    L4,
+    /// 60 degrees behind the body.
+    L5,
+}
+
+/// `vector` turned by `angle` around the unit vector `axis`, counter-clockwise seen from its tip.
+fn rotated(vector: &DecimalVector3d, axis: &DecimalVector3d, angle: DBig) -> DecimalVector3d {
+    let (sin, cos) = (sin(angle.clone(), 32), cos(angle, 32));
+    vector.clone() * &cos
+        + axis.cross(vector.clone()) * sin
+        + axis.clone() * (axis.dot(vector.clone()) * (DBig::ONE - cos))
+}
+
+/// Net acceleration along the line through both bodies in their rotating frame, in units of
+/// the separation and mean motion, at `x` from the barycenter. `mu` is the body's share of the
+/// total mass, the parent sits at `-mu` and the body at `1 - mu`.
+fn collinear_balance(x: &DBig, mu: &DBig) -> DBig {
+    let to_parent = x.clone() + mu;
+    let to_body = x.clone() - DBig::ONE + mu;
+    let cube = |d: &DBig| {
+        let d = d.clone().abs();
+        d.clone() * &d * d
+    };
+    x.clone()
+        - (DBig::ONE - mu) * &to_parent / cube(&to_parent)
+        - mu.clone() * &to_body / cube(&to_body)
+}
+
+/// Distance of collinear point `point` from the parent in units of the separation, negative
+/// for L3 on the far side.
+pub(crate) fn collinear_distance(point: LagrangePoint, mu: &DBig) -> Option<DBig> {
+    let mu = precise(mu.clone());
+    if mu == DBig::ZERO {
+        return Some(match point {
+            LagrangePoint::L3 => -DBig::ONE,
+            _ => DBig::ONE,
+        });
+    }
+    // a thousandth of the Hill radius keeps the brackets clear of the singularities
+    let gap =
+        (mu.clone() / DBig::from(3)).powf(&(DBig::ONE / precise(DBig::from(3)))) / DBig::from(1000);
+    let body = DBig::ONE - &mu;
+    let (low, high) = match point {
+        LagrangePoint::L1 => (-mu.clone() + &gap, body - gap),
+        LagrangePoint::L2 => (body + gap, DBig::from(2)),
+        LagrangePoint::L3 => (-DBig::from(2), -mu.clone() - f64_to_dbig(0.5)),
+        LagrangePoint::L4 | LagrangePoint::L5 => return None,
+    };
+    let balance = |x: &DBig| Some(collinear_balance(x, &mu));
+    let x = find_root(
+        balance,
+        (low.clone(), balance(&low)?),
+        (high.clone(), balance(&high)?),
+        &f64_to_dbig(TOLERANCE),
+    )?;
+    Some(x + mu)
+}
+
+impl Simulation {
+    /// State of Lagrange point `point` of body `id` and its parent at `time`, relative to the
+    /// parent. `None` for root bodies and massless pairs.
+    pub fn lagrange_point_at(
+        &self,
+        id: i32,
+        point: LagrangePoint,
+        time: &Time,
+    ) -> Option<StateVector> {
+        let parent = self.get_body_by_id(id)?.parent()?;
+        let (mass, parent_mass) = (self.central_mass(id)?, self.central_mass(parent)?);
+        let total = mass.clone() + &parent_mass;
+        if total == Mass::zero() {
+            return None;
+        }
+        let mu = &mass / &total;
+        let state = self.relative_state_at(time, id);
+        let (scale, angle) = match point {
+            LagrangePoint::L4 => (DBig::ONE, PI.clone() / DBig::from(3)),
+            LagrangePoint::L5 => (DBig::ONE, -PI.clone() / DBig::from(3)),
+            _ => (collinear_distance(point, &mu)?, DBig::ZERO),
+        };
+        let axis = state.position.cross(state.velocity.clone()).normalized();
+        Some(StateVector::new(
+            rotated(&state.position, &axis, angle.clone()) * &scale,
+            rotated(&state.velocity, &axis, angle) * scale,
+        ))
+    }
+
+    /// State of Lagrange point `point` of body `id` and its parent in the global frame at the
+    /// current simulation time.
+    pub fn lagrange_point(&self, id: i32, point: LagrangePoint) -> Option<StateVector> {
+        let parent = self.get_body_by_id(self.get_body_by_id(id)?.parent()?)?;
+        let relative = self.lagrange_point_at(id, point, self.time())?;
+        Some(StateVector::new(
+            relative.position + parent.position(),
+            relative.velocity + parent.velocity(),
+        ))
+    }
+}
+
+impl Body {
+    /// A body of `mass` and `radius` sharing the orbit of `host`, 60 degrees ahead of it at
+    /// [`LagrangePoint::L4`] or behind it at [`LagrangePoint::L5`]. It has to be added next to
+    /// `host`, under the same parent. `None` unless `host` is on an
+    /// [`OrbitingBodyDynamics`] orbit and `point` is L4 or L5.
+    pub fn trojan(
+        name: &str,
+        mass: Mass,
+        radius: Length,
+        host: &Body,
+        point: LagrangePoint,
+    ) -> Option<Body> {
+        let BodyDynamics::Orbiting(orbit) = &host.dynamics else {
+            return None;
+        };
+        let offset = match point {
+            LagrangePoint::L4 => Angle::from_degrees(DBig::from(60)),
+            LagrangePoint::L5 => Angle::from_degrees(DBig::from(-60)),
+            _ => return None,
+        };
+        Some(Body {
+            rotation_axis: host.rotation_axis.clone(),
+            ..Body::new(
+                name,
+                mass,
+                radius,
+                BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                    orbit_phase: orbit.orbit_phase.clone() + offset,
+                    ..orbit.clone()
+                }),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::StaticBodyDynamics;
+    use crate::sin_cos::dbig_to_f64;
+
+    fn body(name: &str, mass: f64, dynamics: BodyDynamics) -> Body {
+        Body::new(
+            name,
+            Mass::from_kilograms(f64_to_dbig(mass)),
+            Length::from_meters(f64_to_dbig(6.371e6)),
+            dynamics,
+        )
+    }
+
+    #[test]
+    fn sun_earth_lagrange_points() {
+        let earth = body(
+            "Earth",
+            5.972e24,
+            BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                orbit_radius: Length::from_au(DBig::ONE),
+                orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                orbit_period: Time::from_days(f64_to_dbig(365.256)),
+                orbit_phase: Angle::zero(),
+            }),
+        );
+        let asteroid = Body::trojan(
+            "Trojan",
+            Mass::from_kilograms(f64_to_dbig(1e15)),
+            Length::from_meters(DBig::from(5000)),
+            &earth,
+            LagrangePoint::L4,
+        )
+        .unwrap();
+        let mut sim = Simulation::new();
+        let sun = sim.add_hierarchy(
+            Body {
+                satellites: vec![earth, asteroid],
+                ..body(
+                    "Sun",
+                    1.989e30,
+                    BodyDynamics::Static(StaticBodyDynamics {
+                        position: DecimalVector3d::zero(),
+                    }),
+                )
+            },
+            None,
+        );
+        let (earth, trojan) = (sun + 1, sun + 2);
+        sim.update(Time::from_days(DBig::from(40)));
+
+        let earth_state = sim.get_body_by_id(earth).unwrap();
+        let distance_from_earth = |point| {
+            let state = sim.lagrange_point(earth, point).unwrap();
+            dbig_to_f64(&state.position.distance_to(earth_state.position().clone()))
+        };
+        // Hill radius 1.4966e9 m, L1 a little inside it and L2 a little outside
+        assert!((distance_from_earth(LagrangePoint::L1) / 1.4915e9 - 1.0).abs() < 1e-3);
+        assert!((distance_from_earth(LagrangePoint::L2) / 1.5014e9 - 1.0).abs() < 1e-3);
+        let au = 1.495978707e11;
+        assert!((distance_from_earth(LagrangePoint::L3) / (2.0 * au) - 1.0).abs() < 1e-6);
+        assert!((distance_from_earth(LagrangePoint::L4) / au - 1.0).abs() < 1e-9);
+
+        // the trojan sits at L4 and moves with it
+        let l4 = sim.lagrange_point(earth, LagrangePoint::L4).unwrap();
+        let trojan = sim.get_body_by_id(trojan).unwrap();
+        assert!(dbig_to_f64(&l4.position.distance_to(trojan.position().clone())) < 1.0);
+        assert!(dbig_to_f64(&l4.velocity.distance_to(trojan.velocity().clone())) < 1e-6);
+        let l5 = sim.lagrange_point(earth, LagrangePoint::L5).unwrap();
+        assert!(dbig_to_f64(&l5.position.distance_to(trojan.position().clone())) > au);
+        assert!(sim.lagrange_point(sun, LagrangePoint::L1).is_none());
+    }
+}
@@ -0,0 +1,293 @@
+//! Lambert's problem: the conics around a central body of gravitational parameter `mu` that
+//! lead from one position to another in a given time, solved with Izzo's method ("Revisiting
+//! Lambert's problem", 2015). It reduces every case to one time-of-flight equation in a single
+//! variable `x`, -1 < x < 1 on ellipses, 1 on the parabola and above on hyperbolas, solved by
+//! Householder iterations; each additional full revolution adds two solutions, a short and a
+//! long period one, as long as the time of flight allows.
+//!
+//! A transfer is prograde when it turns counter-clockwise around y, the simulation's north
+//! pole, see [`crate::orbital_elements`].
+
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::sin_cos::{acos, asinh, f64_to_dbig, precise, PI};
+use crate::units::Time;
+use dashu_float::ops::{Abs, SquareRoot};
+use dashu_float::DBig;
+
+/// Iterations stop once `x` moves less than this.
+const TOLERANCE: f64 = 1e-25;
+const MAX_ITERATIONS: usize = 50;
+
+#[derive(Debug, Clone)]
+pub struct LambertSolution {
+    /// Full revolutions completed on the way.
+    pub revolutions: u32,
+    /// Velocities at departure and arrival, in meters per second.
+    pub departure_velocity: DecimalVector3d,
+    pub arrival_velocity: DecimalVector3d,
+}
+
+fn two() -> DBig {
+    DBig::from(2)
+}
+
+fn compute_y(x: &DBig, ll: &DBig) -> DBig {
+    precise(DBig::ONE - ll.clone() * ll * (DBig::ONE - x.clone() * x)).sqrt()
+}
+
+/// `2F1(3, 1, 5/2, x)`, the series the time-of-flight equation needs close to the parabola.
+fn hypergeometric(x: &DBig) -> DBig {
+    let limit = f64_to_dbig(TOLERANCE);
+    let mut term = precise(DBig::ONE);
+    let mut result = term.clone();
+    let mut n = 0u32;
+    while term.clone().abs() > limit {
+        term = term * DBig::from(3 + n) * DBig::from(1 + n) / (f64_to_dbig(2.5) + DBig::from(n))
+            * x
+            / DBig::from(n + 1);
+        result += &term;
+        n += 1;
+    }
+    result
+}
+
+/// Non-dimensional time of flight minus `target` at `x`, `revolutions` full turns included.
+fn time_of_flight(x: &DBig, y: &DBig, target: &DBig, ll: &DBig, revolutions: u32) -> DBig {
+    let one_minus_x2 = DBig::ONE - x.clone() * x;
+    let near_parabola = x.clone() * x > f64_to_dbig(0.6) && x.clone() * x < f64_to_dbig(1.4);
+    let tof = if revolutions == 0 && near_parabola {
+        let eta = y.clone() - ll.clone() * x;
+        let s1 = (DBig::ONE - ll - x.clone() * &eta) / two();
+        let q = DBig::from(4) / precise(DBig::from(3)) * hypergeometric(&s1);
+        (eta.clone() * &eta * &eta * q + DBig::from(4) * ll * &eta) / two()
+    } else {
+        let psi = if one_minus_x2 > DBig::ZERO {
+            let cosine = (x.clone() * y + ll.clone() * &one_minus_x2)
+                .min(DBig::ONE)
+                .max(-DBig::ONE);
+            acos(cosine, 32)
+        } else {
+            asinh((y.clone() - x.clone() * ll) * precise(-one_minus_x2.clone()).sqrt())
+        };
+        let root = precise(one_minus_x2.clone().abs()).sqrt();
+        ((psi + DBig::from(revolutions) * PI.clone()) / root - x + ll.clone() * y) / one_minus_x2
+    };
+    tof - target
+}
+
+/// First three derivatives of the time of flight `tof` with respect to `x`.
+fn derivatives(x: &DBig, y: &DBig, tof: &DBig, ll: &DBig) -> (DBig, DBig, DBig) {
+    let one_minus_x2 = DBig::ONE - x.clone() * x;
+    let ll2 = ll.clone() * ll;
+    let ll3 = ll2.clone() * ll;
+    let ll5 = ll3.clone() * &ll2;
+    let y3 = y.clone() * y * y;
+    let y5 = y3.clone() * y * y;
+    let first = (DBig::from(3) * tof * x - two() + two() * &ll3 * y / x) / &one_minus_x2;
+    let second =
+        (DBig::from(3) * tof + DBig::from(5) * x * &first + two() * (DBig::ONE - &ll2) * &ll3 / y3)
+            / &one_minus_x2;
+    let third = (DBig::from(7) * x * &second + DBig::from(8) * &first
+        - DBig::from(6) * (DBig::ONE - ll2) * ll5 * x / y5)
+        / one_minus_x2;
+    (first, second, third)
+}
+
+/// `x` of the shortest time of flight with `revolutions` turns, by Halley iterations on its
+/// first derivative, and that time.
+fn minimum_time_of_flight(ll: &DBig, revolutions: u32) -> (DBig, DBig) {
+    let tolerance = f64_to_dbig(TOLERANCE);
+    let mut x = f64_to_dbig(0.1);
+    for _ in 0..MAX_ITERATIONS {
+        let y = compute_y(&x, ll);
+        let tof = time_of_flight(&x, &y, &DBig::ZERO, ll, revolutions);
+        let (first, second, third) = derivatives(&x, &y, &tof, ll);
+        if second == DBig::ZERO {
+            break;
+        }
+        let step = two() * &first * &second / (two() * &second * &second - first * third);
+        x -= &step;
+        if step.abs() < tolerance {
+            break;
+        }
+    }
+    let y = compute_y(&x, ll);
+    let tof = time_of_flight(&x, &y, &DBig::ZERO, ll, revolutions);
+    (x, tof)
+}
+
+fn initial_guess(tof: &DBig, ll: &DBig, revolutions: u32, low_path: bool) -> DBig {
+    let two_thirds = two() / precise(DBig::from(3));
+    if revolutions == 0 {
+        let t0 = acos(ll.clone(), 32) + ll.clone() * compute_y(&DBig::ZERO, ll);
+        let t1 = two() * (DBig::ONE - ll.clone() * ll * ll) / DBig::from(3);
+        if *tof >= t0 {
+            (t0 / tof).powf(&two_thirds) - DBig::ONE
+        } else if *tof < t1 {
+            f64_to_dbig(2.5) * &t1 / tof * (t1.clone() - tof)
+                / (DBig::ONE - ll.clone() * ll * ll * ll * ll)
+                + DBig::ONE
+        } else {
+            // corrected form of the guess right after (30) in the paper
+            (two().ln() * (tof.clone() / &t0).ln() / (t1 / t0).ln()).exp() - DBig::ONE
+        }
+    } else {
+        let turns = DBig::from(revolutions) * PI.clone();
+        let left = ((turns.clone() + PI.clone()) / (DBig::from(8) * tof)).powf(&two_thirds);
+        let right = (DBig::from(8) * tof / turns).powf(&two_thirds);
+        let left = (left.clone() - DBig::ONE) / (left + DBig::ONE);
+        let right = (right.clone() - DBig::ONE) / (right + DBig::ONE);
+        if low_path == (left > right) {
+            left
+        } else {
+            right
+        }
+    }
+}
+
+/// `x` solving the time-of-flight equation for `tof`, by Householder iterations.
+fn solve_x(tof: &DBig, ll: &DBig, revolutions: u32, low_path: bool) -> Option<DBig> {
+    let tolerance = f64_to_dbig(TOLERANCE);
+    let mut x = initial_guess(tof, ll, revolutions, low_path);
+    for _ in 0..MAX_ITERATIONS {
+        let y = compute_y(&x, ll);
+        let value = time_of_flight(&x, &y, tof, ll, revolutions);
+        let (first, second, third) = derivatives(&x, &y, &(value.clone() + tof), ll);
+        let step = value.clone()
+            * ((first.clone() * &first - value.clone() * &second / two())
+                / (first.clone() * (first.clone() * &first - value.clone() * &second)
+                    + third * &value * &value / DBig::from(6)));
+        x -= &step;
+        if step.abs() < tolerance {
+            return Some(x);
+        }
+    }
+    None
+}
+
+/// Transfers from `departure` to `arrival`, both relative to a central body with gravitational
+/// parameter `gravitational_parameter` in m^3/s^2, taking `time_of_flight`: the direct one first,
+/// then the short and long period ones for each number of revolutions up to
+/// `max_revolutions` the time allows. Empty when the positions are collinear with the central
+/// body, which leaves the transfer plane undefined, or the time is not positive.
+pub fn solve_lambert(
+    departure: &DecimalVector3d,
+    arrival: &DecimalVector3d,
+    time_of_flight: &Time,
+    gravitational_parameter: &DBig,
+    max_revolutions: u32,
+    prograde: bool,
+) -> Vec<LambertSolution> {
+    let mu = precise(gravitational_parameter.clone());
+    let chord = (arrival - departure).length();
+    let (r1, r2) = (departure.length(), arrival.length());
+    let normal = departure.cross(arrival.clone());
+    if time_of_flight <= &Time::zero() || normal.length() == DBig::ZERO || chord == DBig::ZERO {
+        return vec![];
+    }
+    let semiperimeter = (r1.clone() + &r2 + &chord) / two();
+    let (radial1, radial2) = (departure.clone() / &r1, arrival.clone() / &r2);
+    let normal = normal.normalized();
+    let mut ll = precise(DBig::ONE - (chord.clone() / &semiperimeter).min(DBig::ONE)).sqrt();
+    let (mut tangential1, mut tangential2) = if normal.y < DBig::ZERO {
+        ll = -ll;
+        (radial1.cross(normal.clone()), radial2.cross(normal))
+    } else {
+        (normal.cross(radial1.clone()), normal.cross(radial2.clone()))
+    };
+    if !prograde {
+        ll = -ll;
+        tangential1 = -tangential1;
+        tangential2 = -tangential2;
+    }
+
+    let tof = (two() * &mu / (semiperimeter.clone() * &semiperimeter * &semiperimeter)).sqrt()
+        * time_of_flight.to_seconds();
+    let gamma = (mu * &semiperimeter / two()).sqrt();
+    let rho = (r1.clone() - &r2) / &chord;
+    let sigma = precise(DBig::ONE - rho.clone() * &rho).sqrt();
+
+    let mut feasible = (tof.clone() / PI.clone()).floor();
+    let t00 = acos(ll.clone(), 32) + ll.clone() * compute_y(&DBig::ZERO, &ll);
+    if feasible > DBig::ZERO && tof < t00 + feasible.clone() * PI.clone() {
+        let turns = feasible.to_string().parse::<u32>().unwrap_or(u32::MAX);
+        if tof < minimum_time_of_flight(&ll, turns).1 {
+            feasible -= DBig::ONE;
+        }
+    }
+    let feasible = feasible.to_string().parse::<u32>().unwrap_or(u32::MAX);
+
+    let mut solutions = vec![];
+    for revolutions in 0..=max_revolutions.min(feasible) {
+        let paths: &[bool] = if revolutions == 0 {
+            &[true]
+        } else {
+            &[true, false]
+        };
+        for &low_path in paths {
+            let Some(x) = solve_x(&tof, &ll, revolutions, low_path) else {
+                continue;
+            };
+            let y = compute_y(&x, &ll);
+            let (ly_minus_x, ly_plus_x) = (ll.clone() * &y - &x, ll.clone() * &y + &x);
+            let radial_speed1 =
+                gamma.clone() * (ly_minus_x.clone() - rho.clone() * &ly_plus_x) / &r1;
+            let radial_speed2 = -gamma.clone() * (ly_minus_x + rho.clone() * ly_plus_x) / &r2;
+            let tangential = gamma.clone() * &sigma * (y + ll.clone() * x);
+            solutions.push(LambertSolution {
+                revolutions,
+                departure_velocity: radial1.clone() * radial_speed1
+                    + tangential1.clone() * (tangential.clone() / &r1),
+                arrival_velocity: radial2.clone() * radial_speed2
+                    + tangential2.clone() * (tangential / &r2),
+            });
+        }
+    }
+    solutions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conic::ConicDynamics;
+    use crate::reference_frame::StateVector;
+    use crate::sin_cos::dbig_to_f64;
+
+    const GM: f64 = 1.32712440018e20;
+
+    #[test]
+    fn transfers_arrive_on_time() {
+        let gm = f64_to_dbig(GM);
+        let departure = DecimalVector3d::from_f64(1.5e11, 1e9, 0.0);
+        let arrival = DecimalVector3d::from_f64(-1e11, -3e9, -1.9e11);
+        let time = Time::from_days(DBig::from(1000));
+        let solutions = solve_lambert(&departure, &arrival, &time, &gm, 1, true);
+        assert_eq!(solutions.len(), 3);
+        for solution in solutions {
+            let state = StateVector::new(departure.clone(), solution.departure_velocity.clone());
+            let conic = ConicDynamics::from_state(&state, &Time::zero(), gm.clone()).unwrap();
+            let reached = conic.state_at(&time);
+            let miss = dbig_to_f64(&reached.position.distance_to(arrival.clone()));
+            assert!(miss < 1.0, "{} {miss}", solution.revolutions);
+            let speed_error = reached
+                .velocity
+                .distance_to(solution.arrival_velocity.clone());
+            assert!(dbig_to_f64(&speed_error) < 1e-6);
+            // prograde around y
+            let normal = departure.cross(solution.departure_velocity);
+            assert!(normal.y > DBig::ZERO);
+        }
+        let retrograde = solve_lambert(&departure, &arrival, &time, &gm, 0, false);
+        let normal = departure.cross(retrograde[0].departure_velocity.clone());
+        assert!(normal.y < DBig::ZERO);
+        assert!(solve_lambert(
+            &departure,
+            &(departure.clone() * DBig::from(2)),
+            &time,
+            &gm,
+            0,
+            true
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+pub mod approach;
+pub mod attitude;
+pub mod au;
+pub mod barycenter;
+pub mod body;
+pub mod chebyshev;
+pub mod conic;
+pub mod constants;
+pub mod cr3bp;
+pub mod decimal_matrix_3d;
+pub mod decimal_vector_3d;
+pub mod eclipse;
+pub mod ephemeris;
+pub mod events;
+pub mod galactic;
+pub mod horizons;
+pub mod lagrange;
+pub mod lambert;
+pub mod maneuver;
+pub mod orbital_elements;
+pub mod patched_conic;
+pub mod playback;
+pub mod precession;
+pub mod reference_frame;
+pub mod rotation;
+pub mod simulation;
+pub mod sin_cos;
+pub mod solar;
+pub mod spk;
+pub mod surface;
+pub mod time;
+pub mod transfer;
+pub mod units;
@@ -1,75 +1,80 @@
-use crate::au::au_to_meters;
-use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics};
-use crate::decimal_matrix_3d::DecimalMatrix3d;
-use crate::decimal_vector_3d::DecimalVector3d;
-use crate::simulation::Simulation;
-use crate::sin_cos::{cos, f64_to_dbig, sin};
-use dashu_float::ops::Abs;
 use dashu_float::DBig;
-use std::ops::Neg;
-use std::str::FromStr;
+use planetsim_rs::body::{Body, BodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics};
+use planetsim_rs::decimal_matrix_3d::DecimalMatrix3d;
+use planetsim_rs::decimal_vector_3d::DecimalVector3d;
+use planetsim_rs::rotation::RotationModel;
+use planetsim_rs::simulation::Simulation;
+use planetsim_rs::sin_cos::f64_to_dbig;
+use planetsim_rs::units::{Angle, Length, Mass, Time};
 use std::time::Instant;
 
-mod au;
-mod body;
-mod decimal_matrix_3d;
-mod decimal_vector_3d;
-mod simulation;
-mod sin_cos;
-
 fn main() {
-    let mut axis = DecimalVector3d::from_f64(1.0, 0.2, 0.3);
-    axis.normalize();
+    let axis = DecimalVector3d::from_f64(1.0, 0.2, 0.3).normalized();
     let angle = f64_to_dbig(2.1415);
 
     let start = Instant::now();
     let matrix = DecimalMatrix3d::axis_angle(axis.clone(), angle);
     let duration = start.elapsed();
-    axis = matrix.apply(axis.clone());
+    let rotated = matrix.apply(axis);
 
     println!("Time elapsed in expensive_function() is: {:?}", duration);
+    println!("{}", rotated);
 
     let moon = Body {
-        name: String::from_str("moon").unwrap(),
-        dynamics: BodyDynamics::Orbiting(OrbitingBodyDynamics {
-            orbit_radius: DBig::from(384400000),
-            orbit_period: DBig::from(27 * 24 * 3600),
-            orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.1).normalized(),
-        }),
-        satellites: vec![],
-        rotation_axis: DecimalVector3d::from_f64(0.3, 1.0, 0.2).normalized(),
-        rotation_period: DBig::from(27 * 24 * 3600),
+        rotation_axis: DecimalVector3d::from_f64(0.0, 1.0, 0.1).normalized(),
+        rotation_model: Some(RotationModel::tidally_locked()),
+        ..Body::new(
+            "moon",
+            Mass::from_kilograms(f64_to_dbig(7.342e22)),
+            Length::from_meters(f64_to_dbig(1737400.0)),
+            BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                orbit_radius: Length::from_kilometers(DBig::from(384400)),
+                orbit_period: Time::from_days(DBig::from(27)),
+                orbit_phase: Angle::zero(),
+                orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.1).normalized(),
+            }),
+        )
     };
 
     let earth = Body {
-        name: String::from_str("earth").unwrap(),
-        dynamics: BodyDynamics::Orbiting(OrbitingBodyDynamics {
-            orbit_radius: au_to_meters(f64_to_dbig(1.0)),
-            orbit_period: DBig::from(365 * 24 * 3600),
-            orbit_plane_normal: DecimalVector3d::from_f64(0.1, 1.0, 0.0).normalized(),
-        }),
+        flattening: Some(f64_to_dbig(1.0 / 298.257223563)),
         satellites: vec![moon],
         rotation_axis: DecimalVector3d::from_f64(0.3, 1.0, 0.2).normalized(),
-        rotation_period: DBig::from(27 * 24 * 3600),
+        rotation_period: Time::from_days(DBig::from(27)),
+        ..Body::new(
+            "earth",
+            Mass::from_kilograms(f64_to_dbig(5.972e24)),
+            Length::from_meters(f64_to_dbig(6378137.0)),
+            BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                orbit_radius: Length::from_au(DBig::ONE),
+                orbit_period: Time::from_days(DBig::from(365)),
+                orbit_phase: Angle::zero(),
+                orbit_plane_normal: DecimalVector3d::from_f64(0.1, 1.0, 0.0).normalized(),
+            }),
+        )
     };
 
     let sun = Body {
-        name: String::from_str("sun").unwrap(),
-        dynamics: BodyDynamics::Static(StaticBodyDynamics {
-            position: DecimalVector3d::from_str(
-                "64959787070023434667",
-                "23454569021239234304",
-                "29349283489",
-            ),
-        }),
         satellites: vec![earth],
         rotation_axis: DecimalVector3d::from_f64(0.0, 1.0, 0.0).normalized(),
-        rotation_period: DBig::from(7 * 24 * 3600),
+        rotation_period: Time::from_days(DBig::from(7)),
+        ..Body::new(
+            "sun",
+            Mass::from_kilograms(f64_to_dbig(1.989e30)),
+            Length::from_meters(f64_to_dbig(695700000.0)),
+            BodyDynamics::Static(StaticBodyDynamics {
+                position: DecimalVector3d::from_str(
+                    "64959787070023434667",
+                    "23454569021239234304",
+                    "29349283489",
+                ),
+            }),
+        )
     };
 
     let mut sim = Simulation::new();
     sim.add_hierarchy(sun, None);
-    sim.update(f64_to_dbig(123123.0));
+    sim.update(Time::from_seconds(f64_to_dbig(123123.0)));
 
     println!("{:?}", sim);
 }
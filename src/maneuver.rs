@@ -0,0 +1,650 @@
+//! Maneuvers of crafts: impulsive delta-v and finite burns of constant thrust, scheduled on a
+//! [`Simulation`] and executed by [`Simulation::update`] as it passes their times.
+//!
+//! Directions are given in the local orbital frame of the craft relative to its parent:
+//! prograde along the velocity, normal along the orbit normal `r × v` and radial completing
+//! them, pointing away from the parent on a circular orbit. Finite burns are integrated with
+//! fourth-order Runge-Kutta steps in the field of the parent alone, crossings of spheres of
+//! influence during a burn are only noticed once it ends. Crafts stay massless for gravity, the
+//! mass in a [`ManeuverPlan`] only sets how hard their engines push.
+
+use crate::body::BodyDynamics;
+use crate::conic::ConicDynamics;
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::reference_frame::StateVector;
+use crate::simulation::Simulation;
+use crate::sin_cos::{dbig_to_f64, f64_to_dbig, precise};
+use crate::units::{Mass, Time, Velocity};
+use dashu_float::ops::SquareRoot;
+use dashu_float::DBig;
+use std::fmt;
+
+/// Longest integration step of a finite burn, in seconds.
+const BURN_STEP: f64 = 10.0;
+
+/// Angular momentum, relative to that of a circular orbit at the same distance, below which a
+/// craft counts as moving straight towards or away from its parent, which no conic follows.
+const RADIAL_TOLERANCE: f64 = 1e-9;
+
+/// Components along the prograde, normal and radial axes of the local orbital frame.
+#[derive(Debug, Clone)]
+pub struct OrbitalFrameVector {
+    pub prograde: DBig,
+    pub normal: DBig,
+    pub radial: DBig,
+}
+
+impl OrbitalFrameVector {
+    pub fn new(prograde: DBig, normal: DBig, radial: DBig) -> OrbitalFrameVector {
+        OrbitalFrameVector {
+            prograde,
+            normal,
+            radial,
+        }
+    }
+
+    pub fn length(&self) -> DBig {
+        precise(
+            self.prograde.clone() * &self.prograde
+                + self.normal.clone() * &self.normal
+                + self.radial.clone() * &self.radial,
+        )
+        .sqrt()
+    }
+
+    /// The same vector in the frame `state` is given in.
+    pub fn to_global(&self, state: &StateVector) -> DecimalVector3d {
+        let prograde = state.velocity.normalized();
+        let normal = state.position.cross(state.velocity.clone()).normalized();
+        let radial = prograde.cross(normal.clone());
+        prograde * &self.prograde + normal * &self.normal + radial * &self.radial
+    }
+}
+
+/// Constant thrust along a fixed direction of the local orbital frame, which turns with the
+/// orbit, burning propellant at a constant rate.
+#[derive(Debug, Clone)]
+pub struct Burn {
+    pub start: Time,
+    pub duration: Time,
+    /// In newtons.
+    pub thrust: DBig,
+    /// Propellant used, in kilograms per second.
+    pub mass_flow: DBig,
+    pub direction: OrbitalFrameVector,
+}
+
+impl Burn {
+    /// Delta-v of the burn for a craft of `mass` at its start, by the rocket equation.
+    pub fn delta_v(&self, mass: &Mass) -> Velocity {
+        let end_mass = self.end_mass(mass);
+        let delta_v = if self.mass_flow == DBig::ZERO {
+            self.thrust.clone() * self.duration.to_seconds() / mass.to_kilograms()
+        } else {
+            precise(self.thrust.clone() / &self.mass_flow) * (mass / &end_mass).ln()
+        };
+        Velocity::from_meters_per_second(delta_v)
+    }
+
+    pub fn end(&self) -> Time {
+        &self.start + &self.duration
+    }
+
+    pub fn end_mass(&self, mass: &Mass) -> Mass {
+        mass.clone() - Mass::from_kilograms(self.mass_flow.clone() * self.duration.to_seconds())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Maneuver {
+    /// Instant change of velocity by `delta_v` meters per second.
+    Impulse {
+        time: Time,
+        delta_v: OrbitalFrameVector,
+    },
+    Burn(Burn),
+}
+
+impl Maneuver {
+    pub fn start(&self) -> &Time {
+        match self {
+            Maneuver::Impulse { time, .. } => time,
+            Maneuver::Burn(burn) => &burn.start,
+        }
+    }
+
+    pub fn end(&self) -> Time {
+        match self {
+            Maneuver::Impulse { time, .. } => time.clone(),
+            Maneuver::Burn(burn) => burn.end(),
+        }
+    }
+}
+
+/// Mass and maneuvers of one craft.
+#[derive(Debug, Clone)]
+pub struct ManeuverPlan {
+    /// Current mass including propellant, burns lower it.
+    pub mass: Mass,
+    /// Maneuvers still to come ordered by start, a burn in progress starts at the current time.
+    maneuvers: Vec<Maneuver>,
+    spent: Velocity,
+}
+
+impl ManeuverPlan {
+    fn new(mass: Mass) -> ManeuverPlan {
+        ManeuverPlan {
+            mass,
+            maneuvers: vec![],
+            spent: Velocity::zero(),
+        }
+    }
+
+    pub fn maneuvers(&self) -> &[Maneuver] {
+        &self.maneuvers
+    }
+
+    /// Delta-v of the maneuvers already executed.
+    pub fn spent_delta_v(&self) -> &Velocity {
+        &self.spent
+    }
+
+    /// Delta-v of the maneuvers still to come.
+    pub fn pending_delta_v(&self) -> Velocity {
+        let mut mass = self.mass.clone();
+        let mut total = Velocity::zero();
+        for maneuver in &self.maneuvers {
+            match maneuver {
+                Maneuver::Impulse { delta_v, .. } => {
+                    total += &Velocity::from_meters_per_second(delta_v.length());
+                }
+                Maneuver::Burn(burn) => {
+                    total += &burn.delta_v(&mass);
+                    mass = burn.end_mass(&mass);
+                }
+            }
+        }
+        total
+    }
+
+    pub fn total_delta_v(&self) -> Velocity {
+        self.pending_delta_v() + &self.spent
+    }
+
+    fn final_mass(&self) -> Mass {
+        self.maneuvers
+            .iter()
+            .fold(self.mass.clone(), |mass, maneuver| match maneuver {
+                Maneuver::Burn(burn) => burn.end_mass(&mass),
+                Maneuver::Impulse { .. } => mass,
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ManeuverError {
+    /// Body `id` is not a craft, see [`crate::body::Body::craft`].
+    NotACraft { id: i32 },
+    /// The maneuver starts at `time`, before the current simulation time.
+    InThePast { id: i32, time: Time },
+    /// The maneuver overlaps another one of craft `id` starting at `time`.
+    Overlapping { id: i32, time: Time },
+    /// Craft `id` would run out of mass before its burns end, or has none to push.
+    OutOfPropellant { id: i32 },
+    /// The maneuver of craft `id` at `time` would leave it moving straight towards or away
+    /// from its parent.
+    RadialTrajectory { id: i32, time: Time },
+}
+
+impl fmt::Display for ManeuverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ManeuverError::NotACraft { id } => write!(f, "body {id} is not a craft"),
+            ManeuverError::InThePast { id, time } => {
+                write!(f, "maneuver of craft {id} at {time} lies in the past")
+            }
+            ManeuverError::Overlapping { id, time } => {
+                write!(f, "maneuver overlaps the one of craft {id} at {time}")
+            }
+            ManeuverError::OutOfPropellant { id } => {
+                write!(f, "craft {id} runs out of propellant")
+            }
+            ManeuverError::RadialTrajectory { id, time } => {
+                write!(
+                    f,
+                    "maneuver of craft {id} at {time} leaves a radial trajectory"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ManeuverError {}
+
+/// Rate of change of a craft's state around a parent with gravitational parameter `mu`, pushed
+/// by `acceleration` meters per second squared along `direction`.
+fn derivative(
+    state: &StateVector,
+    mu: &DBig,
+    acceleration: &DBig,
+    direction: &OrbitalFrameVector,
+) -> StateVector {
+    let radius = state.position.length();
+    let gravity = state.position.clone() * (-mu.clone() / (radius.clone() * &radius * radius));
+    let thrust = direction.to_global(state).normalized() * acceleration;
+    StateVector::new(state.velocity.clone(), gravity + thrust)
+}
+
+fn advanced(state: &StateVector, rate: &StateVector, step: &DBig) -> StateVector {
+    StateVector::new(
+        &state.position + &(rate.position.clone() * step),
+        &state.velocity + &(rate.velocity.clone() * step),
+    )
+}
+
+/// `state` after a burn from its start to `end` by a craft of `mass` at the start, around a
+/// parent with gravitational parameter `mu`.
+fn integrate_burn(
+    mut state: StateVector,
+    mu: &DBig,
+    mass: &Mass,
+    burn: &Burn,
+    end: &Time,
+) -> StateVector {
+    let span = (end - &burn.start).to_seconds();
+    let steps = (dbig_to_f64(&span) / BURN_STEP).ceil().max(1.0) as usize;
+    let step = precise(span / DBig::from(steps));
+    let half = step.clone() / DBig::from(2);
+    let push = |elapsed: DBig| {
+        let mass = mass.to_kilograms() - burn.mass_flow.clone() * elapsed;
+        precise(burn.thrust.clone() / mass)
+    };
+    let mut elapsed = DBig::ZERO;
+    for _ in 0..steps {
+        let k1 = derivative(&state, mu, &push(elapsed.clone()), &burn.direction);
+        let middle = push(elapsed.clone() + &half);
+        let k2 = derivative(&advanced(&state, &k1, &half), mu, &middle, &burn.direction);
+        let k3 = derivative(&advanced(&state, &k2, &half), mu, &middle, &burn.direction);
+        let k4 = derivative(
+            &advanced(&state, &k3, &step),
+            mu,
+            &push(elapsed.clone() + &step),
+            &burn.direction,
+        );
+        let sixth = step.clone() / DBig::from(6);
+        let two = DBig::from(2);
+        state = StateVector::new(
+            state.position
+                + (k1.position + k2.position * &two + k3.position * &two + k4.position) * &sixth,
+            state.velocity
+                + (k1.velocity + k2.velocity * &two + k3.velocity * &two + k4.velocity) * &sixth,
+        );
+        elapsed += &step;
+    }
+    state
+}
+
+/// Conic through `state` at `time`, `None` if the state is radial, see [`RADIAL_TOLERANCE`].
+fn trajectory_through(state: &StateVector, time: &Time, mu: DBig) -> Option<ConicDynamics> {
+    let momentum = state.position.cross(state.velocity.clone()).length();
+    let circular = precise(mu.clone() * state.position.length()).sqrt();
+    if momentum <= circular * f64_to_dbig(RADIAL_TOLERANCE) {
+        return None;
+    }
+    ConicDynamics::from_state(state, time, mu)
+}
+
+impl Simulation {
+    fn check_craft(&self, id: i32) -> Result<(), ManeuverError> {
+        match self.get_body_by_id(id) {
+            Some(body) if body.body().is_craft() => Ok(()),
+            _ => Err(ManeuverError::NotACraft { id }),
+        }
+    }
+
+    /// Sets the mass craft `id` starts its maneuvers with, including propellant.
+    pub fn set_craft_mass(&mut self, id: i32, mass: Mass) -> Result<(), ManeuverError> {
+        self.check_craft(id)?;
+        self.maneuver_plans
+            .entry(id)
+            .or_insert_with(|| ManeuverPlan::new(Mass::zero()))
+            .mass = mass;
+        Ok(())
+    }
+
+    /// Adds `maneuver` to the plan of craft `id`. Burns need a mass, see
+    /// [`Simulation::set_craft_mass`], that they do not use up.
+    pub fn schedule_maneuver(&mut self, id: i32, maneuver: Maneuver) -> Result<(), ManeuverError> {
+        self.check_craft(id)?;
+        if maneuver.start() < self.time() {
+            return Err(ManeuverError::InThePast {
+                id,
+                time: maneuver.start().clone(),
+            });
+        }
+        let mut plan = self
+            .maneuver_plans
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| ManeuverPlan::new(Mass::zero()));
+        if let Some(other) = plan
+            .maneuvers
+            .iter()
+            .find(|other| *other.start() <= maneuver.end() && maneuver.start() <= &other.end())
+        {
+            return Err(ManeuverError::Overlapping {
+                id,
+                time: other.start().clone(),
+            });
+        }
+        let index = plan
+            .maneuvers
+            .partition_point(|other| other.start() < maneuver.start());
+        plan.maneuvers.insert(index, maneuver);
+        let has_burns = plan
+            .maneuvers
+            .iter()
+            .any(|maneuver| matches!(maneuver, Maneuver::Burn(_)));
+        if has_burns && plan.final_mass() <= Mass::zero() {
+            return Err(ManeuverError::OutOfPropellant { id });
+        }
+        self.check_trajectories(id, &plan)?;
+        self.maneuver_plans.insert(id, plan);
+        Ok(())
+    }
+
+    /// Fails if a maneuver of `plan` leaves craft `id` on a radial trajectory, following its
+    /// current trajectory from one maneuver to the next. Spheres of influence crossed on the
+    /// way are not noticed.
+    fn check_trajectories(&self, id: i32, plan: &ManeuverPlan) -> Result<(), ManeuverError> {
+        let Some(BodyDynamics::Craft(mut trajectory)) = self
+            .get_body_by_id(id)
+            .map(|craft| craft.body().dynamics.clone())
+        else {
+            return Err(ManeuverError::NotACraft { id });
+        };
+        let mut mass = plan.mass.clone();
+        for maneuver in &plan.maneuvers {
+            let mu = precise(trajectory.gravitational_parameter.clone());
+            let mut state = trajectory.state_at(maneuver.start());
+            match maneuver {
+                Maneuver::Impulse { delta_v, .. } => state.velocity += &delta_v.to_global(&state),
+                Maneuver::Burn(burn) => {
+                    state = integrate_burn(state, &mu, &mass, burn, &burn.end());
+                    mass = burn.end_mass(&mass);
+                }
+            }
+            trajectory = trajectory_through(&state, &maneuver.end(), mu).ok_or_else(|| {
+                ManeuverError::RadialTrajectory {
+                    id,
+                    time: maneuver.start().clone(),
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn maneuver_plan(&self, id: i32) -> Option<&ManeuverPlan> {
+        self.maneuver_plans.get(&id)
+    }
+
+    /// Delta-v craft `id` has spent and is going to spend on its scheduled maneuvers.
+    pub fn total_delta_v(&self, id: i32) -> Option<Velocity> {
+        self.maneuver_plans
+            .get(&id)
+            .map(ManeuverPlan::total_delta_v)
+    }
+
+    /// Executes the first maneuver of craft `id` as far as `time`, returning when it stopped.
+    fn execute_maneuver(&mut self, id: i32, time: &Time) -> Option<Time> {
+        let Some(BodyDynamics::Craft(trajectory)) = self
+            .get_body_by_id(id)
+            .map(|craft| craft.body().dynamics.clone())
+        else {
+            return None;
+        };
+        let mut plan = self.maneuver_plans.get(&id)?.clone();
+        let maneuver = plan.maneuvers.remove(0);
+        let start = maneuver.start().clone();
+        let mu = precise(trajectory.gravitational_parameter.clone());
+        let mut state = trajectory.state_at(&start);
+
+        let end = match maneuver {
+            Maneuver::Impulse { delta_v, .. } => {
+                state.velocity += &delta_v.to_global(&state);
+                plan.spent += &Velocity::from_meters_per_second(delta_v.length());
+                start
+            }
+            Maneuver::Burn(burn) => {
+                let end = burn.end().min(time.clone());
+                state = integrate_burn(state, &mu, &plan.mass, &burn, &end);
+
+                let done = Burn {
+                    duration: &end - &start,
+                    ..burn.clone()
+                };
+                plan.spent += &done.delta_v(&plan.mass);
+                plan.mass = done.end_mass(&plan.mass);
+                if end < burn.end() {
+                    let rest = Burn {
+                        start: end.clone(),
+                        duration: burn.end() - &end,
+                        ..burn
+                    };
+                    plan.maneuvers.insert(0, Maneuver::Burn(rest));
+                }
+                end
+            }
+        };
+
+        match trajectory_through(&state, &end, mu) {
+            Some(trajectory) => {
+                self.set_dynamics(id, BodyDynamics::Craft(trajectory));
+                self.maneuver_plans.insert(id, plan);
+            }
+            // only possible after a sphere of influence changed the parent since scheduling,
+            // the maneuver is dropped and the craft keeps its trajectory
+            None => {
+                if let Some(plan) = self.maneuver_plans.get_mut(&id) {
+                    plan.maneuvers.remove(0);
+                }
+            }
+        }
+        Some(end)
+    }
+
+    /// Moves every craft along its trajectory to `time`, executing its maneuvers and changing
+    /// its parent at spheres of influence on the way.
+    pub(crate) fn advance_crafts(&mut self, time: &Time) {
+        if *time <= *self.time() {
+            return;
+        }
+        let crafts: Vec<i32> = self
+            .bodies
+            .iter()
+            .filter(|body| body.body().is_craft())
+            .map(|body| body.id())
+            .collect();
+        for id in crafts {
+            let mut from = self.time().clone();
+            loop {
+                let next = self
+                    .maneuver_plans
+                    .get(&id)
+                    .and_then(|plan| plan.maneuvers.first())
+                    // a burn reaching `time` continues from there with the next update
+                    .filter(|maneuver| match maneuver {
+                        Maneuver::Impulse { time: start, .. } => start <= time,
+                        Maneuver::Burn(burn) => burn.start < *time,
+                    })
+                    .map(|maneuver| maneuver.start().clone());
+                let Some(next) = next else {
+                    self.patch_craft(id, &from, time);
+                    break;
+                };
+                self.patch_craft(id, &from, &next);
+                match self.execute_maneuver(id, time) {
+                    Some(end) => from = end,
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, StaticBodyDynamics};
+    use crate::constants::Constants;
+    use crate::reference_frame::ReferenceFrame;
+    use crate::sin_cos::f64_to_dbig;
+    use crate::units::Length;
+
+    const GM: f64 = 3.986004418e14;
+
+    /// A craft on a circular orbit of radius 7000 km around a static Earth.
+    fn orbiting_craft() -> (Simulation, i32) {
+        let mut sim = Simulation::new();
+        let earth = sim.add_hierarchy(
+            Body::new(
+                "Earth",
+                Constants::default().mass_from_gm(&f64_to_dbig(GM)),
+                Length::from_meters(f64_to_dbig(6.371e6)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            ),
+            None,
+        );
+        let state = StateVector::new(
+            DecimalVector3d::from_f64(7e6, 0.0, 0.0),
+            DecimalVector3d::from_f64(0.0, 0.0, -(GM / 7e6).sqrt()),
+        );
+        let craft = sim.add_craft("probe", earth, &state).unwrap();
+        (sim, craft)
+    }
+
+    fn speed_and_radius(sim: &Simulation, craft: i32) -> (f64, f64) {
+        let state = sim
+            .get_body_state_in(craft, ReferenceFrame::ParentCentered { id: craft })
+            .unwrap();
+        (
+            dbig_to_f64(&state.velocity.length()),
+            dbig_to_f64(&state.position.length()),
+        )
+    }
+
+    #[test]
+    fn impulse_raises_the_apoapsis() {
+        let (mut sim, craft) = orbiting_craft();
+        let circular = (GM / 7e6).sqrt();
+        // Hohmann transfer to 42164 km
+        let transfer = (GM / 7e6).sqrt() * ((2.0f64 * 42164e3 / (7e6 + 42164e3)).sqrt() - 1.0);
+        let impulse = Maneuver::Impulse {
+            time: Time::from_seconds(DBig::from(600)),
+            delta_v: OrbitalFrameVector::new(f64_to_dbig(transfer), DBig::ZERO, DBig::ZERO),
+        };
+        sim.schedule_maneuver(craft, impulse.clone()).unwrap();
+        assert!(matches!(
+            sim.schedule_maneuver(craft, impulse),
+            Err(ManeuverError::Overlapping { .. })
+        ));
+        let total = dbig_to_f64(&sim.total_delta_v(craft).unwrap().to_meters_per_second());
+        assert!((total - transfer).abs() < 1e-9);
+
+        sim.update(Time::from_seconds(DBig::from(600)));
+        // body velocities are differenced over the last second, off by about g^2 / 8v
+        let (speed, _) = speed_and_radius(&sim, craft);
+        assert!((speed - circular - transfer).abs() < 1e-2, "{speed}");
+        let BodyDynamics::Craft(trajectory) = &sim.get_body_by_id(craft).unwrap().body().dynamics
+        else {
+            unreachable!()
+        };
+        let elements = trajectory.elements_at(sim.time());
+        let apoapsis = dbig_to_f64(&elements.apoapsis_distance().unwrap().to_meters());
+        assert!((apoapsis / 42164e3 - 1.0).abs() < 1e-9, "{apoapsis}");
+        let plan = sim.maneuver_plan(craft).unwrap();
+        assert!(plan.maneuvers().is_empty());
+        assert_eq!(
+            dbig_to_f64(&plan.total_delta_v().to_meters_per_second()),
+            dbig_to_f64(&plan.spent_delta_v().to_meters_per_second())
+        );
+        assert!(matches!(
+            sim.schedule_maneuver(
+                craft,
+                Maneuver::Impulse {
+                    time: Time::zero(),
+                    delta_v: OrbitalFrameVector::new(DBig::ONE, DBig::ZERO, DBig::ZERO),
+                }
+            ),
+            Err(ManeuverError::InThePast { .. })
+        ));
+    }
+
+    #[test]
+    fn radial_trajectories_are_rejected() {
+        let (mut sim, craft) = orbiting_craft();
+        // cancels the orbital velocity, leaving the craft to fall straight down
+        let stop = Maneuver::Impulse {
+            time: Time::from_seconds(DBig::from(600)),
+            delta_v: OrbitalFrameVector::new(
+                f64_to_dbig(-(GM / 7e6).sqrt()),
+                DBig::ZERO,
+                DBig::ZERO,
+            ),
+        };
+        assert!(matches!(
+            sim.schedule_maneuver(craft, stop),
+            Err(ManeuverError::RadialTrajectory { .. })
+        ));
+        assert!(sim
+            .maneuver_plan(craft)
+            .is_none_or(|plan| plan.maneuvers().is_empty()));
+    }
+
+    #[test]
+    fn burn_follows_the_rocket_equation() {
+        let (mut sim, craft) = orbiting_craft();
+        let burn = Burn {
+            start: Time::from_seconds(DBig::from(100)),
+            duration: Time::from_seconds(DBig::from(60)),
+            thrust: f64_to_dbig(2e4),
+            mass_flow: f64_to_dbig(6.8),
+            direction: OrbitalFrameVector::new(DBig::ONE, DBig::ZERO, DBig::ZERO),
+        };
+        assert!(matches!(
+            sim.schedule_maneuver(craft, Maneuver::Burn(burn.clone())),
+            Err(ManeuverError::OutOfPropellant { .. })
+        ));
+        sim.set_craft_mass(craft, Mass::from_kilograms(DBig::from(1000)))
+            .unwrap();
+        sim.schedule_maneuver(craft, Maneuver::Burn(burn)).unwrap();
+        // exhaust speed 2941 m/s, 1000 kg down to 592 kg
+        let expected = 2e4 / 6.8 * (1000.0f64 / 592.0).ln();
+        let planned = dbig_to_f64(&sim.total_delta_v(craft).unwrap().to_meters_per_second());
+        assert!((planned - expected).abs() < 1e-9, "{planned}");
+
+        // stopping halfway through the burn changes nothing
+        let mut halfway = sim.clone();
+        halfway.update(Time::from_seconds(DBig::from(130)));
+        let remaining = halfway.maneuver_plan(craft).unwrap().mass.to_kilograms();
+        assert!((dbig_to_f64(&remaining) - 796.0).abs() < 1e-9);
+        halfway.update(Time::from_seconds(DBig::from(160)));
+        sim.update(Time::from_seconds(DBig::from(160)));
+        let (speed, radius) = speed_and_radius(&sim, craft);
+        let (halfway_speed, halfway_radius) = speed_and_radius(&halfway, craft);
+        assert!((speed - halfway_speed).abs() < 1e-6);
+        assert!((radius - halfway_radius).abs() < 1e-3);
+
+        // prograde along a short arc the energy gained is close to v dv
+        let circular = (GM / 7e6).sqrt();
+        let energy = speed * speed / 2.0 - GM / radius + GM / 7e6 / 2.0;
+        let estimate = circular * expected + expected * expected / 2.0;
+        assert!(
+            (energy / estimate - 1.0).abs() < 0.02,
+            "{energy} {estimate}"
+        );
+        assert!(sim.maneuver_plan(craft).unwrap().maneuvers().is_empty());
+    }
+}
@@ -0,0 +1,445 @@
+//! Classical and modified equinoctial orbital elements of a body relative to its parent.
+//!
+//! Elements refer to the parent's reference plane, the simulation's x-z plane with y as its
+//! north pole, and angles count from the x axis: an orbit with `orbit_plane_normal` y has
+//! inclination 0 and is prograde. Conversions go through the modified equinoctial elements of
+//! Walker, Ireland and Owens (1985), which stay regular for circular, equatorial and parabolic
+//! orbits. Orbits inclined by more than pi / 2 use the retrograde factor of Broucke and Cefola
+//! (1972), so retrograde equatorial orbits, inclined by exactly pi, stay regular as well.
+
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::reference_frame::{ReferenceFrame, StateVector};
+use crate::simulation::Simulation;
+use crate::sin_cos::{atan, atan2, cos, f64_to_dbig, precise, sin, PI, PIMUL2};
+use crate::units::{Angle, Length, Time};
+use dashu_float::ops::{Abs, SquareRoot};
+use dashu_float::DBig;
+
+/// Eccentricities closer than this to 0 or 1 count as circular or parabolic in [`ConicKind`].
+const ECCENTRICITY_TOLERANCE: f64 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConicKind {
+    Circular,
+    Elliptic,
+    Parabolic,
+    Hyperbolic,
+}
+
+#[derive(Debug, Clone)]
+pub struct OrbitalElements {
+    /// Closest distance to the parent, finite for every conic unlike the semi-major axis.
+    pub periapsis_distance: Length,
+    pub eccentricity: DBig,
+    /// In [0, pi].
+    pub inclination: Angle,
+    /// In [0, 2pi), 0 for equatorial orbits.
+    pub longitude_of_ascending_node: Angle,
+    /// In [0, 2pi), 0 for exactly circular orbits so the true anomaly counts from the node.
+    pub argument_of_periapsis: Angle,
+    /// In (-pi, pi], negative while approaching periapsis.
+    pub true_anomaly: Angle,
+}
+
+/// Modified equinoctial elements: `f` and `g` are the eccentricity vector and `h` and `k` the
+/// node vector scaled by `tan(i / 2)`, both in the reference plane.
+#[derive(Debug, Clone)]
+pub struct EquinoctialElements {
+    pub semi_latus_rectum: Length,
+    pub f: DBig,
+    pub g: DBig,
+    pub h: DBig,
+    pub k: DBig,
+    /// Longitude of the ascending node plus argument of periapsis plus true anomaly.
+    pub true_longitude: Angle,
+    /// Retrograde factor `I = -1`: the node vector is scaled by `cot(i / 2)` instead and the
+    /// longitudes subtract the ascending node, `f`, `g` and the true longitude are measured
+    /// in the orbit's own sense of motion.
+    pub retrograde: bool,
+}
+
+/// Simulation coordinates (y up) in the conventional reference frame (z up).
+fn to_z_up(v: &DecimalVector3d) -> DecimalVector3d {
+    DecimalVector3d::new(v.x.clone(), -v.z.clone(), v.y.clone())
+}
+
+fn from_z_up(v: DecimalVector3d) -> DecimalVector3d {
+    DecimalVector3d::new(v.x, v.z, -v.y)
+}
+
+/// `angle` moved into [0, 2pi).
+pub(crate) fn wrap_angle(angle: DBig) -> DBig {
+    let wrapped = (angle / PIMUL2.clone()).fract() * PIMUL2.clone();
+    if wrapped < DBig::ZERO {
+        wrapped + PIMUL2.clone()
+    } else {
+        wrapped
+    }
+}
+
+/// `angle` moved into (-pi, pi].
+pub(crate) fn wrap_signed_angle(angle: DBig) -> DBig {
+    let wrapped = wrap_angle(angle);
+    if wrapped > PI.clone() {
+        wrapped - PIMUL2.clone()
+    } else {
+        wrapped
+    }
+}
+
+impl EquinoctialElements {
+    /// Unit vectors `f` and `g` are measured along, z up. They span the orbit plane and point
+    /// along the reference x and y axes for equatorial orbits, flipping y when retrograde.
+    fn axes(h: &DBig, k: &DBig, retrograde: bool) -> (DecimalVector3d, DecimalVector3d) {
+        let factor = if retrograde { -DBig::ONE } else { DBig::ONE };
+        let two = DBig::from(2);
+        let s2 = DBig::ONE + h.clone() * h + k.clone() * k;
+        let f_axis = DecimalVector3d::new(
+            DBig::ONE - k.clone() * k + h.clone() * h,
+            two.clone() * h * k,
+            -two.clone() * k * &factor,
+        ) / &s2;
+        let g_axis = DecimalVector3d::new(
+            two.clone() * h * k * &factor,
+            (DBig::ONE + k.clone() * k - h.clone() * h) * &factor,
+            two * h,
+        ) / &s2;
+        (f_axis, g_axis)
+    }
+
+    /// Elements of the orbit through `state`, given relative to the parent, around a parent
+    /// with gravitational parameter `gravitational_parameter` in m^3/s^2. `None` without
+    /// angular momentum (radial motion).
+    pub fn from_state(
+        state: &StateVector,
+        gravitational_parameter: &DBig,
+    ) -> Option<EquinoctialElements> {
+        let mu = precise(gravitational_parameter.clone());
+        let position = to_z_up(&state.position);
+        let velocity = to_z_up(&state.velocity);
+        let radius = position.length();
+        let momentum = position.cross(velocity.clone());
+        let momentum_length = momentum.length();
+        if radius == DBig::ZERO || momentum_length == DBig::ZERO {
+            return None;
+        }
+        let normal = momentum.clone() / &momentum_length;
+        let retrograde = normal.z < DBig::ZERO;
+        let denominator = match retrograde {
+            true => DBig::ONE - &normal.z,
+            false => DBig::ONE + &normal.z,
+        };
+        let h = -normal.y.clone() / &denominator;
+        let k = normal.x.clone() / &denominator;
+        let (f_axis, g_axis) = EquinoctialElements::axes(&h, &k, retrograde);
+        let eccentricity = velocity.cross(momentum) / &mu - position.clone() / &radius;
+
+        Some(EquinoctialElements {
+            semi_latus_rectum: Length::from_meters(
+                momentum_length.clone() * &momentum_length / &mu,
+            ),
+            f: eccentricity.dot(f_axis.clone()),
+            g: eccentricity.dot(g_axis.clone()),
+            h,
+            k,
+            true_longitude: Angle::from_radians(wrap_angle(atan2(
+                position.dot(g_axis),
+                position.dot(f_axis),
+                32,
+            ))),
+            retrograde,
+        })
+    }
+
+    /// Position and velocity relative to the parent.
+    pub fn to_state(&self, gravitational_parameter: &DBig) -> StateVector {
+        let mu = precise(gravitational_parameter.clone());
+        let p = self.semi_latus_rectum.to_meters();
+        let longitude = self.true_longitude.to_radians();
+        let (sin_l, cos_l) = (sin(longitude.clone(), 32), cos(longitude, 32));
+        let (f_axis, g_axis) = EquinoctialElements::axes(&self.h, &self.k, self.retrograde);
+        let w = DBig::ONE + self.f.clone() * &cos_l + self.g.clone() * &sin_l;
+        let radius = p.clone() / w;
+
+        let position = (f_axis.clone() * &cos_l + g_axis.clone() * &sin_l) * radius;
+        let speed = (mu / p).sqrt();
+        let velocity = (g_axis * (cos_l + &self.f) - f_axis * (sin_l + &self.g)) * speed;
+        StateVector::new(from_z_up(position), from_z_up(velocity))
+    }
+
+    pub fn to_classical(&self) -> OrbitalElements {
+        let eccentricity = precise(self.f.clone() * &self.f + self.g.clone() * &self.g).sqrt();
+        let tan_half = precise(self.h.clone() * &self.h + self.k.clone() * &self.k).sqrt();
+        let node = atan2(self.k.clone(), self.h.clone(), 32);
+        let half_inclination = atan(tan_half, 32);
+        let (inclination, node_term) = match self.retrograde {
+            true => (PI.clone() - DBig::from(2) * half_inclination, -node.clone()),
+            false => (DBig::from(2) * half_inclination, node.clone()),
+        };
+        // a circular orbit has no periapsis, it is put on the node
+        let periapsis_longitude = if eccentricity == DBig::ZERO {
+            node_term.clone()
+        } else {
+            atan2(self.g.clone(), self.f.clone(), 32)
+        };
+        OrbitalElements {
+            periapsis_distance: &self.semi_latus_rectum / &(DBig::ONE + &eccentricity),
+            eccentricity,
+            inclination: Angle::from_radians(inclination),
+            longitude_of_ascending_node: Angle::from_radians(wrap_angle(node)),
+            argument_of_periapsis: Angle::from_radians(wrap_angle(
+                periapsis_longitude.clone() - node_term,
+            )),
+            true_anomaly: Angle::from_radians(wrap_signed_angle(
+                self.true_longitude.to_radians() - periapsis_longitude,
+            )),
+        }
+    }
+}
+
+impl OrbitalElements {
+    /// See [`EquinoctialElements::from_state`].
+    pub fn from_state(
+        state: &StateVector,
+        gravitational_parameter: &DBig,
+    ) -> Option<OrbitalElements> {
+        EquinoctialElements::from_state(state, gravitational_parameter)
+            .map(|elements| elements.to_classical())
+    }
+
+    pub fn to_state(&self, gravitational_parameter: &DBig) -> StateVector {
+        self.to_equinoctial().to_state(gravitational_parameter)
+    }
+
+    pub fn to_equinoctial(&self) -> EquinoctialElements {
+        let retrograde = self.inclination.to_radians() > PI.clone() / DBig::from(2);
+        let node = self.longitude_of_ascending_node.to_radians();
+        let half = self.inclination.to_radians() / DBig::from(2);
+        let (tan_half, periapsis_longitude) = match retrograde {
+            true => (
+                cos(half.clone(), 32) / sin(half, 32),
+                self.argument_of_periapsis.to_radians() - &node,
+            ),
+            false => (
+                sin(half.clone(), 32) / cos(half, 32),
+                self.argument_of_periapsis.to_radians() + &node,
+            ),
+        };
+        EquinoctialElements {
+            semi_latus_rectum: self.semi_latus_rectum(),
+            f: self.eccentricity.clone() * cos(periapsis_longitude.clone(), 32),
+            g: self.eccentricity.clone() * sin(periapsis_longitude.clone(), 32),
+            h: tan_half.clone() * cos(node.clone(), 32),
+            k: tan_half * sin(node, 32),
+            true_longitude: Angle::from_radians(wrap_angle(
+                periapsis_longitude + self.true_anomaly.to_radians(),
+            )),
+            retrograde,
+        }
+    }
+
+    pub fn kind(&self) -> ConicKind {
+        let tolerance = f64_to_dbig(ECCENTRICITY_TOLERANCE);
+        if self.eccentricity < tolerance {
+            ConicKind::Circular
+        } else if (self.eccentricity.clone() - DBig::ONE).abs() < tolerance {
+            ConicKind::Parabolic
+        } else if self.eccentricity < DBig::ONE {
+            ConicKind::Elliptic
+        } else {
+            ConicKind::Hyperbolic
+        }
+    }
+
+    pub fn semi_latus_rectum(&self) -> Length {
+        &self.periapsis_distance * &(DBig::ONE + &self.eccentricity)
+    }
+
+    /// Negative for hyperbolic orbits, `None` for parabolic ones.
+    pub fn semi_major_axis(&self) -> Option<Length> {
+        if self.eccentricity == DBig::ONE {
+            return None;
+        }
+        Some(&self.periapsis_distance / &(DBig::ONE - &self.eccentricity))
+    }
+
+    /// `None` for open orbits.
+    pub fn apoapsis_distance(&self) -> Option<Length> {
+        if self.eccentricity >= DBig::ONE {
+            return None;
+        }
+        Some(&self.semi_latus_rectum() / &(DBig::ONE - &self.eccentricity))
+    }
+
+    /// Time one revolution takes, `None` for open orbits.
+    pub fn period(&self, gravitational_parameter: &DBig) -> Option<Time> {
+        let a = self.apoapsis_distance()? + &self.periapsis_distance;
+        let a = a.to_meters() / DBig::from(2);
+        let mean_motion = (precise(gravitational_parameter.clone()) / (a.clone() * &a * a)).sqrt();
+        Some(Time::from_seconds(PIMUL2.clone() / mean_motion))
+    }
+}
+
+impl Simulation {
+    /// `G (M + m)` of body `id` orbiting its parent, `M` being the parent's
+    /// [`Simulation::central_mass`]. `None` for root bodies.
+    pub fn gravitational_parameter(&self, id: i32) -> Option<DBig> {
+        let body = self.get_body_by_id(id)?;
+        let central_mass = self.central_mass(body.parent()?)?;
+        Some(
+            self.constants()
+                .gm_from_mass(&(central_mass + &body.body().mass)),
+        )
+    }
+
+    /// Osculating elements of body `id` around its parent at the current simulation time.
+    pub fn orbital_elements(&self, id: i32) -> Option<OrbitalElements> {
+        self.equinoctial_elements(id)
+            .map(|elements| elements.to_classical())
+    }
+
+    pub fn equinoctial_elements(&self, id: i32) -> Option<EquinoctialElements> {
+        let state = self.get_body_state_in(id, ReferenceFrame::ParentCentered { id })?;
+        EquinoctialElements::from_state(&state, &self.gravitational_parameter(id)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sin_cos::dbig_to_f64;
+
+    const GM: f64 = 1.32712440018e20;
+
+    fn assert_states_match(a: &StateVector, b: &StateVector) {
+        let position = dbig_to_f64(&a.position.distance_to(b.position.clone()));
+        let velocity = dbig_to_f64(&a.velocity.distance_to(b.velocity.clone()));
+        assert!(
+            position < 1e-12 * dbig_to_f64(&a.position.length()),
+            "{position}"
+        );
+        assert!(
+            velocity < 1e-12 * dbig_to_f64(&a.velocity.length()),
+            "{velocity}"
+        );
+    }
+
+    #[test]
+    fn round_trips_every_conic() {
+        let gm = f64_to_dbig(GM);
+        let elliptic = StateVector::new(
+            DecimalVector3d::from_f64(1.2e11, 3e10, -4e10),
+            DecimalVector3d::from_f64(5e3, 4e3, 2.8e4),
+        );
+        let elements = OrbitalElements::from_state(&elliptic, &gm).unwrap();
+        assert_eq!(elements.kind(), ConicKind::Elliptic);
+        assert_states_match(&elliptic, &elements.to_state(&gm));
+
+        // escape speed at 1 AU is 42.1 km/s
+        let hyperbolic = StateVector::new(
+            DecimalVector3d::from_f64(1.5e11, 0.0, 0.0),
+            DecimalVector3d::from_f64(-2e4, 1e4, -5e4),
+        );
+        let elements = OrbitalElements::from_state(&hyperbolic, &gm).unwrap();
+        assert_eq!(elements.kind(), ConicKind::Hyperbolic);
+        assert!(elements.semi_major_axis().unwrap() < Length::zero());
+        assert!(elements.true_anomaly.to_radians() < DBig::ZERO);
+        assert_states_match(&hyperbolic, &elements.to_state(&gm));
+
+        // a parabola with periapsis 1e11 m, inclined by 30 degrees
+        let parabolic = OrbitalElements {
+            periapsis_distance: Length::from_meters(f64_to_dbig(1e11)),
+            eccentricity: DBig::ONE,
+            inclination: Angle::from_degrees(DBig::from(30)),
+            longitude_of_ascending_node: Angle::from_degrees(DBig::from(80)),
+            argument_of_periapsis: Angle::from_degrees(DBig::from(200)),
+            true_anomaly: Angle::from_degrees(DBig::from(120)),
+        };
+        assert_eq!(parabolic.kind(), ConicKind::Parabolic);
+        assert!(parabolic.semi_major_axis().is_none());
+        let state = parabolic.to_state(&gm);
+        let speed = dbig_to_f64(&state.velocity.length());
+        let escape = (2.0 * GM / dbig_to_f64(&state.position.length())).sqrt();
+        assert!((speed / escape - 1.0).abs() < 1e-12);
+        let back = OrbitalElements::from_state(&state, &gm).unwrap();
+        assert!((dbig_to_f64(&back.eccentricity) - 1.0).abs() < 1e-12);
+        assert!((dbig_to_f64(&back.inclination.to_degrees()) - 30.0).abs() < 1e-9);
+        assert!((dbig_to_f64(&back.argument_of_periapsis.to_degrees()) - 200.0).abs() < 1e-9);
+        assert!((dbig_to_f64(&back.true_anomaly.to_degrees()) - 120.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn circular_equatorial_orbits_stay_regular() {
+        let gm = f64_to_dbig(GM);
+        let radius = 1.5e11;
+        // prograde around y, moving from +x towards -z
+        let state = StateVector::new(
+            DecimalVector3d::from_f64(0.0, 0.0, -radius),
+            DecimalVector3d::from_f64(-(GM / radius).sqrt(), 0.0, 0.0),
+        );
+        let elements = OrbitalElements::from_state(&state, &gm).unwrap();
+        assert_eq!(elements.kind(), ConicKind::Circular);
+        assert!(dbig_to_f64(&elements.inclination.to_radians()).abs() < 1e-15);
+        assert_eq!(elements.longitude_of_ascending_node, Angle::zero());
+        let longitude =
+            elements.argument_of_periapsis.to_radians() + elements.true_anomaly.to_radians();
+        let longitude = dbig_to_f64(&wrap_angle(longitude));
+        assert!(
+            (longitude - std::f64::consts::FRAC_PI_2).abs() < 1e-9,
+            "{longitude}"
+        );
+        assert_states_match(&state, &elements.to_state(&gm));
+        let year = dbig_to_f64(&elements.period(&gm).unwrap().to_days());
+        assert!((year - 366.73).abs() < 0.01, "{year}");
+
+        // an exactly circular inclined orbit counts its true anomaly from the node
+        let inclined = EquinoctialElements {
+            semi_latus_rectum: Length::from_meters(f64_to_dbig(radius)),
+            f: DBig::ZERO,
+            g: DBig::ZERO,
+            h: f64_to_dbig(0.1),
+            k: f64_to_dbig(0.2),
+            true_longitude: Angle::from_radians(f64_to_dbig(2.0)),
+            retrograde: false,
+        };
+        let elements = inclined.to_classical();
+        assert_eq!(elements.argument_of_periapsis, Angle::zero());
+        assert_states_match(&inclined.to_state(&gm), &elements.to_state(&gm));
+
+        // the same orbit run clockwise, inclined by exactly pi
+        let retrograde =
+            StateVector::new(state.position.clone(), state.velocity * f64_to_dbig(-1.0));
+        let elements = OrbitalElements::from_state(&retrograde, &gm).unwrap();
+        let inclination = dbig_to_f64(&elements.inclination.to_radians());
+        assert!(
+            (inclination - std::f64::consts::PI).abs() < 1e-15,
+            "{inclination}"
+        );
+        assert_states_match(&retrograde, &elements.to_state(&gm));
+        let equinoctial = EquinoctialElements::from_state(&retrograde, &gm).unwrap();
+        assert!(equinoctial.retrograde);
+        assert_states_match(&retrograde, &equinoctial.to_state(&gm));
+
+        // and an inclined retrograde ellipse
+        let elements = OrbitalElements {
+            periapsis_distance: Length::from_meters(f64_to_dbig(1e11)),
+            eccentricity: f64_to_dbig(0.3),
+            inclination: Angle::from_degrees(DBig::from(150)),
+            longitude_of_ascending_node: Angle::from_degrees(DBig::from(40)),
+            argument_of_periapsis: Angle::from_degrees(DBig::from(70)),
+            true_anomaly: Angle::from_degrees(DBig::from(-100)),
+        };
+        let back = OrbitalElements::from_state(&elements.to_state(&gm), &gm).unwrap();
+        for (a, b) in [
+            (&elements.inclination, &back.inclination),
+            (
+                &elements.longitude_of_ascending_node,
+                &back.longitude_of_ascending_node,
+            ),
+            (&elements.argument_of_periapsis, &back.argument_of_periapsis),
+            (&elements.true_anomaly, &back.true_anomaly),
+        ] {
+            assert!((dbig_to_f64(&(a - b).to_degrees())).abs() < 1e-9, "{a} {b}");
+        }
+    }
+}
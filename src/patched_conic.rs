@@ -0,0 +1,355 @@
+//! Massless craft on patched conics. A craft follows a Keplerian conic around its parent until
+//! it leaves the parent's sphere of influence or enters the sphere of one of its siblings, then
+//! it moves under the new body in the hierarchy and continues on the conic through its state
+//! relative to it.
+//!
+//! Crossings between two updates are found by sampling with steps no longer than the time the
+//! craft needs to reach the nearest sphere at its current relative speed, then refined with
+//! [`find_root`]. Spheres have the Laplace radius of [`Simulation::sphere_of_influence`], with
+//! the mass a body's satellites feel, see [`Simulation::central_mass`]. Updating backwards in
+//! time keeps the current parent.
+
+use crate::body::{Body, BodyDynamics};
+use crate::conic::ConicDynamics;
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::events::{find_root, DEFAULT_TOLERANCE};
+use crate::reference_frame::StateVector;
+use crate::simulation::Simulation;
+use crate::sin_cos::f64_to_dbig;
+use crate::units::{Length, Mass, Time};
+use dashu_float::ops::Abs;
+use dashu_float::DBig;
+
+/// Shortest sampling step while looking for crossings, in seconds.
+const MIN_STEP: f64 = 1.0;
+
+impl Body {
+    /// A massless craft moving on `trajectory` around the body it is added to.
+    pub fn craft(name: &str, trajectory: ConicDynamics) -> Body {
+        Body::new(
+            name,
+            Mass::zero(),
+            Length::zero(),
+            BodyDynamics::Craft(trajectory),
+        )
+    }
+
+    pub fn is_craft(&self) -> bool {
+        matches!(self.dynamics, BodyDynamics::Craft(_))
+    }
+}
+
+/// A sphere of influence boundary a craft can cross.
+#[derive(Debug, Clone, Copy)]
+enum Boundary {
+    /// Leaving the sphere of `parent` for `grandparent`.
+    Escape { parent: i32, grandparent: i32 },
+    /// Entering the sphere of `body`, a sibling of the craft.
+    Encounter { body: i32 },
+}
+
+impl Simulation {
+    /// Adds a craft at `state`, given relative to `parent`, at the current simulation time.
+    /// `None` if `parent` is unknown or `state` has no conic, see
+    /// [`crate::orbital_elements::OrbitalElements::from_state`].
+    pub fn add_craft(&mut self, name: &str, parent: i32, state: &StateVector) -> Option<i32> {
+        let gm = self.constants().gm_from_mass(&self.central_mass(parent)?);
+        let trajectory = ConicDynamics::from_state(state, self.time(), gm)?;
+        Some(self.add_hierarchy(Body::craft(name, trajectory), Some(parent)))
+    }
+
+    /// State of body `id` relative to its parent at `time`, the velocity differenced over the
+    /// same second [`Simulation::try_update`] uses.
+    pub(crate) fn relative_state_at(&self, time: &Time, id: i32) -> StateVector {
+        let position = self.get_relative_position(time, id);
+        let second_ago = time - &Time::from_seconds(DBig::ONE);
+        let velocity = &position - &self.get_relative_position(&second_ago, id);
+        StateVector::new(position, velocity)
+    }
+
+    /// Sphere of influence radius of body `id` sitting at `offset` from its parent `parent`.
+    fn sphere_of_influence_at(
+        &self,
+        id: i32,
+        parent: i32,
+        offset: &DecimalVector3d,
+    ) -> Option<DBig> {
+        let mass_ratio = &self.central_mass(id)? / &self.central_mass(parent)?;
+        Some(offset.length() * mass_ratio.powf(&f64_to_dbig(0.4)))
+    }
+
+    fn boundaries(&self, craft: i32) -> Vec<Boundary> {
+        let Some(parent) = self.get_body_by_id(craft).and_then(|craft| craft.parent()) else {
+            return vec![];
+        };
+        let parent_body = self.get_body_by_id(parent).unwrap();
+        let mut boundaries: Vec<Boundary> = parent_body
+            .parent()
+            .map(|grandparent| Boundary::Escape {
+                parent,
+                grandparent,
+            })
+            .into_iter()
+            .collect();
+        boundaries.extend(
+            self.resolve_hierarchy_down(parent_body)
+                .into_iter()
+                .filter(|body| body.parent() == Some(parent) && !body.body().is_craft())
+                .filter(|body| {
+                    self.central_mass(body.id())
+                        .is_some_and(|m| m > Mass::zero())
+                })
+                .map(|body| Boundary::Encounter { body: body.id() }),
+        );
+        boundaries
+    }
+
+    /// Distance of a craft at `state`, relative to its parent, from `boundary` at `time`,
+    /// positive outside the sphere, and the relative speed bounding how fast it changes.
+    fn boundary_distance(
+        &self,
+        state: &StateVector,
+        time: &Time,
+        boundary: Boundary,
+    ) -> Option<(DBig, DBig)> {
+        match boundary {
+            Boundary::Escape {
+                parent,
+                grandparent,
+            } => {
+                let parent_state = self.relative_state_at(time, parent);
+                let radius =
+                    self.sphere_of_influence_at(parent, grandparent, &parent_state.position)?;
+                Some((state.position.length() - radius, state.velocity.length()))
+            }
+            Boundary::Encounter { body } => {
+                let parent = self.get_body_by_id(body)?.parent()?;
+                let body_state = self.relative_state_at(time, body);
+                let radius = self.sphere_of_influence_at(body, parent, &body_state.position)?;
+                Some((
+                    (&state.position - &body_state.position).length() - radius,
+                    (&state.velocity - &body_state.velocity).length(),
+                ))
+            }
+        }
+    }
+
+    /// First boundary craft `id` crosses between `from` and `to`, with the time it does.
+    fn next_crossing(&self, id: i32, from: &Time, to: &Time) -> Option<(Time, Boundary)> {
+        let Some(BodyDynamics::Craft(trajectory)) =
+            self.get_body_by_id(id).map(|craft| &craft.body().dynamics)
+        else {
+            return None;
+        };
+        let boundaries = self.boundaries(id);
+        if boundaries.is_empty() {
+            return None;
+        }
+        let distance = |time: &Time, boundary: Boundary| {
+            self.boundary_distance(&trajectory.state_at(time), time, boundary)
+        };
+        let sample = |time: &Time| {
+            let state = trajectory.state_at(time);
+            boundaries
+                .iter()
+                .map(|&boundary| self.boundary_distance(&state, time, boundary))
+                .collect::<Option<Vec<(DBig, DBig)>>>()
+        };
+
+        let min_step = f64_to_dbig(MIN_STEP);
+        let tolerance = f64_to_dbig(DEFAULT_TOLERANCE);
+        let mut time = from.clone();
+        let mut values = sample(&time)?;
+        while time < *to {
+            let step = values
+                .iter()
+                .filter(|(_, speed)| *speed > DBig::ZERO)
+                .map(|(distance, speed)| distance.clone().abs() / speed)
+                .reduce(|shortest, step| shortest.min(step))
+                .map_or(to.to_seconds() - time.to_seconds(), |step| {
+                    step.max(min_step.clone())
+                });
+            let next_time = (&time + &Time::from_seconds(step)).min(to.clone());
+            let next_values = sample(&next_time)?;
+
+            let mut earliest: Option<(Time, Boundary)> = None;
+            for (index, &boundary) in boundaries.iter().enumerate() {
+                let (before, after) = (&values[index].0, &next_values[index].0);
+                let crossed = match boundary {
+                    Boundary::Escape { .. } => *before <= DBig::ZERO && *after > DBig::ZERO,
+                    Boundary::Encounter { .. } => *before > DBig::ZERO && *after <= DBig::ZERO,
+                };
+                if !crossed {
+                    continue;
+                }
+                let root = Time::from_seconds(find_root(
+                    |t| Some(distance(&Time::from_seconds(t.clone()), boundary)?.0),
+                    (time.to_seconds(), before.clone()),
+                    (next_time.to_seconds(), after.clone()),
+                    &tolerance,
+                )?);
+                if earliest.as_ref().is_none_or(|(first, _)| root < *first) {
+                    earliest = Some((root, boundary));
+                }
+            }
+            if earliest.is_some() {
+                return earliest;
+            }
+            time = next_time;
+            values = next_values;
+        }
+        None
+    }
+
+    /// Moves craft `id` under the body whose sphere of influence it is in at `to`, handling the
+    /// crossings since `from` in order.
+    pub(crate) fn patch_craft(&mut self, id: i32, from: &Time, to: &Time) {
+        let mut from = from.clone();
+        while let Some((crossing, boundary)) = self.next_crossing(id, &from, to) {
+            let Some(BodyDynamics::Craft(trajectory)) =
+                self.get_body_by_id(id).map(|craft| &craft.body().dynamics)
+            else {
+                break;
+            };
+            let state = trajectory.state_at(&crossing);
+            let (parent, state) = match boundary {
+                Boundary::Escape {
+                    parent,
+                    grandparent,
+                } => {
+                    let parent_state = self.relative_state_at(&crossing, parent);
+                    (
+                        grandparent,
+                        StateVector::new(
+                            state.position + parent_state.position,
+                            state.velocity + parent_state.velocity,
+                        ),
+                    )
+                }
+                Boundary::Encounter { body } => {
+                    let body_state = self.relative_state_at(&crossing, body);
+                    (
+                        body,
+                        StateVector::new(
+                            state.position - body_state.position,
+                            state.velocity - body_state.velocity,
+                        ),
+                    )
+                }
+            };
+            let Some(central_mass) = self.central_mass(parent) else {
+                break;
+            };
+            let gm = self.constants().gm_from_mass(&central_mass);
+            // a purely radial state has no conic, the craft stays where it is
+            let Some(trajectory) = ConicDynamics::from_state(&state, &crossing, gm) else {
+                break;
+            };
+            self.reparent(id, parent);
+            self.set_dynamics(id, BodyDynamics::Craft(trajectory));
+            from = crossing;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{OrbitingBodyDynamics, StaticBodyDynamics};
+    use crate::reference_frame::ReferenceFrame;
+    use crate::sin_cos::dbig_to_f64;
+    use crate::units::Angle;
+
+    fn solar_system() -> (Simulation, i32, i32) {
+        let mut sim = Simulation::new();
+        let earth = Body::new(
+            "Earth",
+            Mass::from_kilograms(f64_to_dbig(5.972e24)),
+            Length::from_meters(f64_to_dbig(6.371e6)),
+            BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                orbit_radius: Length::from_au(DBig::ONE),
+                orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                orbit_period: Time::from_days(f64_to_dbig(365.25)),
+                orbit_phase: Angle::zero(),
+            }),
+        );
+        let sun = sim.add_hierarchy(
+            Body {
+                satellites: vec![earth],
+                ..Body::new(
+                    "Sun",
+                    Mass::from_kilograms(f64_to_dbig(1.989e30)),
+                    Length::from_meters(f64_to_dbig(6.9634e8)),
+                    BodyDynamics::Static(StaticBodyDynamics {
+                        position: DecimalVector3d::zero(),
+                    }),
+                )
+            },
+            None,
+        );
+        sim.update(Time::zero());
+        (sim, sun, sun + 1)
+    }
+
+    fn children(sim: &Simulation, id: i32) -> Vec<i32> {
+        sim.resolve_hierarchy_down(sim.get_body_by_id(id).unwrap())
+            .into_iter()
+            .map(|body| body.id())
+            .collect()
+    }
+
+    #[test]
+    fn escaping_craft_moves_under_the_sun() {
+        let (mut sim, sun, earth) = solar_system();
+        // 12 km/s at 7000 km from the center is well above the 10.7 km/s escape speed
+        let state = StateVector::new(
+            DecimalVector3d::from_f64(7e6, 0.0, 0.0),
+            DecimalVector3d::from_f64(0.0, 0.0, -1.2e4),
+        );
+        let craft = sim.add_craft("probe", earth, &state).unwrap();
+        let mut stepped = sim.clone();
+
+        sim.update(Time::from_days(DBig::ONE));
+        assert_eq!(sim.get_body_by_id(craft).unwrap().parent(), Some(earth));
+        sim.update(Time::from_days(DBig::from(10)));
+        assert_eq!(sim.get_body_by_id(craft).unwrap().parent(), Some(sun));
+        assert_eq!(children(&sim, earth), Vec::<i32>::new());
+        assert!(children(&sim, sun).contains(&craft));
+        let soi = dbig_to_f64(&sim.sphere_of_influence(earth).unwrap().to_meters());
+        assert!(dbig_to_f64(&sim.get_distance(craft, earth).unwrap().to_meters()) > soi);
+
+        // the crossing does not depend on how the time is stepped
+        for day in 1..=10 {
+            stepped.update(Time::from_days(DBig::from(day)));
+        }
+        let drift = sim
+            .get_body_by_id(craft)
+            .unwrap()
+            .position()
+            .distance_to(stepped.get_body_by_id(craft).unwrap().position().clone());
+        assert!(dbig_to_f64(&drift) < 1.0, "{drift}");
+    }
+
+    #[test]
+    fn passing_craft_flies_by_the_earth() {
+        let (mut sim, sun, earth) = solar_system();
+        let earth_state = sim
+            .get_body_state_in(earth, ReferenceFrame::ParentCentered { id: earth })
+            .unwrap();
+        // 2e9 m behind the Earth along its orbit, catching up at 5 km/s and passing 2e7 m aside
+        let along = earth_state.velocity.normalized();
+        let aside = DecimalVector3d::from_f64(0.0, 1.0, 0.0);
+        let state = StateVector::new(
+            earth_state.position.clone() - along.clone() * f64_to_dbig(2e9)
+                + aside * f64_to_dbig(2e7),
+            earth_state.velocity.clone() + along * f64_to_dbig(5e3),
+        );
+        let craft = sim.add_craft("comet", sun, &state).unwrap();
+
+        sim.update(Time::from_days(DBig::from(3)));
+        assert_eq!(sim.get_body_by_id(craft).unwrap().parent(), Some(earth));
+        assert_eq!(children(&sim, earth), vec![craft]);
+        sim.update(Time::from_days(DBig::from(9)));
+        assert_eq!(sim.get_body_by_id(craft).unwrap().parent(), Some(sun));
+    }
+}
@@ -0,0 +1,275 @@
+use crate::chebyshev::{ChebyshevBody, ChebyshevEphemeris};
+use crate::sin_cos::dbig_to_f64;
+use crate::units::Time;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaybackError {
+    /// `time` lies outside the segments recorded for body `id`.
+    OutOfCoverage {
+        id: i32,
+        time: Time,
+    },
+    /// The segments of body `id` were fitted with `max_error` meters, more than `bound` allows.
+    ErrorBoundExceeded {
+        id: i32,
+        max_error: f64,
+        bound: f64,
+    },
+    UnknownBody {
+        id: i32,
+    },
+    /// The segments of body `id` carry no fit error, so no bound can be checked.
+    UnknownErrorBound {
+        id: i32,
+    },
+    /// Body `id` has no segments, so it covers no time at all.
+    NoSegments {
+        id: i32,
+    },
+}
+
+impl fmt::Display for PlaybackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlaybackError::OutOfCoverage { id, time } => {
+                write!(f, "body {id} has no ephemeris coverage at {time}")
+            }
+            PlaybackError::ErrorBoundExceeded {
+                id,
+                max_error,
+                bound,
+            } => write!(
+                f,
+                "ephemeris of body {id} is accurate to {max_error} m, {bound} m was required"
+            ),
+            PlaybackError::UnknownBody { id } => write!(f, "no ephemeris for body {id}"),
+            PlaybackError::UnknownErrorBound { id } => {
+                write!(f, "ephemeris of body {id} does not state its accuracy")
+            }
+            PlaybackError::NoSegments { id } => write!(f, "ephemeris of body {id} is empty"),
+        }
+    }
+}
+
+impl std::error::Error for PlaybackError {}
+
+pub(crate) fn check_error_bound(body: &ChebyshevBody, bound: f64) -> Result<(), PlaybackError> {
+    if body.segments.is_empty() {
+        return Err(PlaybackError::NoSegments { id: body.id });
+    }
+    let max_error = body
+        .max_error()
+        .ok_or(PlaybackError::UnknownErrorBound { id: body.id })?;
+    if max_error > bound {
+        return Err(PlaybackError::ErrorBoundExceeded {
+            id: body.id,
+            max_error,
+            bound,
+        });
+    }
+    Ok(())
+}
+
+struct PlaybackBody {
+    segments: ChebyshevBody,
+    start: f64,
+    interval: f64,
+    origin: [f64; 3],
+}
+
+/// Plain f64 playback of a [`ChebyshevEphemeris`], without a `Simulation`. Looking up a body
+/// costs one segment index computation and one polynomial evaluation per parent.
+pub struct ChebyshevPlayback {
+    bodies: HashMap<i32, PlaybackBody>,
+}
+
+impl ChebyshevPlayback {
+    /// Fails if any body's fit is off by more than `max_error` meters anywhere.
+    pub fn new(ephemeris: ChebyshevEphemeris, max_error: f64) -> Result<Self, PlaybackError> {
+        let mut bodies = HashMap::new();
+        for segments in ephemeris.bodies {
+            check_error_bound(&segments, max_error)?;
+            let origin = &segments.origin;
+            bodies.insert(
+                segments.id,
+                PlaybackBody {
+                    start: dbig_to_f64(&segments.start.to_seconds()),
+                    interval: dbig_to_f64(&segments.interval.to_seconds()),
+                    origin: [
+                        dbig_to_f64(&origin.x),
+                        dbig_to_f64(&origin.y),
+                        dbig_to_f64(&origin.z),
+                    ],
+                    segments,
+                },
+            );
+        }
+        Ok(ChebyshevPlayback { bodies })
+    }
+
+    /// Position and velocity of body `id` relative to its center at `time` TDB seconds since
+    /// [`Epoch::j2000`](crate::time::Epoch::j2000), see [`ChebyshevBody::start`].
+    pub fn relative_state(&self, id: i32, time: f64) -> Result<[[f64; 3]; 2], PlaybackError> {
+        let body = self
+            .bodies
+            .get(&id)
+            .ok_or(PlaybackError::UnknownBody { id })?;
+        let out_of_coverage = || PlaybackError::OutOfCoverage {
+            id,
+            time: Time::from_seconds(crate::sin_cos::f64_to_dbig(time)),
+        };
+        let elapsed = (time - body.start) / body.interval;
+        let count = body.segments.segments.len();
+        if count == 0 || !(0.0..=count as f64).contains(&elapsed) {
+            return Err(out_of_coverage());
+        }
+        let index = (elapsed as usize).min(count - 1);
+        let x = (elapsed - index as f64) * 2.0 - 1.0;
+        let (mut position, mut velocity) = body.segments.segments[index].evaluate(x);
+        for axis in 0..3 {
+            position[axis] += body.origin[axis];
+            velocity[axis] *= 2.0 / body.interval;
+        }
+        Ok([position, velocity])
+    }
+
+    /// Position and velocity of body `id` in the global frame at `time` seconds, summed up
+    /// through its centers.
+    pub fn state(&self, id: i32, time: f64) -> Result<[[f64; 3]; 2], PlaybackError> {
+        let [mut position, mut velocity] = self.relative_state(id, time)?;
+        let mut center = self.bodies[&id].segments.center;
+        while let Some(id) = center {
+            let [center_position, center_velocity] = self.relative_state(id, time)?;
+            for axis in 0..3 {
+                position[axis] += center_position[axis];
+                velocity[axis] += center_velocity[axis];
+            }
+            center = self.bodies[&id].segments.center;
+        }
+        Ok([position, velocity])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{
+        Body, BodyDynamics, EphemerisBodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics,
+    };
+    use crate::decimal_vector_3d::DecimalVector3d;
+    use crate::simulation::Simulation;
+    use crate::sin_cos::f64_to_dbig;
+    use crate::units::{Angle, Length, Mass};
+
+    fn star(planet_dynamics: BodyDynamics) -> Body {
+        let planet = Body {
+            rotation_period: Time::from_seconds(f64_to_dbig(86400.0)),
+            ..Body::new(
+                "planet",
+                Mass::from_kilograms(f64_to_dbig(6e24)),
+                Length::from_meters(f64_to_dbig(6.4e6)),
+                planet_dynamics,
+            )
+        };
+        Body {
+            satellites: vec![planet],
+            rotation_period: Time::from_seconds(f64_to_dbig(2e6)),
+            ..Body::new(
+                "star",
+                Mass::from_kilograms(f64_to_dbig(2e30)),
+                Length::from_meters(f64_to_dbig(7e8)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::from_f64(1e12, 0.0, 0.0),
+                }),
+            )
+        }
+    }
+
+    #[test]
+    fn plays_back_within_coverage() {
+        let mut original = Simulation::new();
+        original.add_hierarchy(
+            star(BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                orbit_radius: Length::from_meters(f64_to_dbig(1.5e11)),
+                orbit_period: Time::from_seconds(f64_to_dbig(3.15e7)),
+                orbit_phase: Angle::zero(),
+                orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+            })),
+            None,
+        );
+        let ephemeris = original
+            .generate_chebyshev_ephemeris(
+                None,
+                Time::zero(),
+                Time::from_seconds(f64_to_dbig(2e6)),
+                Time::from_seconds(f64_to_dbig(1e6)),
+                10,
+            )
+            .unwrap();
+        let segments = ephemeris.body(1).unwrap().clone();
+        assert!(matches!(
+            EphemerisBodyDynamics::new(segments.clone(), 0.0),
+            Err(PlaybackError::ErrorBoundExceeded { id: 1, .. })
+        ));
+        let mut empty = segments.clone();
+        empty.segments.clear();
+        assert_eq!(
+            EphemerisBodyDynamics::new(empty.clone(), 1.0).unwrap_err(),
+            PlaybackError::NoSegments { id: 1 }
+        );
+        let empty = ChebyshevEphemeris {
+            bodies: vec![empty],
+        };
+        assert!(ChebyshevPlayback::new(empty, 1.0).is_err());
+
+        let mut played = Simulation::new();
+        played.add_hierarchy(
+            star(BodyDynamics::Ephemeris(
+                EphemerisBodyDynamics::new(segments, 1.0).unwrap(),
+            )),
+            None,
+        );
+        let time = Time::from_seconds(f64_to_dbig(1.5e6));
+        played.update(time.clone());
+        original.update(time.clone());
+        let distance = played
+            .get_body_by_id(1)
+            .unwrap()
+            .position()
+            .distance_to(original.get_body_by_id(1).unwrap().position().clone());
+        assert!(dbig_to_f64(&distance) < 1.0);
+
+        let outside = Time::from_seconds(f64_to_dbig(2.5e6));
+        assert_eq!(
+            played.try_update(outside.clone()),
+            Err(PlaybackError::OutOfCoverage {
+                id: 1,
+                time: outside.clone()
+            })
+        );
+        assert_eq!(dbig_to_f64(&played.time().to_seconds()), 1.5e6);
+        // queries reaching past the coverage give up instead of panicking
+        assert!(played.at(outside.clone()).is_none());
+        assert!(played
+            .closest_approach(
+                0,
+                1,
+                time.clone(),
+                outside,
+                Time::from_seconds(f64_to_dbig(1e5))
+            )
+            .is_none());
+
+        let playback = ChebyshevPlayback::new(ephemeris, 1.0).unwrap();
+        let [position, _] = playback.state(1, 1.5e6).unwrap();
+        let expected = original.get_body_by_id(1).unwrap().position();
+        assert!((position[0] - dbig_to_f64(&expected.x)).abs() < 1.0);
+        assert!((position[2] - dbig_to_f64(&expected.z)).abs() < 1.0);
+        assert!(matches!(
+            playback.state(1, -1.0),
+            Err(PlaybackError::OutOfCoverage { id: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::decimal_matrix_3d::DecimalMatrix3d;
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::simulation::Simulation;
+use dashu_float::DBig;
+
+/// Position (meters) and velocity (meters per second) of a point relative to some frame.
+#[derive(Debug, Clone)]
+pub struct StateVector {
+    pub position: DecimalVector3d,
+    pub velocity: DecimalVector3d,
+}
+
+impl StateVector {
+    pub fn new(position: DecimalVector3d, velocity: DecimalVector3d) -> StateVector {
+        StateVector { position, velocity }
+    }
+
+    pub fn zero() -> StateVector {
+        StateVector::new(DecimalVector3d::zero(), DecimalVector3d::zero())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceFrame {
+    /// The implicit frame every `SimulatedBody` position is stored in.
+    Global,
+    /// Inertial frame centered on the parent of the body, heliocentric for a planet.
+    ParentCentered { id: i32 },
+    /// Inertial frame centered on the body, axes parallel to the global ones.
+    BodyCentered { id: i32 },
+    /// Frame centered on the body and rotating with it, see [`crate::body::Body::orientation_at`].
+    BodyFixed { id: i32 },
+    /// Inertial frame centered on the center of mass of the hierarchy the body belongs to.
+    Barycentric { id: i32 },
+}
+
+/// Where a frame sits in the global frame at the current simulation time.
+struct FrameOrigin {
+    origin: StateVector,
+    /// Maps frame coordinates into global coordinates.
+    orientation: DecimalMatrix3d,
+    angular_velocity: DecimalVector3d,
+}
+
+impl FrameOrigin {
+    fn inertial(origin: StateVector) -> FrameOrigin {
+        FrameOrigin {
+            origin,
+            orientation: DecimalMatrix3d::identity(),
+            angular_velocity: DecimalVector3d::zero(),
+        }
+    }
+
+    fn to_global(&self, state: &StateVector) -> StateVector {
+        let offset = self.orientation.apply(state.position.clone());
+        let velocity = self.orientation.apply(state.velocity.clone())
+            + self.angular_velocity.cross(offset.clone())
+            + &self.origin.velocity;
+        StateVector::new(offset + &self.origin.position, velocity)
+    }
+
+    fn to_local(&self, state: &StateVector) -> StateVector {
+        let inverse = self.orientation.transposed();
+        let offset = &state.position - &self.origin.position;
+        let velocity =
+            &state.velocity - &self.origin.velocity - self.angular_velocity.cross(offset.clone());
+        StateVector::new(inverse.apply(offset), inverse.apply(velocity))
+    }
+}
+
+impl Simulation {
+    fn body_state(&self, id: i32) -> Option<StateVector> {
+        self.get_body_by_id(id)
+            .map(|body| StateVector::new(body.position().clone(), body.velocity().clone()))
+    }
+
+    /// Center of mass of the hierarchy `id` belongs to, in the global frame.
+    pub fn barycenter(&self, id: i32) -> Option<StateVector> {
+        let root = self.get_body_by_id(self.get_root_id(id))?;
+        let mut members = self.resolve_hierarchy_down(root);
+        members.push(root);
+
+        let mut total_mass = DBig::ZERO;
+        let mut position = DecimalVector3d::zero();
+        let mut velocity = DecimalVector3d::zero();
+        for member in members {
+            let mass = member.body().mass.to_kilograms();
+            total_mass += &mass;
+            position += member.position() * &mass;
+            velocity += member.velocity() * &mass;
+        }
+        if total_mass == DBig::ZERO {
+            return None;
+        }
+        Some(StateVector::new(
+            position / &total_mass,
+            velocity / &total_mass,
+        ))
+    }
+
+    fn frame_origin(&self, frame: ReferenceFrame) -> Option<FrameOrigin> {
+        match frame {
+            ReferenceFrame::Global => Some(FrameOrigin::inertial(StateVector::zero())),
+            ReferenceFrame::ParentCentered { id } => {
+                let parent = self.get_body_by_id(id)?.parent()?;
+                self.body_state(parent).map(FrameOrigin::inertial)
+            }
+            ReferenceFrame::BodyCentered { id } => self.body_state(id).map(FrameOrigin::inertial),
+            ReferenceFrame::BodyFixed { id } => {
+                let body = self.get_body_by_id(id)?;
+                Some(FrameOrigin {
+                    origin: self.body_state(id)?,
+                    orientation: body.orientation().clone(),
+                    angular_velocity: self.body_angular_velocity(id)?,
+                })
+            }
+            ReferenceFrame::Barycentric { id } => self.barycenter(id).map(FrameOrigin::inertial),
+        }
+    }
+
+    /// Re-expresses `state`, given relative to `from`, relative to `to` at the current
+    /// simulation time. Returns `None` if a frame refers to an unknown body, a parent-centered
+    /// frame is asked for a root, or a barycentric frame has no mass.
+    pub fn transform(
+        &self,
+        state: &StateVector,
+        from: ReferenceFrame,
+        to: ReferenceFrame,
+    ) -> Option<StateVector> {
+        if from == to {
+            return Some(state.clone());
+        }
+        let global = self.frame_origin(from)?.to_global(state);
+        Some(self.frame_origin(to)?.to_local(&global))
+    }
+
+    /// State of body `id` relative to `frame`.
+    pub fn get_body_state_in(&self, id: i32, frame: ReferenceFrame) -> Option<StateVector> {
+        self.transform(&self.body_state(id)?, ReferenceFrame::Global, frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics};
+    use crate::sin_cos::{dbig_to_f64, f64_to_dbig};
+    use crate::units::{Angle, Length, Mass, Time};
+
+    fn simulation() -> (Simulation, i32, i32) {
+        let planet = Body {
+            rotation_axis: DecimalVector3d::from_f64(0.2, 1.0, 0.1).normalized(),
+            rotation_period: Time::from_seconds(f64_to_dbig(86400.0)),
+            ..Body::new(
+                "planet",
+                Mass::from_kilograms(f64_to_dbig(6e24)),
+                Length::from_meters(f64_to_dbig(6.4e6)),
+                BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                    orbit_radius: Length::from_meters(f64_to_dbig(1.5e11)),
+                    orbit_period: Time::from_seconds(f64_to_dbig(3.15e7)),
+                    orbit_phase: Angle::zero(),
+                    orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                }),
+            )
+        };
+        let star = Body {
+            satellites: vec![planet],
+            rotation_period: Time::from_seconds(f64_to_dbig(2e6)),
+            ..Body::new(
+                "star",
+                Mass::from_kilograms(f64_to_dbig(2e30)),
+                Length::from_meters(f64_to_dbig(7e8)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::from_f64(1e12, 0.0, 0.0),
+                }),
+            )
+        };
+        let mut sim = Simulation::new();
+        let star_id = sim.add_hierarchy(star, None);
+        sim.update(Time::from_seconds(f64_to_dbig(12345.0)));
+        (sim, star_id, star_id + 1)
+    }
+
+    fn assert_close(a: &DecimalVector3d, b: &DecimalVector3d, tolerance: f64) {
+        let distance = dbig_to_f64(&a.distance_to(b.clone()));
+        assert!(distance < tolerance, "{a} and {b} are {distance} apart");
+    }
+
+    #[test]
+    fn parent_centered_is_heliocentric() {
+        let (sim, star_id, planet_id) = simulation();
+        let state = sim
+            .get_body_state_in(planet_id, ReferenceFrame::ParentCentered { id: planet_id })
+            .unwrap();
+        let star = sim.get_body_by_id(star_id).unwrap();
+        let planet = sim.get_body_by_id(planet_id).unwrap();
+        assert_close(
+            &(planet.position() - star.position()),
+            &state.position,
+            1e-6,
+        );
+        assert!(dbig_to_f64(&state.position.length()) > 1.4e11);
+    }
+
+    #[test]
+    fn body_fixed_round_trip() {
+        let (sim, star_id, planet_id) = simulation();
+        let state = StateVector::new(
+            DecimalVector3d::from_f64(6.4e6, 1.0e5, -2.0e5),
+            DecimalVector3d::from_f64(10.0, -3.0, 7.0),
+        );
+        let fixed = ReferenceFrame::BodyFixed { id: planet_id };
+        let barycentric = ReferenceFrame::Barycentric { id: star_id };
+        let there = sim.transform(&state, fixed, barycentric).unwrap();
+        let back = sim.transform(&there, barycentric, fixed).unwrap();
+        assert_close(&state.position, &back.position, 1e-6);
+        assert_close(&state.velocity, &back.velocity, 1e-9);
+    }
+
+    #[test]
+    fn surface_point_co_rotates() {
+        let (sim, _, planet_id) = simulation();
+        let planet = sim.get_body_by_id(planet_id).unwrap();
+        // a point resting on the equator moves with the surface speed in the body-centered frame
+        let radius = 6.4e6;
+        let state = StateVector::new(
+            DecimalVector3d::from_f64(radius, 0.0, 0.0),
+            DecimalVector3d::zero(),
+        );
+        let inertial = sim
+            .transform(
+                &state,
+                ReferenceFrame::BodyFixed { id: planet_id },
+                ReferenceFrame::BodyCentered { id: planet_id },
+            )
+            .unwrap();
+        let speed = dbig_to_f64(&inertial.velocity.length());
+        let expected = radius * std::f64::consts::TAU / 86400.0;
+        assert!((speed - expected).abs() < 1e-6);
+        let axis = planet.body().rotation_axis.clone();
+        assert!(dbig_to_f64(&inertial.position.dot(axis)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn nested_velocity_is_global() {
+        let (mut sim, _, planet_id) = simulation();
+        // a moon on a copy of the planet's orbit, so it circles the planet as fast as the planet
+        // circles the star
+        let mut moon = sim.get_body_by_id(planet_id).unwrap().body().clone();
+        moon.name = String::from("moon");
+        let moon_id = sim.add_hierarchy(moon, Some(planet_id));
+        sim.update(Time::from_seconds(f64_to_dbig(12345.0)));
+        let planet_velocity = sim.get_body_by_id(planet_id).unwrap().velocity().clone();
+        let moon_velocity = sim.get_body_by_id(moon_id).unwrap().velocity().clone();
+        assert_close(
+            &moon_velocity,
+            &(planet_velocity.clone() * DBig::from(2)),
+            1e-3,
+        );
+        let state = sim
+            .get_body_state_in(moon_id, ReferenceFrame::BodyCentered { id: planet_id })
+            .unwrap();
+        assert_close(&state.velocity, &planet_velocity, 1e-3);
+    }
+}
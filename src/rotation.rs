@@ -0,0 +1,682 @@
+//! Rotation models moving the pole and prime meridian of a body over time, set in
+//! [`Body::rotation_model`](crate::body::Body::rotation_model).
+//!
+//! [`IauRotation`] follows the report of the IAU Working Group on Cartographic Coordinates and
+//! Rotational Elements: the pole's right ascension and declination are polynomials in Julian
+//! centuries and the prime meridian angle `W` one in days, each plus a series of periodic
+//! terms, which covers nutation and the libration of locked moons. Angles are in degrees
+//! relative to the ICRF, turned y-up like Horizons vectors, and `time` counts from J2000.0 TDB.
+//! A simulation shifts its own time by [`Simulation::epoch`] before evaluating them.
+//!
+//! [`PrecessionCone`] swings the pole around a fixed axis for Earth-like axial precession, and
+//! [`SpinOrbitResonance`] ties the spin to the body's own orbit for tidally locked moons.
+//! [`RigidBodyAttitude`] lets a body tumble freely.
+
+use crate::attitude::RigidBodyAttitude;
+use crate::body::{Body, BodyDynamics};
+use crate::decimal_matrix_3d::DecimalMatrix3d;
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::horizons::y_up;
+use crate::precession::SecularPrecession;
+use crate::simulation::Simulation;
+use crate::sin_cos::{cos, precise, sin, PIMUL2};
+use crate::time::{TimeScale, SECONDS_PER_DAY};
+use crate::units::{Angle, Time};
+use dashu_float::ops::SquareRoot;
+use dashu_float::DBig;
+
+const DAYS_PER_CENTURY: u32 = 36525;
+
+#[derive(Debug, Clone)]
+pub enum RotationModel {
+    Iau(IauRotation),
+    PrecessionCone(PrecessionCone),
+    SpinOrbit(SpinOrbitResonance),
+    /// Tumbling freely, see [`crate::attitude`].
+    RigidBody(Box<RigidBodyAttitude>),
+}
+
+/// `amplitude * sin(phase + rate * d)`, `d` in days since J2000.0, all angles in degrees.
+/// Declination terms use the cosine instead.
+#[derive(Debug, Clone)]
+pub struct IauTerm {
+    pub amplitude: DBig,
+    pub phase: DBig,
+    /// Degrees per day.
+    pub rate: DBig,
+}
+
+impl IauTerm {
+    pub fn new(amplitude: DBig, phase: DBig, rate: DBig) -> IauTerm {
+        IauTerm {
+            amplitude,
+            phase,
+            rate,
+        }
+    }
+
+    /// A term whose argument advances by `rate` degrees per Julian century, like the
+    /// nutation-precession angles of the giant planets.
+    pub fn per_century(amplitude: DBig, phase: DBig, rate: DBig) -> IauTerm {
+        IauTerm::new(amplitude, phase, rate / DBig::from(DAYS_PER_CENTURY))
+    }
+
+    /// Value and its rate per day at `days`, `cosine` picking the declination form.
+    fn evaluate(&self, days: &DBig, cosine: bool) -> (DBig, DBig) {
+        let argument = Angle::from_degrees(self.phase.clone() + self.rate.clone() * days);
+        let (sin, cos) = (
+            sin(argument.to_radians(), 32),
+            cos(argument.to_radians(), 32),
+        );
+        let rate = Angle::from_degrees(self.rate.clone()).to_radians() * &self.amplitude;
+        match cosine {
+            false => (self.amplitude.clone() * sin, rate * cos),
+            true => (self.amplitude.clone() * cos, -rate * sin),
+        }
+    }
+}
+
+/// Pole and prime meridian as in the IAU WGCCRE reports.
+#[derive(Debug, Clone)]
+pub struct IauRotation {
+    /// Degrees, coefficients of increasing powers of Julian centuries since J2000.0.
+    pub right_ascension: Vec<DBig>,
+    /// Degrees, coefficients of increasing powers of Julian centuries since J2000.0.
+    pub declination: Vec<DBig>,
+    /// Degrees, coefficients of increasing powers of days since J2000.0.
+    pub prime_meridian: Vec<DBig>,
+    pub right_ascension_terms: Vec<IauTerm>,
+    pub declination_terms: Vec<IauTerm>,
+    pub prime_meridian_terms: Vec<IauTerm>,
+}
+
+/// Polynomial with `coefficients` of increasing powers and its derivative at `x`.
+fn polynomial(coefficients: &[DBig], x: &DBig) -> (DBig, DBig) {
+    coefficients
+        .iter()
+        .rev()
+        .fold((DBig::ZERO, DBig::ZERO), |(value, derivative), c| {
+            (value.clone() * x + c, derivative * x + value)
+        })
+}
+
+/// Sum of `terms` and its rate per day at `days`.
+fn series(terms: &[IauTerm], days: &DBig, cosine: bool) -> (DBig, DBig) {
+    terms
+        .iter()
+        .map(|term| term.evaluate(days, cosine))
+        .fold((DBig::ZERO, DBig::ZERO), |(value, rate), (v, r)| {
+            (value + v, rate + r)
+        })
+}
+
+impl IauRotation {
+    /// Right ascension and declination of the pole and the prime meridian angle at `time`
+    /// since J2000.0 TDB, and the rate of the latter in radians per second.
+    pub fn angles_at(&self, time: &Time) -> (Angle, Angle, Angle, DBig) {
+        let days = precise(time.to_seconds()) / DBig::from(SECONDS_PER_DAY);
+        let centuries = days.clone() / DBig::from(DAYS_PER_CENTURY);
+        let degrees = |polynomial_part: (DBig, DBig), series_part: (DBig, DBig)| {
+            Angle::from_degrees(polynomial_part.0 + series_part.0)
+        };
+        let right_ascension = degrees(
+            polynomial(&self.right_ascension, &centuries),
+            series(&self.right_ascension_terms, &days, false),
+        );
+        let declination = degrees(
+            polynomial(&self.declination, &centuries),
+            series(&self.declination_terms, &days, true),
+        );
+        let (meridian, meridian_rate) = polynomial(&self.prime_meridian, &days);
+        let (terms, terms_rate) = series(&self.prime_meridian_terms, &days, false);
+        let spin = Angle::from_degrees(meridian_rate).to_radians() + terms_rate;
+        (
+            right_ascension,
+            declination,
+            Angle::from_degrees(meridian + terms),
+            spin / DBig::from(SECONDS_PER_DAY),
+        )
+    }
+
+    /// Body-fixed axes at `time`: x towards the prime meridian, y the pole.
+    fn axes_at(&self, time: &Time) -> (DecimalVector3d, DecimalVector3d, DBig) {
+        let (right_ascension, declination, meridian, spin) = self.angles_at(time);
+        let (ra, dec, w) = (
+            right_ascension.to_radians(),
+            declination.to_radians(),
+            meridian.to_radians(),
+        );
+        let (sin_ra, cos_ra) = (sin(ra.clone(), 32), cos(ra, 32));
+        let (sin_dec, cos_dec) = (sin(dec.clone(), 32), cos(dec, 32));
+        let pole = y_up(
+            cos_dec.clone() * &cos_ra,
+            cos_dec * &sin_ra,
+            sin_dec.clone(),
+        );
+        // ascending node of the body's equator on the ICRF equator
+        let node = y_up(-sin_ra, cos_ra, DBig::ZERO);
+        let meridian = DecimalMatrix3d::axis_angle(pole.clone(), w).apply(node);
+        (meridian, pole, spin)
+    }
+}
+
+/// Pole circling `axis` at `obliquity` once per `period`, the body spinning around it with its
+/// own rotation period. The prime meridian counts from the ascending node of the equator on
+/// the plane normal to `axis`.
+#[derive(Debug, Clone)]
+pub struct PrecessionCone {
+    pub axis: DecimalVector3d,
+    pub obliquity: Angle,
+    /// Counter-clockwise around `axis`, negative for a westward precession like the Earth's.
+    pub period: Time,
+    /// Angle the pole has already turned around `axis` at `time = 0`, from the direction
+    /// perpendicular to it closest to the global x axis.
+    pub phase: Angle,
+}
+
+impl PrecessionCone {
+    pub fn pole_at(&self, time: &Time) -> DecimalVector3d {
+        let axis = self.axis.normalized();
+        let reference = DecimalVector3d::new(DBig::ONE, DBig::ZERO, DBig::ZERO);
+        let mut tilt = axis.cross(reference);
+        if tilt.length_squared() == DBig::ZERO {
+            tilt = axis.cross(DecimalVector3d::new(DBig::ZERO, DBig::ZERO, DBig::ONE));
+        }
+        let tilted = DecimalMatrix3d::axis_angle(tilt.normalized(), self.obliquity.to_radians())
+            .apply(axis.clone());
+        DecimalMatrix3d::axis_angle(axis, self.turned(time)).apply(tilted)
+    }
+
+    fn turned(&self, time: &Time) -> DBig {
+        if self.period == Time::zero() {
+            return self.phase.to_radians();
+        }
+        PIMUL2.clone() * (time / &self.period) + self.phase.to_radians()
+    }
+
+    fn rate(&self) -> DBig {
+        if self.period == Time::zero() {
+            return DBig::ZERO;
+        }
+        PIMUL2.clone() / self.period.to_seconds()
+    }
+}
+
+/// Spin locked to the orbit, `spins` turns for every `orbits` revolutions around the parent,
+/// like the 3:2 resonance of Mercury. The prime meridian points at the parent at periapsis and
+/// the body turns evenly around the orbit normal, so on eccentric orbits the parent drifts
+/// back and forth across the sky as optical libration. Only [`BodyDynamics::Orbiting`] and
+/// closed [`BodyDynamics::Conic`] orbits can lock, other bodies keep their uniform spin. In a
+/// simulation the lock follows the orbit as [`Simulation::set_secular_precession`] turns it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpinOrbitResonance {
+    spins: u32,
+    orbits: u32,
+}
+
+impl SpinOrbitResonance {
+    /// `spins` turns for every `orbits` revolutions, `None` for zero revolutions.
+    pub fn new(spins: u32, orbits: u32) -> Option<SpinOrbitResonance> {
+        if orbits == 0 {
+            return None;
+        }
+        Some(SpinOrbitResonance { spins, orbits })
+    }
+
+    pub fn spins(&self) -> u32 {
+        self.spins
+    }
+
+    pub fn orbits(&self) -> u32 {
+        self.orbits
+    }
+
+    /// Tidally locked, the same face always towards the parent.
+    pub const SYNCHRONOUS: SpinOrbitResonance = SpinOrbitResonance {
+        spins: 1,
+        orbits: 1,
+    };
+
+    fn ratio(&self) -> DBig {
+        precise(DBig::from(self.spins)) / DBig::from(self.orbits)
+    }
+
+    /// Orbit normal, direction of the body at periapsis, or at zero phase for circular orbits,
+    /// mean anomaly at `time` and mean motion in radians per second. With a `precession` the
+    /// normal and periapsis turn with the precessed orbit.
+    fn orbit_reference(
+        dynamics: &BodyDynamics,
+        precession: Option<&SecularPrecession>,
+        time: &Time,
+    ) -> Option<(DecimalVector3d, DecimalVector3d, DBig, DBig)> {
+        let (pole, reference, anomaly, mean_motion) = match dynamics {
+            BodyDynamics::Orbiting(orbit) if orbit.orbit_period != Time::zero() => {
+                let pole = orbit.orbit_plane_normal.normalized();
+                let x = DecimalVector3d::new(DBig::ONE, DBig::ZERO, DBig::ZERO);
+                let reference = (x.clone() - pole.clone() * pole.dot(x)).normalized();
+                let anomaly = PIMUL2.clone() * (time / &orbit.orbit_period).fract()
+                    + orbit.orbit_phase.to_radians();
+                let mean_motion = PIMUL2.clone() / orbit.orbit_period.to_seconds();
+                (pole, reference, anomaly, mean_motion)
+            }
+            BodyDynamics::Conic(conic) if conic.eccentricity < DBig::ONE => {
+                let periapsis = conic.state_at(&conic.periapsis_time);
+                let pole = periapsis.position.cross(periapsis.velocity).normalized();
+                let a = conic.periapsis_distance.to_meters() / (DBig::ONE - &conic.eccentricity);
+                let gm = precise(conic.gravitational_parameter.clone());
+                let mean_motion = (gm / (a.clone() * &a * a)).sqrt();
+                let anomaly = mean_motion.clone() * (time - &conic.periapsis_time).to_seconds();
+                (pole, periapsis.position.normalized(), anomaly, mean_motion)
+            }
+            _ => return None,
+        };
+        Some(match precession {
+            Some(precession) => (
+                precession.apply(time, pole),
+                precession.apply(time, reference),
+                anomaly,
+                mean_motion,
+            ),
+            None => (pole, reference, anomaly, mean_motion),
+        })
+    }
+
+    /// Maps body-fixed coordinates of a body on `dynamics` at `time` into global coordinates.
+    fn orientation_at(
+        &self,
+        dynamics: &BodyDynamics,
+        precession: Option<&SecularPrecession>,
+        time: &Time,
+    ) -> Option<DecimalMatrix3d> {
+        let (pole, reference, anomaly, _) =
+            SpinOrbitResonance::orbit_reference(dynamics, precession, time)?;
+        let turned = DecimalMatrix3d::axis_angle(pole.clone(), self.ratio() * anomaly);
+        let meridian = -turned.apply(reference);
+        let z = meridian.cross(pole.clone());
+        Some(DecimalMatrix3d::from_basis(meridian, pole, z))
+    }
+
+    /// Spin vector of a body on `dynamics` at `time` in radians per second.
+    fn angular_velocity_at(
+        &self,
+        dynamics: &BodyDynamics,
+        precession: Option<&SecularPrecession>,
+        time: &Time,
+    ) -> Option<DecimalVector3d> {
+        let (pole, _, _, mean_motion) =
+            SpinOrbitResonance::orbit_reference(dynamics, precession, time)?;
+        let spin = pole * (self.ratio() * mean_motion);
+        Some(match precession {
+            Some(precession) => spin + precession.angular_velocity_at(time),
+            None => spin,
+        })
+    }
+}
+
+impl RotationModel {
+    /// Tidally locked to the parent, see [`SpinOrbitResonance::SYNCHRONOUS`].
+    pub fn tidally_locked() -> RotationModel {
+        RotationModel::SpinOrbit(SpinOrbitResonance::SYNCHRONOUS)
+    }
+
+    /// Pole of `body` at `time`, `None` if the model does not apply to it.
+    pub fn pole_at(&self, time: &Time, body: &Body) -> Option<DecimalVector3d> {
+        match self {
+            RotationModel::Iau(iau) => Some(iau.axes_at(time).1),
+            RotationModel::PrecessionCone(cone) => Some(cone.pole_at(time)),
+            RotationModel::SpinOrbit(_) => {
+                SpinOrbitResonance::orbit_reference(&body.dynamics, None, time)
+                    .map(|(pole, ..)| pole)
+            }
+            RotationModel::RigidBody(attitude) => {
+                let orientation = attitude.state_at(time).orientation;
+                Some(orientation.apply(DecimalVector3d::new(DBig::ZERO, DBig::ONE, DBig::ZERO)))
+            }
+        }
+    }
+
+    /// Maps body-fixed coordinates of `body` at `time` into global coordinates, spinning at its
+    /// rotation period where the model leaves the spin to the body. `None` if the model does
+    /// not apply to it.
+    pub fn orientation_at(&self, time: &Time, body: &Body) -> Option<DecimalMatrix3d> {
+        let (meridian, pole) = match self {
+            RotationModel::Iau(iau) => {
+                let (meridian, pole, _) = iau.axes_at(time);
+                (meridian, pole)
+            }
+            RotationModel::PrecessionCone(cone) => {
+                let pole = cone.pole_at(time);
+                let mut node = cone.axis.cross(pole.clone());
+                if node.length_squared() == DBig::ZERO {
+                    node = pole.cross(DecimalVector3d::new(DBig::ZERO, DBig::ZERO, DBig::ONE));
+                }
+                let spin = body.rotation_angle(time).to_radians();
+                let meridian =
+                    DecimalMatrix3d::axis_angle(pole.clone(), spin).apply(node.normalized());
+                (meridian, pole)
+            }
+            RotationModel::SpinOrbit(resonance) => {
+                return resonance.orientation_at(&body.dynamics, None, time);
+            }
+            RotationModel::RigidBody(attitude) => return Some(attitude.state_at(time).orientation),
+        };
+        let z = meridian.cross(pole.clone());
+        Some(DecimalMatrix3d::from_basis(meridian, pole, z))
+    }
+
+    /// Spin vector of `body` in radians per second at `time`, `None` if the model does not
+    /// apply to it. For IAU models only the spin around the pole counts, the pole itself moves
+    /// too slowly to matter.
+    pub fn angular_velocity_at(&self, time: &Time, body: &Body) -> Option<DecimalVector3d> {
+        match self {
+            RotationModel::Iau(iau) => {
+                let (_, pole, spin) = iau.axes_at(time);
+                Some(pole * spin)
+            }
+            RotationModel::PrecessionCone(cone) => {
+                let spin = body.angular_velocity().length();
+                Some(cone.pole_at(time) * spin + cone.axis.normalized() * cone.rate())
+            }
+            RotationModel::SpinOrbit(resonance) => {
+                resonance.angular_velocity_at(&body.dynamics, None, time)
+            }
+            RotationModel::RigidBody(attitude) => {
+                Some(attitude.angular_velocity(&attitude.state_at(time)))
+            }
+        }
+    }
+}
+
+impl Simulation {
+    /// `time` as the seconds since J2000.0 TDB that [`IauRotation`] models count, shifted by
+    /// [`Simulation::epoch`].
+    fn iau_time(&self, time: &Time) -> Time {
+        Time::from_seconds(self.epoch_at(time).seconds_since_j2000(TimeScale::Tdb))
+    }
+
+    /// Maps body-fixed coordinates of body `id` at `time` into global coordinates like
+    /// [`Body::orientation_at`], with IAU models counting from the simulation's epoch, a
+    /// spin-orbit lock following the orbit as [`Simulation::set_secular_precession`] turns it
+    /// and a tumbling body stepped from its kept state, see [`crate::attitude`].
+    pub(crate) fn body_orientation_at(&self, id: i32, time: &Time) -> Option<DecimalMatrix3d> {
+        let body = self.get_body_by_id(id)?.body();
+        match &body.rotation_model {
+            Some(RotationModel::Iau(_)) => return Some(body.orientation_at(&self.iau_time(time))),
+            Some(RotationModel::RigidBody(attitude)) => {
+                return Some(self.attitude_at(id, attitude, time).orientation)
+            }
+            Some(RotationModel::SpinOrbit(resonance)) => {
+                let precession = self.precessions.get(&id);
+                if let Some(orientation) =
+                    resonance.orientation_at(&body.dynamics, precession, time)
+                {
+                    return Some(orientation);
+                }
+            }
+            _ => {}
+        }
+        Some(body.orientation_at(time))
+    }
+
+    /// Direction of the pole of body `id` at `time`, see [`Simulation::body_orientation_at`].
+    pub(crate) fn body_pole_at(&self, id: i32, time: &Time) -> Option<DecimalVector3d> {
+        let orientation = self.body_orientation_at(id, time)?;
+        Some(orientation.apply(DecimalVector3d::new(DBig::ZERO, DBig::ONE, DBig::ZERO)))
+    }
+
+    /// Spin vector of body `id` at `time` in radians per second like
+    /// [`Body::angular_velocity_at`], see [`Simulation::body_orientation_at`].
+    pub(crate) fn body_angular_velocity_at(&self, id: i32, time: &Time) -> Option<DecimalVector3d> {
+        let body = self.get_body_by_id(id)?.body();
+        match &body.rotation_model {
+            Some(RotationModel::Iau(_)) => {
+                return Some(body.angular_velocity_at(&self.iau_time(time)))
+            }
+            Some(RotationModel::RigidBody(attitude)) => {
+                let state = self.attitude_at(id, attitude, time);
+                return Some(attitude.angular_velocity(&state));
+            }
+            Some(RotationModel::SpinOrbit(resonance)) => {
+                let precession = self.precessions.get(&id);
+                if let Some(spin) = resonance.angular_velocity_at(&body.dynamics, precession, time)
+                {
+                    return Some(spin);
+                }
+            }
+            _ => {}
+        }
+        Some(body.angular_velocity_at(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{OrbitingBodyDynamics, StaticBodyDynamics};
+    use crate::conic::ConicDynamics;
+    use crate::simulation::Simulation;
+    use crate::sin_cos::{dbig_to_f64, f64_to_dbig};
+    use crate::time::Epoch;
+    use crate::units::{Length, Mass};
+
+    fn planet(rotation_period: Time, rotation_model: RotationModel) -> Body {
+        Body {
+            rotation_period,
+            rotation_model: Some(rotation_model),
+            ..Body::new(
+                "Earth",
+                Mass::from_kilograms(f64_to_dbig(5.972e24)),
+                Length::from_meters(f64_to_dbig(6.371e6)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            )
+        }
+    }
+
+    fn assert_close(a: &DecimalVector3d, b: &DecimalVector3d, tolerance: f64) {
+        let distance = dbig_to_f64(&a.distance_to(b.clone()));
+        assert!(distance < tolerance, "{a} vs {b}");
+    }
+
+    #[test]
+    fn iau_earth_model() {
+        let earth = RotationModel::Iau(IauRotation {
+            right_ascension: vec![DBig::ZERO, f64_to_dbig(-0.641)],
+            declination: vec![DBig::from(90), f64_to_dbig(-0.557)],
+            prime_meridian: vec![f64_to_dbig(190.147), f64_to_dbig(360.9856235)],
+            right_ascension_terms: vec![],
+            declination_terms: vec![],
+            prime_meridian_terms: vec![],
+        });
+        let mut sim = Simulation::new();
+        let id = sim.add_hierarchy(planet(Time::zero(), earth), None);
+        sim.update(Time::zero());
+
+        // at J2000 the pole is the ICRF pole and the meridian 190.147 degrees past the node,
+        // up to the tens of microseconds J2000.0 TT and 0 TDB lie apart
+        let orientation = sim.get_body_by_id(id).unwrap().orientation().clone();
+        let pole = orientation.apply(DecimalVector3d::from_f64(0.0, 1.0, 0.0));
+        assert_close(&pole, &DecimalVector3d::from_f64(0.0, 1.0, 0.0), 1e-15);
+        let meridian = orientation.apply(DecimalVector3d::from_f64(1.0, 0.0, 0.0));
+        let offset = dbig_to_f64(&Epoch::j2000().seconds_since_j2000(TimeScale::Tdb)) / 86400.0;
+        let w = (190.147f64 + 360.9856235 * offset).to_radians();
+        assert_close(
+            &meridian,
+            &DecimalVector3d::from_f64(-w.sin(), 0.0, -w.cos()),
+            1e-12,
+        );
+
+        // a century later the pole has wandered by the polynomial rates
+        sim.update(Time::from_days(f64_to_dbig(36525.0)));
+        let body = sim.get_body_by_id(id).unwrap();
+        let pole = body
+            .orientation()
+            .apply(DecimalVector3d::from_f64(0.0, 1.0, 0.0));
+        let (x, y, z) = (
+            dbig_to_f64(&pole.x),
+            dbig_to_f64(&pole.y),
+            dbig_to_f64(&pole.z),
+        );
+        let tilt = x.hypot(z).atan2(y).to_degrees();
+        assert!((tilt - 0.557).abs() < 1e-9);
+        let spin = dbig_to_f64(&body.body().angular_velocity_at(sim.time()).length());
+        assert!((spin - 360.9856235f64.to_radians() / 86400.0).abs() < 1e-15);
+
+        // a simulation starting a quarter day after J2000 starts where the first one is then
+        let quarter = Time::from_days(f64_to_dbig(0.25));
+        let mut later = sim.clone();
+        later.set_epoch(sim.epoch_at(&quarter));
+        later.update(Time::zero());
+        sim.update(quarter);
+        let x = DecimalVector3d::from_f64(1.0, 0.0, 0.0);
+        let meridian = |sim: &Simulation| {
+            sim.get_body_by_id(id)
+                .unwrap()
+                .orientation()
+                .apply(x.clone())
+        };
+        assert_close(&meridian(&later), &meridian(&sim), 1e-15);
+    }
+
+    #[test]
+    fn axial_precession_cone() {
+        let cone = RotationModel::PrecessionCone(PrecessionCone {
+            axis: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+            obliquity: Angle::from_degrees(f64_to_dbig(23.44)),
+            period: Time::from_julian_years(DBig::from(-25772)),
+            phase: Angle::zero(),
+        });
+        let mut sim = Simulation::new();
+        let id = sim.add_hierarchy(
+            planet(Time::from_seconds(f64_to_dbig(86164.0905)), cone),
+            None,
+        );
+        sim.update(Time::zero());
+        let pole_now = sim.get_body_by_id(id).unwrap().body().pole_at(sim.time());
+        sim.update(Time::from_julian_years(DBig::from(25772 / 2)));
+        let body = sim.get_body_by_id(id).unwrap();
+        let pole_later = body.body().pole_at(sim.time());
+
+        // half a cycle later the pole points 2 * 23.44 degrees away, on the same cone
+        let swing = dbig_to_f64(&pole_now.dot(pole_later.clone()))
+            .acos()
+            .to_degrees();
+        assert!((swing - 46.88).abs() < 1e-9);
+        assert!((dbig_to_f64(&pole_later.y).acos().to_degrees() - 23.44).abs() < 1e-9);
+        let pole = body
+            .orientation()
+            .apply(DecimalVector3d::from_f64(0.0, 1.0, 0.0));
+        assert_close(&pole, &pole_later, 1e-20);
+    }
+
+    fn locked(eccentricity: f64, resonance: SpinOrbitResonance) -> (Body, ConicDynamics) {
+        let a = 3.844e8;
+        let conic = ConicDynamics {
+            periapsis_distance: Length::from_meters(f64_to_dbig(a * (1.0 - eccentricity))),
+            eccentricity: f64_to_dbig(eccentricity),
+            inclination: Angle::from_degrees(f64_to_dbig(5.0)),
+            longitude_of_ascending_node: Angle::from_degrees(DBig::from(30)),
+            argument_of_periapsis: Angle::from_degrees(DBig::from(60)),
+            periapsis_time: Time::from_days(DBig::from(3)),
+            gravitational_parameter: f64_to_dbig(4.035e14),
+        };
+        let moon = Body {
+            name: "Moon".to_string(),
+            rotation_period: Time::zero(),
+            rotation_model: Some(RotationModel::SpinOrbit(resonance)),
+            dynamics: BodyDynamics::Conic(conic.clone()),
+            ..planet(Time::zero(), RotationModel::tidally_locked())
+        };
+        (moon, conic)
+    }
+
+    /// Angle in degrees between the prime meridian of `body` and the direction to its parent.
+    fn off_parent(body: &Body, conic: &ConicDynamics, time: &Time) -> f64 {
+        let meridian = body
+            .orientation_at(time)
+            .apply(DecimalVector3d::from_f64(1.0, 0.0, 0.0));
+        let to_parent = -conic.position_at(time).normalized();
+        dbig_to_f64(&meridian.dot(to_parent))
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees()
+    }
+
+    #[test]
+    fn tidally_locked_moon_librates() {
+        let (moon, conic) = locked(0.0549, SpinOrbitResonance::SYNCHRONOUS);
+        let period = 2.0 * std::f64::consts::PI * (3.844e8f64.powi(3) / 4.035e14).sqrt();
+        let at = |fraction: f64| {
+            conic.periapsis_time.clone() + Time::from_seconds(f64_to_dbig(period * fraction))
+        };
+        // facing the parent at periapsis and apoapsis, swinging by about 2e radians between
+        assert!(off_parent(&moon, &conic, &at(0.0)) < 1e-6);
+        assert!(off_parent(&moon, &conic, &at(0.5)) < 1e-6);
+        let libration = (0..40)
+            .map(|step| off_parent(&moon, &conic, &at(step as f64 / 40.0)))
+            .fold(0.0, f64::max);
+        assert!((libration - 2.0 * 0.0549f64.to_degrees()).abs() < 0.3);
+
+        let pole = moon.pole_at(&at(0.3));
+        let state = conic.state_at(&at(0.3));
+        let normal = state.position.cross(state.velocity).normalized();
+        assert_close(&pole, &normal, 1e-12);
+    }
+
+    #[test]
+    fn locked_moon_follows_its_precessing_orbit() {
+        // the moon of the demo, its node turning a radian in about two orbits
+        let normal = DecimalVector3d::from_f64(0.0, 1.0, 0.1).normalized();
+        let moon = Body {
+            rotation_period: Time::zero(),
+            dynamics: BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                orbit_radius: Length::from_kilometers(DBig::from(384400)),
+                orbit_period: Time::from_days(DBig::from(27)),
+                orbit_phase: Angle::zero(),
+                orbit_plane_normal: normal.clone(),
+            }),
+            ..planet(Time::zero(), RotationModel::tidally_locked())
+        };
+        let mut sim = Simulation::new();
+        let earth = sim.add_hierarchy(
+            Body {
+                satellites: vec![moon],
+                ..planet(Time::from_days(DBig::ONE), RotationModel::tidally_locked())
+            },
+            None,
+        );
+        let moon = earth + 1;
+        let precession = SecularPrecession {
+            epoch: Time::zero(),
+            pole: normal.clone(),
+            nodal: DecimalVector3d::from_f64(0.0, 2e-7, 0.0),
+            apsidal_rate: f64_to_dbig(1e-7),
+        };
+        sim.set_secular_precession(moon, Some(precession.clone()));
+        sim.update(Time::from_days(f64_to_dbig(10.3)));
+
+        let state = sim.relative_state_at(sim.time(), moon);
+        let orientation = sim.get_body_by_id(moon).unwrap().orientation().clone();
+        let meridian = orientation.apply(DecimalVector3d::from_f64(1.0, 0.0, 0.0));
+        assert_close(&meridian, &-state.position.normalized(), 1e-12);
+        // the pole stands on the turned orbit plane, the moon moving through it
+        let pole = orientation.apply(DecimalVector3d::from_f64(0.0, 1.0, 0.0));
+        assert_close(&pole, &precession.apply(sim.time(), normal), 1e-12);
+        assert!(dbig_to_f64(&pole.dot(state.position.normalized())).abs() < 1e-12);
+    }
+
+    #[test]
+    fn three_to_two_resonance() {
+        assert!(SpinOrbitResonance::new(3, 0).is_none());
+        let resonance = SpinOrbitResonance::new(3, 2).unwrap();
+        let (planet, conic) = locked(0.2056, resonance);
+        let period = 2.0 * std::f64::consts::PI * (3.844e8f64.powi(3) / 4.035e14).sqrt();
+        let after = conic.periapsis_time.clone() + Time::from_seconds(f64_to_dbig(period));
+        // one and a half turns per orbit, so the other face points at the parent next periapsis
+        assert!(off_parent(&planet, &conic, &conic.periapsis_time) < 1e-6);
+        assert!((off_parent(&planet, &conic, &after) - 180.0).abs() < 1e-6);
+        let spin = dbig_to_f64(&planet.angular_velocity_at(&after).length());
+        assert!((spin * period / std::f64::consts::TAU - 1.5).abs() < 1e-9);
+    }
+}
@@ -1,9 +1,16 @@
 use std::collections::BTreeMap;
 
+use crate::attitude::AttitudeState;
 use crate::body::{Body, BodyDynamics};
+use crate::constants::Constants;
 use crate::decimal_matrix_3d::DecimalMatrix3d;
 use crate::decimal_vector_3d::DecimalVector3d;
-use crate::sin_cos::PIMUL2;
+use crate::maneuver::ManeuverPlan;
+use crate::playback::PlaybackError;
+use crate::precession::SecularPrecession;
+use crate::sin_cos::{f64_to_dbig, PIMUL2};
+use crate::time::Epoch;
+use crate::units::{Length, Time, Velocity};
 use dashu_float::DBig;
 
 #[derive(Debug, Clone)]
@@ -16,11 +23,47 @@ pub struct SimulatedBody {
     parent: Option<i32>, // -1 means no
 }
 
-#[derive(Debug)]
+impl SimulatedBody {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    pub fn position(&self) -> &DecimalVector3d {
+        &self.position
+    }
+
+    pub fn velocity(&self) -> &DecimalVector3d {
+        &self.velocity
+    }
+
+    /// Maps body-fixed coordinates into global coordinates, see [`Body::orientation_at`].
+    pub fn orientation(&self) -> &DecimalMatrix3d {
+        &self.orientation
+    }
+
+    pub fn parent(&self) -> Option<i32> {
+        self.parent
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Simulation {
     pub bodies: Vec<SimulatedBody>,
     id_counter: i32,
     bodies_tree: BTreeMap<Key, u32>,
+    time: Time,
+    epoch: Epoch,
+    constants: Constants,
+    /// Maneuvers of crafts by craft id, see [`crate::maneuver`].
+    pub(crate) maneuver_plans: BTreeMap<i32, ManeuverPlan>,
+    /// Secular drift of orbits by body id, see [`crate::precession`].
+    pub(crate) precessions: BTreeMap<i32, SecularPrecession>,
+    /// Latest rotation state of tumbling bodies by body id, see [`crate::attitude`].
+    pub(crate) attitudes: BTreeMap<i32, AttitudeState>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -35,12 +78,24 @@ enum Key {
     Parent { id: i32 },
 }
 
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Simulation {
     pub fn new() -> Self {
         Simulation {
             bodies: vec![],
             id_counter: 0,
             bodies_tree: BTreeMap::new(),
+            time: Time::zero(),
+            epoch: Epoch::j2000(),
+            constants: Constants::default(),
+            maneuver_plans: BTreeMap::new(),
+            precessions: BTreeMap::new(),
+            attitudes: BTreeMap::new(),
         }
     }
 
@@ -65,19 +120,12 @@ impl Simulation {
                 .insert(Key::Parent { id: new_id }, parent as u32);
         }
 
-        if let BodyDynamics::Static(_) = &body.dynamics {
+        if parent.is_none() {
             self.bodies_tree
                 .insert(Key::StaticBody { id: new_id }, new_id as u32);
         }
         for satellite in body.satellites.clone() {
-            let satellite_id = self.add_body(satellite, Some(new_id));
-            self.bodies_tree.insert(
-                Key::Satellite {
-                    parent: new_id,
-                    child: satellite_id,
-                },
-                new_id as u32,
-            );
+            self.add_body(satellite, Some(new_id));
         }
 
         let simulated_body = SimulatedBody {
@@ -100,7 +148,54 @@ impl Simulation {
         self.add_body(body, parent)
     }
 
-    fn get_body_by_id(&self, id: i32) -> Option<&SimulatedBody> {
+    /// Moves body `id`, which has a parent, with everything below it under `parent`.
+    pub(crate) fn reparent(&mut self, id: i32, parent: i32) {
+        if let Some(old) = self.bodies_tree.insert(Key::Parent { id }, parent as u32) {
+            self.bodies_tree.remove(&Key::Satellite {
+                parent: old as i32,
+                child: id,
+            });
+        }
+        self.bodies_tree
+            .insert(Key::Satellite { parent, child: id }, id as u32);
+        if let Some(body) = self.get_mut_body_by_id(id) {
+            body.parent = Some(parent);
+        }
+    }
+
+    /// Replaces the dynamics of body `id`.
+    pub(crate) fn set_dynamics(&mut self, id: i32, dynamics: BodyDynamics) {
+        if let Some(body) = self.get_mut_body_by_id(id) {
+            body.body.dynamics = dynamics;
+        }
+    }
+
+    /// Time of the last [`Simulation::update`], TDB since [`Simulation::epoch`].
+    pub fn time(&self) -> &Time {
+        &self.time
+    }
+
+    /// Instant simulation time 0 stands for, J2000.0 unless set otherwise.
+    pub fn epoch(&self) -> &Epoch {
+        &self.epoch
+    }
+
+    pub fn set_epoch(&mut self, epoch: Epoch) {
+        self.epoch = epoch;
+    }
+
+    /// Constants gravity uses, the IAU 2012 set unless set otherwise. Convert astronomical units
+    /// with [`Constants::length_from_au`] and read Horizons tables with
+    /// [`HorizonsTable::parse_with`](crate::horizons::HorizonsTable::parse_with) to follow it.
+    pub fn constants(&self) -> &Constants {
+        &self.constants
+    }
+
+    pub fn set_constants(&mut self, constants: Constants) {
+        self.constants = constants;
+    }
+
+    pub fn get_body_by_id(&self, id: i32) -> Option<&SimulatedBody> {
         self.bodies_tree
             .get(&Key::Body { id })
             .map(|&idx| &self.bodies[idx as usize])
@@ -112,6 +207,13 @@ impl Simulation {
             .map(|&idx| &mut self.bodies[idx as usize])
     }
 
+    /// Id of the static body at the top of the hierarchy `id` belongs to.
+    pub fn get_root_id(&self, id: i32) -> i32 {
+        let mut parents = Vec::new();
+        self.get_all_parents_into(id, &mut parents);
+        parents.last().copied().unwrap_or(id)
+    }
+
     fn get_all_parents_into(&self, id: i32, buf: &mut Vec<i32>) {
         if let Some(&parent) = self.bodies_tree.get(&Key::Parent { id }) {
             let parent = parent as i32;
@@ -139,7 +241,7 @@ impl Simulation {
         }
     }
 
-    fn resolve_hierarchy_up(&self, body: &SimulatedBody) -> Vec<&SimulatedBody> {
+    pub fn resolve_hierarchy_up(&self, body: &SimulatedBody) -> Vec<&SimulatedBody> {
         /* how this will look like for example for the moon,
          moon gets into this function, we don't want to add it
          its parent is earth, it gets found, is added to the moon-result
@@ -158,7 +260,7 @@ impl Simulation {
             .collect()
     }
 
-    fn resolve_hierarchy_down(&self, body: &SimulatedBody) -> Vec<&SimulatedBody> {
+    pub fn resolve_hierarchy_down(&self, body: &SimulatedBody) -> Vec<&SimulatedBody> {
         /* how this will look like for example for the sun,
         sun gets into this function, its satellites are iterated, lets simplify to Venus, Earth, and Mars
         to sun result first added is [Venus]
@@ -176,45 +278,170 @@ impl Simulation {
             .collect()
     }
 
-    fn get_body_position(&self, time: DBig, body_id: i32) -> DecimalVector3d {
+    fn get_body_position(&self, time: &Time, body_id: i32) -> DecimalVector3d {
+        let body = self.get_body_by_id(body_id).unwrap();
+        let relative_position = self.get_relative_position(time, body_id);
+        match (&body.body.dynamics, body.parent) {
+            (BodyDynamics::Static(_), _) | (_, None) => relative_position,
+            // SAFETY: parents are added before their satellites and updated before them.
+            (_, Some(parent)) => relative_position + &self.get_body_by_id(parent).unwrap().position,
+        }
+    }
+
+    /// Global position of body `id` at `time` from its dynamics and those of its parents, without
+    /// moving any body. `None` outside the coverage of an ephemeris on the way.
+    pub(crate) fn global_position_at(&self, time: &Time, id: i32) -> Option<DecimalVector3d> {
+        let body = self.get_body_by_id(id)?;
+        if let BodyDynamics::Ephemeris(ephemeris) = &body.body.dynamics {
+            if !ephemeris.segments.covers(&self.ephemeris_time(time)) {
+                return None;
+            }
+        }
+        let relative_position = self.get_relative_position(time, id);
+        match (&body.body.dynamics, body.parent) {
+            (BodyDynamics::Static(_), _) | (_, None) => Some(relative_position),
+            (_, Some(parent)) => Some(relative_position + self.global_position_at(time, parent)?),
+        }
+    }
+
+    /// Position of body `body_id` at `time` relative to its parent, or the global origin for
+    /// roots and static bodies, independent of where the parent currently is.
+    pub(crate) fn get_relative_position(&self, time: &Time, body_id: i32) -> DecimalVector3d {
         let body = self.get_body_by_id(body_id).unwrap();
         match &body.body.dynamics {
             BodyDynamics::Static(static_dynamics) => static_dynamics.position.clone(),
+            BodyDynamics::Linear(linear) => {
+                linear.position.clone() + linear.velocity.clone() * time.to_seconds()
+            }
+            BodyDynamics::Galactic(galactic) => galactic.position_at(time),
             BodyDynamics::Orbiting(orbiting) => {
-                // SAFETY: we know that this body has a parent, and that the parent is in this tree.
-                let parent = self.get_body_by_id(body.parent.unwrap()).unwrap();
-                let parent_position = &parent.position;
                 let orbit_progression = (time / &orbiting.orbit_period).fract();
-                let angle = PIMUL2.clone() * orbit_progression;
+                let angle = PIMUL2.clone() * orbit_progression + orbiting.orbit_phase.to_radians();
                 let rotation_matrix =
                     DecimalMatrix3d::axis_angle(orbiting.orbit_plane_normal.clone(), angle);
-                rotation_matrix.apply(DecimalVector3d::new(
-                    orbiting.orbit_radius.clone(),
-                    DBig::ZERO,
-                    DBig::ZERO,
-                )) + parent_position
+                self.precessed(
+                    body_id,
+                    time,
+                    rotation_matrix.apply(DecimalVector3d::new(
+                        orbiting.orbit_radius.to_meters(),
+                        DBig::ZERO,
+                        DBig::ZERO,
+                    )),
+                )
+            }
+            BodyDynamics::Conic(conic) => self.precessed(body_id, time, conic.position_at(time)),
+            BodyDynamics::Craft(conic) => conic.position_at(time),
+            BodyDynamics::Ephemeris(ephemeris) => {
+                // SAFETY: `try_update` checks the coverage before moving any body.
+                let (position, _) = ephemeris
+                    .segments
+                    .evaluate(&self.ephemeris_time(time))
+                    .unwrap();
+                position
             }
         }
     }
 
-    pub fn update(&mut self, time: DBig) {
-        let mut schedule: Vec<i32> = vec![];
+    /// Moves every body to `time`.
+    ///
+    /// # Panics
+    ///
+    /// If a body played back from an ephemeris has no coverage at `time`, see
+    /// [`Simulation::try_update`].
+    pub fn update(&mut self, time: Time) {
+        self.try_update(time).unwrap();
+    }
 
+    /// Moves every body to `time`, fails without changing anything if a body played back from
+    /// an ephemeris has no coverage at `time` or a second before it.
+    pub fn try_update(&mut self, time: Time) -> Result<(), PlaybackError> {
+        let second = Time::from_seconds(DBig::ONE);
+        let second_ago = &time - &second;
+        for body in &self.bodies {
+            if let BodyDynamics::Ephemeris(ephemeris) = &body.body.dynamics {
+                let covers = |time| ephemeris.segments.covers(&self.ephemeris_time(time));
+                if !covers(&time) || !covers(&second_ago) {
+                    return Err(PlaybackError::OutOfCoverage { id: body.id, time });
+                }
+            }
+        }
+
+        // crafts crossing a sphere of influence change parent, so the schedule follows them
+        self.advance_crafts(&time);
+        let mut schedule: Vec<i32> = vec![];
         for (_, &id) in self
             .bodies_tree
             .range(Key::StaticBody { id: i32::MIN }..=Key::StaticBody { id: i32::MAX })
         {
+            schedule.push(id as i32);
             self.get_all_children_into(id as i32, &mut schedule);
         }
 
         for body_id in schedule {
-            let position = self.get_body_position(time.clone(), body_id);
-            let pos_second_ago = self.get_body_position(time.clone() - DBig::ONE, body_id);
-            let velocity = position.clone() - pos_second_ago;
+            let position = self.get_body_position(&time, body_id);
+            let pos_second_ago = self.get_body_position(&second_ago, body_id);
+            // both samples are taken against the parent's current position, so this is the
+            // velocity relative to the parent
+            let mut velocity = position.clone() - pos_second_ago;
+            if let Some(parent) = self.get_body_by_id(body_id).unwrap().parent {
+                velocity += &self.get_body_by_id(parent).unwrap().velocity;
+            }
 
+            let orientation = self.advance_orientation(body_id, &time);
             let body = self.get_mut_body_by_id(body_id).unwrap();
+            body.orientation = orientation;
             body.position = position;
             body.velocity = velocity;
         }
+        self.time = time;
+        Ok(())
+    }
+
+    /// Copy of this simulation updated to `time`, for looking at other moments without losing
+    /// the current state. `None` where [`Simulation::try_update`] fails.
+    pub fn at(&self, time: Time) -> Option<Simulation> {
+        let mut simulation = self.clone();
+        simulation.try_update(time).ok()?;
+        Some(simulation)
+    }
+
+    /// Current distance between bodies `a` and `b`.
+    pub fn get_distance(&self, a: i32, b: i32) -> Option<Length> {
+        let a = self.get_body_by_id(a)?;
+        let b = self.get_body_by_id(b)?;
+        Some(Length::from_meters(
+            a.position.distance_to(b.position.clone()),
+        ))
+    }
+
+    /// Rate the distance between bodies `a` and `b` changes at, positive while they move apart.
+    /// `None` while they are at the same position.
+    pub fn get_range_rate(&self, a: i32, b: i32) -> Option<Velocity> {
+        let a = self.get_body_by_id(a)?;
+        let b = self.get_body_by_id(b)?;
+        let offset = &a.position - &b.position;
+        let distance = offset.length();
+        if distance == DBig::ZERO {
+            return None;
+        }
+        Some(Velocity::from_meters_per_second(
+            offset.dot(&a.velocity - &b.velocity) / distance,
+        ))
+    }
+
+    /// Range rate of body `id` relative to its parent, positive between periapsis and apoapsis.
+    pub fn get_radial_velocity(&self, id: i32) -> Option<Velocity> {
+        let parent = self.get_body_by_id(id)?.parent?;
+        self.get_range_rate(id, parent)
+    }
+
+    /// Laplace sphere of influence radius of body `id` around its parent, `a * (m / M)^(2/5)`
+    /// with `a` the current distance to the parent and `M` its [`Simulation::central_mass`].
+    /// `None` for root bodies.
+    pub fn sphere_of_influence(&self, id: i32) -> Option<Length> {
+        let body = self.get_body_by_id(id)?;
+        let parent = self.get_body_by_id(body.parent?)?;
+        let mass_ratio = &body.body.mass / &self.central_mass(parent.id)?;
+        Some(self.get_distance(id, parent.id)? * mass_ratio.powf(&f64_to_dbig(0.4)))
     }
 }
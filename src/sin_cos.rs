@@ -1,9 +1,12 @@
-use dashu_float::ops::Abs;
+use dashu_float::ops::{Abs, SquareRoot};
 use dashu_float::DBig;
 use std::str::FromStr;
 use std::sync::LazyLock;
 
-static PI: LazyLock<DBig> = LazyLock::new(|| {
+/// Significant digits carried by values entering the simulation from f64 or integer literals.
+pub const PRECISION: usize = 40;
+
+pub static PI: LazyLock<DBig> = LazyLock::new(|| {
     DBig::from_str("3.141592653589793238462643383279502884197169399375105820974944592307816406286")
         .unwrap()
 });
@@ -14,7 +17,7 @@ pub static PIMUL2: LazyLock<DBig> = LazyLock::new(|| {
         * DBig::from(2)
 });
 
-static PIDIV2: LazyLock<DBig> = LazyLock::new(|| {
+pub static PIDIV2: LazyLock<DBig> = LazyLock::new(|| {
     DBig::from_str("3.141592653589793238462643383279502884197169399375105820974944592307816406286")
         .unwrap()
         / DBig::from(2)
@@ -44,12 +47,107 @@ pub fn cos(x: DBig, precision: i64) -> DBig {
     sin(x + PIDIV2.clone(), precision)
 }
 
+pub fn atan(x: DBig, precision: i64) -> DBig {
+    if x < DBig::ZERO {
+        return -atan(-x, precision);
+    }
+    let x = precise(x);
+    if x > DBig::ONE {
+        return PIDIV2.clone() - atan(DBig::ONE / x, precision);
+    }
+    // atan(x) = 2 * atan(x / (1 + sqrt(1 + x^2))), halving twice keeps the series short
+    let mut reduced = x;
+    for _ in 0..2 {
+        reduced = reduced.clone() / (DBig::ONE + (DBig::ONE + reduced.clone() * &reduced).sqrt());
+    }
+    let mut term = reduced.clone();
+    let mut result = reduced.clone();
+    let mut n = 1;
+    let x_sq = reduced.clone() * reduced;
+
+    let limit = DBIGTEN.powf(&DBig::from(-precision));
+
+    while term.clone().abs() > limit {
+        term = -term * x_sq.clone();
+        result += term.clone() / DBig::from(2 * n + 1);
+        n += 1;
+    }
+
+    result * DBig::from(4)
+}
+
+/// Angle of the point (x, y) from the positive x axis, in (-pi, pi].
+pub fn atan2(y: DBig, x: DBig, precision: i64) -> DBig {
+    if x > DBig::ZERO {
+        atan(y / x, precision)
+    } else if x < DBig::ZERO {
+        if y < DBig::ZERO {
+            atan(y / x, precision) - PI.clone()
+        } else {
+            atan(y / x, precision) + PI.clone()
+        }
+    } else if y > DBig::ZERO {
+        PIDIV2.clone()
+    } else if y < DBig::ZERO {
+        -PIDIV2.clone()
+    } else {
+        DBig::ZERO
+    }
+}
+
+pub fn asin(x: DBig, precision: i64) -> DBig {
+    let x = precise(x);
+    let cos = precise((DBig::ONE - x.clone() * &x).max(DBig::ZERO)).sqrt();
+    atan2(x, cos, precision)
+}
+
+pub fn acos(x: DBig, precision: i64) -> DBig {
+    let x = precise(x);
+    let sin = precise((DBig::ONE - x.clone() * &x).max(DBig::ZERO)).sqrt();
+    atan2(sin, x, precision)
+}
+
+pub fn sinh(x: DBig) -> DBig {
+    let x = precise(x);
+    (x.exp_m1() - (-x).exp_m1()) / DBig::from(2)
+}
+
+pub fn cosh(x: DBig) -> DBig {
+    let x = precise(x);
+    (x.exp() + (-x).exp()) / DBig::from(2)
+}
+
+/// `ln(x + sqrt(x^2 + 1))`.
+pub fn asinh(x: DBig) -> DBig {
+    if x < DBig::ZERO {
+        return -asinh(-x);
+    }
+    let x = precise(x);
+    (x.clone() + (x.clone() * &x + DBig::ONE).sqrt()).ln()
+}
+
+/// `ln((1 + x) / (1 - x)) / 2`, for `|x| < 1`.
+pub fn atanh(x: DBig) -> DBig {
+    let x = precise(x);
+    ((DBig::ONE + &x) / (DBig::ONE - x)).ln() / DBig::from(2)
+}
+
 pub fn dbig_to_f64(v: &DBig) -> f64 {
     f64::from_str(v.to_string().as_str()).unwrap()
 }
 
 pub fn f64_to_dbig(v: f64) -> DBig {
-    DBig::from_str(v.to_string().as_str()).unwrap()
+    precise(DBig::from_str(v.to_string().as_str()).unwrap())
+}
+
+/// Raises `v` to the working [`PRECISION`], a literal like `DBig::from(3)` only carries one digit
+/// otherwise and every quotient derived from it gets rounded to that.
+pub fn precise(v: DBig) -> DBig {
+    if v.precision() >= PRECISION {
+        v
+    } else {
+        v.with_precision(PRECISION).value()
+    }
 }
 
 #[cfg(test)]
@@ -87,4 +185,40 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn atan2_works() {
+        for i in -10..10 {
+            for f in -10..10 {
+                let (y, x) = (i as f64 / 3.0, f as f64 / 7.0);
+                let atan_dec = atan2(f64_to_dbig(y), f64_to_dbig(x), 32);
+                assert!((dbig_to_f64(&atan_dec) - y.atan2(x)).abs() < 0.0000000000001);
+            }
+        }
+    }
+
+    #[test]
+    fn asin_acos_work() {
+        for i in -10..=10 {
+            let v = i as f64 / 10.0;
+            let asin_dec = asin(f64_to_dbig(v), 32);
+            let acos_dec = acos(f64_to_dbig(v), 32);
+            assert!((dbig_to_f64(&asin_dec) - v.asin()).abs() < 0.0000000000001);
+            assert!((dbig_to_f64(&acos_dec) - v.acos()).abs() < 0.0000000000001);
+        }
+    }
+
+    #[test]
+    fn hyperbolic_functions_work() {
+        for i in -10..=10 {
+            let v = i as f64 / 2.0;
+            let sinh_dec = dbig_to_f64(&sinh(f64_to_dbig(v)));
+            let cosh_dec = dbig_to_f64(&cosh(f64_to_dbig(v)));
+            assert!((sinh_dec - v.sinh()).abs() < 1e-13 * v.sinh().abs().max(1.0));
+            assert!((cosh_dec / v.cosh() - 1.0).abs() < 1e-13);
+            let t = v / 6.0;
+            assert!((dbig_to_f64(&atanh(f64_to_dbig(t))) - t.atanh()).abs() < 1e-13);
+            assert!((dbig_to_f64(&asinh(f64_to_dbig(v))) - v.asinh()).abs() < 1e-13);
+        }
+    }
 }
@@ -0,0 +1,354 @@
+use crate::body::Body;
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::events::{find_root, DEFAULT_TOLERANCE};
+use crate::reference_frame::{ReferenceFrame, StateVector};
+use crate::simulation::Simulation;
+use crate::sin_cos::{asin, atan2, cos, f64_to_dbig, sin, PI, PIMUL2};
+use crate::surface::GeodeticCoordinates;
+use crate::units::{Angle, Length, Time};
+use dashu_float::DBig;
+
+/// Passes correcting the sub-solar point for the parallax of the star, each gains the digits of
+/// the star's distance in body radii.
+const SUB_SOLAR_ITERATIONS: usize = 4;
+
+/// Direction of the star as seen from a surface point.
+#[derive(Debug, Clone)]
+pub struct HorizontalCoordinates {
+    pub elevation: Angle, // above the horizon
+    pub azimuth: Angle,   // from north towards east, in [0, 2pi)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolarEventKind {
+    Sunrise,
+    Sunset,
+}
+
+#[derive(Debug, Clone)]
+pub struct SolarEvent {
+    pub kind: SolarEventKind,
+    pub time: Time,
+}
+
+/// Local up, east and north unit vectors at `location`, in the body-fixed frame.
+fn local_axes(
+    location: &GeodeticCoordinates,
+) -> (DecimalVector3d, DecimalVector3d, DecimalVector3d) {
+    let (latitude, longitude) = (
+        location.latitude.to_radians(),
+        location.longitude.to_radians(),
+    );
+    let (sin_lat, cos_lat) = (sin(latitude.clone(), 32), cos(latitude, 32));
+    let (sin_lon, cos_lon) = (sin(longitude.clone(), 32), cos(longitude, 32));
+    let up = DecimalVector3d::new(cos_lat.clone() * &cos_lon, sin_lat, -cos_lat * &sin_lon);
+    let east = DecimalVector3d::new(-sin_lon, DBig::ZERO, -cos_lon);
+    let north = up.cross(east.clone());
+    (up, east, north)
+}
+
+/// Elevation and azimuth of `star`, given in the body-fixed frame of `body`, seen from
+/// `location` on it.
+fn horizontal_coordinates(
+    body: &Body,
+    star: &DecimalVector3d,
+    location: &GeodeticCoordinates,
+) -> HorizontalCoordinates {
+    let point = body.geodetic_to_body_fixed(location);
+    let direction = (star.clone() - point).normalized();
+    let (up, east, north) = local_axes(location);
+
+    let mut azimuth = atan2(direction.dot(east), direction.dot(north), 32);
+    if azimuth < DBig::ZERO {
+        azimuth += PIMUL2.clone();
+    }
+    HorizontalCoordinates {
+        elevation: Angle::from_radians(asin(direction.dot(up), 32)),
+        azimuth: Angle::from_radians(azimuth),
+    }
+}
+
+/// Elevation of the upper limb of a star of `star_radius` at `star`, positive while any of the
+/// star is above the horizon.
+fn upper_limb_elevation(
+    body: &Body,
+    star: &DecimalVector3d,
+    star_radius: &Length,
+    location: &GeodeticCoordinates,
+) -> DBig {
+    let distance = star.distance_to(body.geodetic_to_body_fixed(location));
+    let elevation = horizontal_coordinates(body, star, location).elevation;
+    elevation.to_radians() + asin(star_radius.to_meters() / distance, 32)
+}
+
+impl Simulation {
+    /// Position of the star lighting `id`'s hierarchy in `id`'s body-fixed frame, together with
+    /// the star's radius, see [`Simulation::get_star_id`]. `None` for the star itself.
+    fn star_in_body_fixed(&self, id: i32) -> Option<(DecimalVector3d, Length)> {
+        let star_id = self.get_star_id(id);
+        if star_id == id {
+            return None;
+        }
+        let star = self.get_body_by_id(star_id)?;
+        let state = StateVector::new(star.position().clone(), DecimalVector3d::zero());
+        let fixed = self.transform(
+            &state,
+            ReferenceFrame::Global,
+            ReferenceFrame::BodyFixed { id },
+        )?;
+        Some((fixed.position, star.body().radius.clone()))
+    }
+
+    /// [`Simulation::star_in_body_fixed`] at `time`, from the dynamics and orientation of the two
+    /// bodies alone.
+    fn star_in_body_fixed_at(&self, id: i32, time: &Time) -> Option<(DecimalVector3d, Length)> {
+        let star_id = self.get_star_id(id);
+        if star_id == id {
+            return None;
+        }
+        let offset = self.global_position_at(time, star_id)? - self.global_position_at(time, id)?;
+        let orientation = self.body_orientation_at(id, time)?;
+        let star = self.get_body_by_id(star_id)?.body();
+        Some((orientation.transposed().apply(offset), star.radius.clone()))
+    }
+
+    /// Point on body `id` where the star stands at the zenith, along the normal of the reference
+    /// ellipsoid.
+    pub fn sub_solar_point(&self, id: i32) -> Option<GeodeticCoordinates> {
+        let body = self.get_body_by_id(id)?.body();
+        let (star, _) = self.star_in_body_fixed(id)?;
+        // the normal at geodetic latitude `lat` has sin(lat) as its polar component; starting
+        // from the direction seen from the center, each pass shrinks the parallax by the ratio
+        // of the body's radius to the star's distance
+        let mut direction = star.normalized();
+        let mut location = GeodeticCoordinates::new(Angle::zero(), Angle::zero(), Length::zero());
+        for _ in 0..SUB_SOLAR_ITERATIONS {
+            location = GeodeticCoordinates::new(
+                Angle::from_radians(asin(direction.y.clone(), 32)),
+                Angle::from_radians(atan2(-direction.z.clone(), direction.x.clone(), 32)),
+                Length::zero(),
+            );
+            direction = (star.clone() - body.geodetic_to_body_fixed(&location)).normalized();
+        }
+        Some(location)
+    }
+
+    /// Elevation and azimuth of the star's center seen from `location` on body `id`.
+    pub fn solar_position(
+        &self,
+        id: i32,
+        location: &GeodeticCoordinates,
+    ) -> Option<HorizontalCoordinates> {
+        let body = self.get_body_by_id(id)?.body();
+        let (star, _) = self.star_in_body_fixed(id)?;
+        Some(horizontal_coordinates(body, &star, location))
+    }
+
+    /// Whether any part of the star is above the horizon at `location` on body `id`. Only the
+    /// horizon is considered, shadows of other bodies are not.
+    pub fn is_daylight(&self, id: i32, location: &GeodeticCoordinates) -> Option<bool> {
+        let body = self.get_body_by_id(id)?.body();
+        let (star, star_radius) = self.star_in_body_fixed(id)?;
+        Some(upper_limb_elevation(body, &star, &star_radius, location) > DBig::ZERO)
+    }
+
+    /// Local true solar time at `location` on body `id`, in local hours: a solar day is split
+    /// into 24 of them and 12 is when the star crosses the meridian.
+    pub fn local_solar_time(&self, id: i32, location: &GeodeticCoordinates) -> Option<Time> {
+        let sub_solar = self.sub_solar_point(id)?;
+        let hour_angle = (&location.longitude - &sub_solar.longitude).to_radians() + PI.clone();
+        let turns = (hour_angle / PIMUL2.clone()).fract();
+        let turns = if turns < DBig::ZERO {
+            turns + DBig::ONE
+        } else {
+            turns
+        };
+        Some(Time::from_hours(turns * DBig::from(24)))
+    }
+
+    /// Sunrises and sunsets at `location` on body `id` between `start` and `end`. The upper limb
+    /// elevation is sampled every `step` and refined like in
+    /// [`EventDetector`](crate::events::EventDetector), evaluating only the body and its star.
+    /// `None` if `step` is not positive.
+    pub fn sunrise_sunset(
+        &self,
+        id: i32,
+        location: &GeodeticCoordinates,
+        start: Time,
+        end: Time,
+        step: Time,
+    ) -> Option<Vec<SolarEvent>> {
+        if step <= Time::zero() {
+            return None;
+        }
+        let body = self.get_body_by_id(id)?.body();
+        let elevation = |seconds: &DBig| {
+            let time = Time::from_seconds(seconds.clone());
+            let (star, star_radius) = self.star_in_body_fixed_at(id, &time)?;
+            Some(upper_limb_elevation(body, &star, &star_radius, location))
+        };
+        let tolerance = f64_to_dbig(DEFAULT_TOLERANCE);
+
+        let mut events = vec![];
+        let mut time = start;
+        let mut value = elevation(&time.to_seconds())?;
+        while time < end {
+            let next_time = (&time + &step).min(end.clone());
+            let next_value = elevation(&next_time.to_seconds())?;
+            let kind = match (value > DBig::ZERO, next_value > DBig::ZERO) {
+                (false, true) => Some(SolarEventKind::Sunrise),
+                (true, false) => Some(SolarEventKind::Sunset),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                let root = find_root(
+                    elevation,
+                    (time.to_seconds(), value),
+                    (next_time.to_seconds(), next_value.clone()),
+                    &tolerance,
+                )?;
+                events.push(SolarEvent {
+                    kind,
+                    time: Time::from_seconds(root),
+                });
+            }
+            time = next_time;
+            value = next_value;
+        }
+        Some(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics};
+    use crate::sin_cos::{dbig_to_f64, f64_to_dbig};
+    use crate::units::Mass;
+
+    fn simulation() -> (Simulation, i32) {
+        simulation_with(None, DecimalVector3d::from_f64(0.0, 1.0, 0.0))
+    }
+
+    fn simulation_with(
+        flattening: Option<DBig>,
+        rotation_axis: DecimalVector3d,
+    ) -> (Simulation, i32) {
+        let planet = Body {
+            flattening,
+            rotation_axis,
+            rotation_period: Time::from_seconds(f64_to_dbig(86400.0)),
+            ..Body::new(
+                "planet",
+                Mass::from_kilograms(f64_to_dbig(6e24)),
+                Length::from_meters(f64_to_dbig(6.4e6)),
+                BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                    orbit_radius: Length::from_meters(f64_to_dbig(1.5e11)),
+                    orbit_period: Time::from_seconds(f64_to_dbig(3.15e7)),
+                    orbit_phase: Angle::zero(),
+                    orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                }),
+            )
+        };
+        let star = Body {
+            satellites: vec![planet],
+            rotation_period: Time::from_seconds(f64_to_dbig(2e6)),
+            ..Body::new(
+                "star",
+                Mass::from_kilograms(f64_to_dbig(2e30)),
+                Length::from_meters(f64_to_dbig(7e8)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            )
+        };
+        let mut sim = Simulation::new();
+        let star_id = sim.add_hierarchy(star, None);
+        sim.update(Time::zero());
+        (sim, star_id + 1)
+    }
+
+    #[test]
+    fn noon_at_the_sub_solar_point() {
+        let (sim, planet_id) = simulation();
+        let sub_solar = sim.sub_solar_point(planet_id).unwrap();
+        // equatorial orbit around an upright planet keeps the star over the equator
+        assert!(dbig_to_f64(&sub_solar.latitude.to_radians()).abs() < 1e-9);
+
+        let position = sim.solar_position(planet_id, &sub_solar).unwrap();
+        assert!(
+            (dbig_to_f64(&position.elevation.to_radians()) - std::f64::consts::FRAC_PI_2).abs()
+                < 1e-6
+        );
+        assert!(sim.is_daylight(planet_id, &sub_solar).unwrap());
+        let time = dbig_to_f64(
+            &sim.local_solar_time(planet_id, &sub_solar)
+                .unwrap()
+                .to_hours(),
+        );
+        assert!((time - 12.0).abs() < 1e-9);
+
+        let mut antipode = sub_solar.clone();
+        antipode.longitude += &Angle::from_radians(PI.clone());
+        assert!(!sim.is_daylight(planet_id, &antipode).unwrap());
+    }
+
+    #[test]
+    fn zenith_over_a_tilted_oblate_planet() {
+        let tilt = 23.44f64.to_radians();
+        let (sim, planet_id) = simulation_with(
+            Some(f64_to_dbig(1.0 / 298.257)),
+            DecimalVector3d::from_f64(tilt.sin(), tilt.cos(), 0.0),
+        );
+        let sub_solar = sim.sub_solar_point(planet_id).unwrap();
+        // the planet's pole leans away from the star, the star stands over the southern tropic
+        let latitude = dbig_to_f64(&sub_solar.latitude.to_radians());
+        assert!((latitude + tilt).abs() < 1e-4, "{latitude}");
+        let position = sim.solar_position(planet_id, &sub_solar).unwrap();
+        let elevation = dbig_to_f64(&position.elevation.to_radians());
+        assert!(
+            (elevation - std::f64::consts::FRAC_PI_2).abs() < 1e-9,
+            "{elevation}"
+        );
+    }
+
+    #[test]
+    fn one_sunrise_and_sunset_per_day() {
+        let (sim, planet_id) = simulation();
+        let location = GeodeticCoordinates::new(
+            Angle::from_radians(f64_to_dbig(0.5)),
+            Angle::zero(),
+            Length::zero(),
+        );
+        let events = sim
+            .sunrise_sunset(
+                planet_id,
+                &location,
+                Time::zero(),
+                Time::from_hours(DBig::from(24)),
+                Time::from_hours(DBig::from(2)),
+            )
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_ne!(events[0].kind, events[1].kind);
+        // on the equinox day and slightly longer than half a day because of the star's disk
+        let day_length = match events[0].kind {
+            SolarEventKind::Sunrise => {
+                dbig_to_f64(&(&events[1].time - &events[0].time).to_seconds())
+            }
+            SolarEventKind::Sunset => {
+                86400.0 - dbig_to_f64(&(&events[1].time - &events[0].time).to_seconds())
+            }
+        };
+        assert!(day_length > 43200.0 && day_length < 43800.0, "{day_length}");
+        assert!(sim
+            .sunrise_sunset(
+                planet_id,
+                &location,
+                Time::zero(),
+                Time::zero(),
+                Time::zero()
+            )
+            .is_none());
+    }
+}
@@ -0,0 +1,360 @@
+//! Chebyshev segments of binary SPK kernels (`.bsp`), the DAF files JPL publishes its planetary
+//! ephemerides in, see the NAIF "SPK Required Reading". Only data types 2 (position) and 3
+//! (position and velocity) are read, for type 3 the velocity is still derived from the
+//! position polynomials.
+//!
+//! Times count from [`Epoch::j2000`] like those of every [`ChebyshevBody`] and the z-up frames
+//! are turned y-up like in [`crate::horizons`]. Segments in the equatorial J2000 frame are rotated
+//! onto the ecliptic of J2000, segments in other frames are skipped.
+
+use crate::chebyshev::{ChebyshevBody, ChebyshevSegment};
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::horizons::J2000_OBLIQUITY_ARCSECONDS;
+use crate::sin_cos::f64_to_dbig;
+use crate::time::{Epoch, TimeScale};
+use crate::units::Time;
+use std::fmt;
+
+const RECORD_BYTES: usize = 1024;
+const DOUBLE_BYTES: usize = 8;
+const NAME_BYTES: usize = 40;
+
+/// NAIF frame ids of the equatorial J2000 frame and the ecliptic of J2000.
+const FRAME_J2000: i32 = 1;
+const FRAME_ECLIPJ2000: i32 = 17;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpkError {
+    /// The file does not start with `DAF/SPK`.
+    NotSpk,
+    /// Byte order or summary layout this reader does not handle.
+    UnsupportedFormat(String),
+    /// A record or segment points past the end of the file, or a record number or count lies
+    /// outside what the file can hold.
+    Truncated,
+}
+
+impl fmt::Display for SpkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpkError::NotSpk => write!(f, "not an SPK file"),
+            SpkError::UnsupportedFormat(format) => write!(f, "unsupported SPK format {format}"),
+            SpkError::Truncated => write!(f, "SPK file is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for SpkError {}
+
+/// One segment of an SPK file, the positions of `target` relative to `center` between `start`
+/// and `end`. Ids are NAIF ids.
+#[derive(Debug, Clone)]
+pub struct SpkSegment {
+    pub name: String,
+    pub target: i32,
+    pub center: i32,
+    /// NAIF frame id the file stores the segment in, 1 for J2000 and 17 for the ecliptic of
+    /// J2000. `body` is always in the ecliptic frame.
+    pub frame: i32,
+    pub data_type: i32,
+    pub start: Time,
+    pub end: Time,
+    /// The segment's records in meters. The file does not state their accuracy, set the
+    /// segments' `max_error` from the kernel's documentation before handing them to
+    /// [`crate::body::EphemerisBodyDynamics`].
+    pub body: ChebyshevBody,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpkFile {
+    pub internal_name: String,
+    /// Type 2 and 3 segments in the J2000 or ECLIPJ2000 frames in file order, other segments
+    /// are skipped.
+    pub segments: Vec<SpkSegment>,
+}
+
+struct Daf<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl Daf<'_> {
+    fn slice(&self, offset: usize, length: usize) -> Result<&[u8], SpkError> {
+        let end = offset.checked_add(length).ok_or(SpkError::Truncated)?;
+        self.bytes.get(offset..end).ok_or(SpkError::Truncated)
+    }
+
+    fn i32_at(&self, offset: usize) -> Result<i32, SpkError> {
+        let bytes = self.slice(offset, 4)?.try_into().unwrap();
+        Ok(match self.little_endian {
+            true => i32::from_le_bytes(bytes),
+            false => i32::from_be_bytes(bytes),
+        })
+    }
+
+    fn f64_at(&self, offset: usize) -> Result<f64, SpkError> {
+        let bytes = self.slice(offset, DOUBLE_BYTES)?.try_into().unwrap();
+        Ok(match self.little_endian {
+            true => f64::from_le_bytes(bytes),
+            false => f64::from_be_bytes(bytes),
+        })
+    }
+
+    /// Double at a DAF address, addresses count doubles from 1.
+    fn double(&self, address: usize) -> Result<f64, SpkError> {
+        self.f64_at(address.checked_sub(1).ok_or(SpkError::Truncated)? * DOUBLE_BYTES)
+    }
+
+    fn text(&self, offset: usize, length: usize) -> Result<String, SpkError> {
+        let bytes = self.slice(offset, length)?;
+        Ok(String::from_utf8_lossy(bytes)
+            .trim_end_matches(['\0', ' '])
+            .to_string())
+    }
+}
+
+impl SpkFile {
+    pub fn parse(bytes: &[u8]) -> Result<SpkFile, SpkError> {
+        if !bytes.starts_with(b"DAF/SPK") {
+            return Err(SpkError::NotSpk);
+        }
+        let mut daf = Daf {
+            bytes,
+            little_endian: true,
+        };
+        daf.little_endian = match daf.text(88, 8)?.as_str() {
+            "LTL-IEEE" => true,
+            "BIG-IEEE" => false,
+            format => return Err(SpkError::UnsupportedFormat(format.to_string())),
+        };
+        let (doubles, integers) = (daf.i32_at(8)?, daf.i32_at(12)?);
+        if (doubles, integers) != (2, 6) {
+            return Err(SpkError::UnsupportedFormat(format!(
+                "ND={doubles} NI={integers}"
+            )));
+        }
+        // two doubles and six integers packed into three doubles
+        let summary_bytes = 5 * DOUBLE_BYTES;
+        let summaries_per_record = (RECORD_BYTES - 3 * DOUBLE_BYTES) / summary_bytes;
+
+        let mut segments = vec![];
+        let mut record = record_number(daf.i32_at(76)?.into(), bytes)?;
+        let mut visited = 0;
+        while record != 0 {
+            visited += 1;
+            if visited > bytes.len() / RECORD_BYTES {
+                return Err(SpkError::Truncated);
+            }
+            let base = (record - 1) * RECORD_BYTES;
+            let next = record_number(daf.f64_at(base)?, bytes)?;
+            let count = daf.f64_at(base + 2 * DOUBLE_BYTES)?;
+            if count.fract() != 0.0 || !(0.0..=summaries_per_record as f64).contains(&count) {
+                return Err(SpkError::Truncated);
+            }
+            let count = count as usize;
+            for index in 0..count {
+                let summary = base + 3 * DOUBLE_BYTES + index * summary_bytes;
+                let [target, center, frame, data_type, begin, end] =
+                    [0, 1, 2, 3, 4, 5].map(|i| daf.i32_at(summary + 2 * DOUBLE_BYTES + 4 * i));
+                let (data_type, frame) = (data_type?, frame?);
+                if data_type != 2 && data_type != 3 {
+                    continue;
+                }
+                if frame != FRAME_J2000 && frame != FRAME_ECLIPJ2000 {
+                    continue;
+                }
+                let (target, center) = (target?, center?);
+                let name = daf.text(base + RECORD_BYTES + index * NAME_BYTES, NAME_BYTES)?;
+                let records = Records {
+                    data_type,
+                    frame,
+                    begin: usize::try_from(begin?).map_err(|_| SpkError::Truncated)?,
+                    end: usize::try_from(end?).map_err(|_| SpkError::Truncated)?,
+                };
+                let body = read_chebyshev(&daf, target, center, &name, &records)?;
+                segments.push(SpkSegment {
+                    name,
+                    target,
+                    center,
+                    frame,
+                    data_type,
+                    start: j2000_time(daf.f64_at(summary)?),
+                    end: j2000_time(daf.f64_at(summary + DOUBLE_BYTES)?),
+                    body,
+                });
+            }
+            record = next;
+        }
+        Ok(SpkFile {
+            internal_name: daf.text(16, 60)?,
+            segments,
+        })
+    }
+
+    /// First segment of `target`, `None` if the file has none.
+    pub fn segment(&self, target: i32) -> Option<&SpkSegment> {
+        self.segments
+            .iter()
+            .find(|segment| segment.target == target)
+    }
+}
+
+/// A record number read from the file, records count from 1 and 0 ends the chain of summary
+/// records.
+fn record_number(value: f64, bytes: &[u8]) -> Result<usize, SpkError> {
+    let records = bytes.len() / RECORD_BYTES;
+    if value.fract() != 0.0 || !(0.0..=records as f64).contains(&value) {
+        return Err(SpkError::Truncated);
+    }
+    Ok(value as usize)
+}
+
+/// Time since [`Epoch::j2000`] of a count of seconds since J2000.0 TDB.
+fn j2000_time(seconds: f64) -> Time {
+    Time::from_seconds(f64_to_dbig(seconds) - Epoch::j2000().seconds_since_j2000(TimeScale::Tdb))
+}
+
+/// Where a segment's records lie in the file and how to read them.
+struct Records {
+    data_type: i32,
+    frame: i32,
+    begin: usize,
+    end: usize,
+}
+
+/// Reads the records between DAF addresses `begin` and `end`, which end with the directory
+/// INIT, INTLEN, RSIZE and N. Every record holds MID, RADIUS and the coefficients in km.
+fn read_chebyshev(
+    daf: &Daf,
+    target: i32,
+    center: i32,
+    name: &str,
+    records: &Records,
+) -> Result<ChebyshevBody, SpkError> {
+    let Records {
+        data_type,
+        frame,
+        begin,
+        end,
+    } = *records;
+    let directory = end.checked_sub(3).ok_or(SpkError::Truncated)?;
+    let init = daf.double(directory)?;
+    let interval = daf.double(directory + 1)?;
+    let record_size = daf.double(directory + 2)? as usize;
+    let record_count = daf.double(directory + 3)? as usize;
+    let components = if data_type == 2 { 3 } else { 6 };
+    let count = record_size.saturating_sub(2) / components;
+    let records_end = record_size
+        .checked_mul(record_count)
+        .and_then(|size| size.checked_add(begin));
+    if count == 0 || records_end.is_none_or(|records_end| records_end > directory) {
+        return Err(SpkError::Truncated);
+    }
+
+    let mut segments = vec![];
+    for record in 0..record_count {
+        let first = begin + record * record_size + 2;
+        let mut axes: [Vec<f64>; 3] = Default::default();
+        for (axis, coefficients) in axes.iter_mut().enumerate() {
+            for j in 0..count {
+                coefficients.push(daf.double(first + axis * count + j)? * 1000.0);
+            }
+        }
+        let [x, mut y, mut z] = axes;
+        if frame == FRAME_J2000 {
+            // the polynomials are linear in their coefficients, so these turn like vectors
+            let (sin, cos) = (J2000_OBLIQUITY_ARCSECONDS / 3600.0).to_radians().sin_cos();
+            (y, z) = y
+                .iter()
+                .zip(&z)
+                .map(|(y, z)| (cos * y + sin * z, cos * z - sin * y))
+                .unzip();
+        }
+        // z up in the file, y up in the simulation
+        let coefficients = [x, z, y.into_iter().map(|c| -c).collect()];
+        segments.push(ChebyshevSegment {
+            coefficients,
+            // the producer's fit error is not part of the file
+            max_error: None,
+        });
+    }
+    Ok(ChebyshevBody {
+        id: target,
+        name: name.to_string(),
+        center: Some(center),
+        origin: DecimalVector3d::zero(),
+        start: j2000_time(init),
+        interval: Time::from_seconds(f64_to_dbig(interval)),
+        segments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::EphemerisBodyDynamics;
+    use crate::playback::PlaybackError;
+    use crate::sin_cos::dbig_to_f64;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn reads_type_2_segments() {
+        let file = SpkFile::parse(include_bytes!("../fixtures/moon_type2.bsp")).unwrap();
+        assert_eq!(file.internal_name, "synthetic circular moon orbit fixture");
+        assert_eq!(file.segments.len(), 1);
+        let segment = file.segment(301).unwrap();
+        assert_eq!(
+            (segment.center, segment.frame, segment.data_type),
+            (399, 1, 2)
+        );
+        assert_eq!(segment.name, "MOON CIRCULAR FIXTURE");
+        // J2000.0 is an instant of TT, a little off midday TDB
+        let end = dbig_to_f64(&segment.end.to_seconds());
+        assert!((end - 12.0 * 86400.0).abs() < 1e-3 && end != 12.0 * 86400.0);
+
+        let time = 5.3 * 86400.0;
+        let (position, velocity) = segment
+            .body
+            .evaluate(&Time::from_seconds(f64_to_dbig(time)))
+            .unwrap();
+        let period = 27.321661 * 86400.0;
+        let angle = 2.0 * PI * time / period;
+        let (sin, cos) = (angle.sin() * 3.844e8, angle.cos() * 3.844e8);
+        // inclined by 0.09 rad to the equator around the equinox, the ecliptic is tilted further
+        let inclination = 0.09 - (J2000_OBLIQUITY_ARCSECONDS / 3600.0).to_radians();
+        let expected =
+            DecimalVector3d::from_f64(cos, sin * inclination.sin(), -sin * inclination.cos());
+        assert!(dbig_to_f64(&position.distance_to(expected)) < 1.0);
+        let speed = dbig_to_f64(&velocity.length());
+        assert!(
+            (speed - 2.0 * PI * 3.844e8 / period).abs() < 1e-3,
+            "{speed}"
+        );
+        assert!(segment
+            .body
+            .evaluate(&Time::from_seconds(f64_to_dbig(-1.0)))
+            .is_none());
+        assert_eq!(
+            EphemerisBodyDynamics::new(segment.body.clone(), 1e3).unwrap_err(),
+            PlaybackError::UnknownErrorBound { id: 301 }
+        );
+
+        assert_eq!(SpkFile::parse(b"NAIF/DAF").unwrap_err(), SpkError::NotSpk);
+        let truncated = &include_bytes!("../fixtures/moon_type2.bsp")[..3000];
+        assert_eq!(SpkFile::parse(truncated).unwrap_err(), SpkError::Truncated);
+        // record numbers past the file or negative ones, forward and chained
+        let bytes = include_bytes!("../fixtures/moon_type2.bsp");
+        for record in [-1i32, 1000] {
+            let mut corrupt = bytes.to_vec();
+            corrupt[76..80].copy_from_slice(&record.to_le_bytes());
+            assert_eq!(SpkFile::parse(&corrupt).unwrap_err(), SpkError::Truncated);
+        }
+        let first = i32::from_le_bytes(bytes[76..80].try_into().unwrap()) as usize;
+        let base = (first - 1) * RECORD_BYTES;
+        for next in [-3.0, 1e300, f64::NAN] {
+            let mut corrupt = bytes.to_vec();
+            corrupt[base..base + DOUBLE_BYTES].copy_from_slice(&next.to_le_bytes());
+            assert_eq!(SpkFile::parse(&corrupt).unwrap_err(), SpkError::Truncated);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::body::Body;
+use crate::decimal_vector_3d::DecimalVector3d;
+use crate::reference_frame::{ReferenceFrame, StateVector};
+use crate::simulation::Simulation;
+use crate::sin_cos::{atan2, cos, f64_to_dbig, precise, sin};
+use crate::units::{Angle, Length};
+use dashu_float::ops::{Abs, SquareRoot};
+use dashu_float::DBig;
+
+const MAX_ITERATIONS: usize = 64;
+
+/// A point relative to a body's reference ellipsoid. Latitude is geodetic (measured against the
+/// surface normal) and longitude grows eastwards, in the direction the body spins.
+#[derive(Debug, Clone)]
+pub struct GeodeticCoordinates {
+    pub latitude: Angle,
+    pub longitude: Angle, // 0 is the prime meridian (body-fixed x axis)
+    pub altitude: Length, // above the ellipsoid
+}
+
+impl GeodeticCoordinates {
+    pub fn new(latitude: Angle, longitude: Angle, altitude: Length) -> GeodeticCoordinates {
+        GeodeticCoordinates {
+            latitude,
+            longitude,
+            altitude,
+        }
+    }
+}
+
+impl Body {
+    /// Square of the first eccentricity of the reference ellipsoid, `f * (2 - f)`.
+    fn ellipsoid_eccentricity_squared(&self) -> DBig {
+        match &self.flattening {
+            Some(f) => precise(f.clone()) * (DBig::from(2) - f),
+            None => DBig::ZERO,
+        }
+    }
+
+    /// Converts body-fixed coordinates (y is the pole, x the prime meridian) into geodetic ones.
+    pub fn body_fixed_to_geodetic(&self, position: &DecimalVector3d) -> GeodeticCoordinates {
+        let a = self.radius.to_meters();
+        let e2 = self.ellipsoid_eccentricity_squared();
+        let polar = precise(position.y.clone());
+        let equatorial =
+            precise(position.x.clone() * &position.x + position.z.clone() * &position.z).sqrt();
+        let longitude = atan2(-position.z.clone(), position.x.clone(), 32);
+
+        let limit = f64_to_dbig(1e-30);
+        let mut latitude = atan2(polar.clone(), equatorial.clone() * (DBig::ONE - &e2), 32);
+        let mut altitude = DBig::ZERO;
+        for _ in 0..MAX_ITERATIONS {
+            let (s, c) = (sin(latitude.clone(), 32), cos(latitude.clone(), 32));
+            let root = (DBig::ONE - e2.clone() * &s * &s).sqrt();
+            let prime_vertical = a.clone() / &root;
+            // stays well conditioned at the poles unlike `p / cos(lat) - N`
+            altitude = equatorial.clone() * &c + polar.clone() * &s - a.clone() * &root;
+            let next = atan2(
+                polar.clone(),
+                equatorial.clone()
+                    * (DBig::ONE
+                        - e2.clone() * &prime_vertical / (prime_vertical.clone() + &altitude)),
+                32,
+            );
+            let delta = (next.clone() - &latitude).abs();
+            latitude = next;
+            if delta < limit {
+                break;
+            }
+        }
+
+        GeodeticCoordinates::new(
+            Angle::from_radians(latitude),
+            Angle::from_radians(longitude),
+            Length::from_meters(altitude),
+        )
+    }
+
+    /// Converts geodetic coordinates into body-fixed ones.
+    pub fn geodetic_to_body_fixed(&self, coordinates: &GeodeticCoordinates) -> DecimalVector3d {
+        let a = self.radius.to_meters();
+        let e2 = self.ellipsoid_eccentricity_squared();
+        let (latitude, longitude) = (
+            coordinates.latitude.to_radians(),
+            coordinates.longitude.to_radians(),
+        );
+        let altitude = coordinates.altitude.to_meters();
+        let (sin_lat, cos_lat) = (sin(latitude.clone(), 32), cos(latitude, 32));
+        let (sin_lon, cos_lon) = (sin(longitude.clone(), 32), cos(longitude, 32));
+        let prime_vertical = a / (DBig::ONE - e2.clone() * &sin_lat * &sin_lat).sqrt();
+        let equatorial = (prime_vertical.clone() + &altitude) * cos_lat;
+        let polar = (prime_vertical * (DBig::ONE - e2) + &altitude) * sin_lat;
+        DecimalVector3d::new(equatorial.clone() * cos_lon, polar, -equatorial * sin_lon)
+    }
+}
+
+impl Simulation {
+    /// Geodetic coordinates of the global `position` on body `id` at the current simulation time.
+    pub fn global_to_geodetic(
+        &self,
+        id: i32,
+        position: &DecimalVector3d,
+    ) -> Option<GeodeticCoordinates> {
+        let body = self.get_body_by_id(id)?;
+        let state = StateVector::new(position.clone(), DecimalVector3d::zero());
+        let fixed = self.transform(
+            &state,
+            ReferenceFrame::Global,
+            ReferenceFrame::BodyFixed { id },
+        )?;
+        Some(body.body().body_fixed_to_geodetic(&fixed.position))
+    }
+
+    /// Global position of a point given in geodetic coordinates on body `id`.
+    pub fn geodetic_to_global(
+        &self,
+        id: i32,
+        coordinates: &GeodeticCoordinates,
+    ) -> Option<DecimalVector3d> {
+        self.surface_point_state(id, coordinates)
+            .map(|state| state.position)
+    }
+
+    /// Global position and velocity of a point fixed to body `id`, the velocity includes the
+    /// body's orbital motion and the surface speed from its rotation.
+    pub fn surface_point_state(
+        &self,
+        id: i32,
+        coordinates: &GeodeticCoordinates,
+    ) -> Option<StateVector> {
+        let body = self.get_body_by_id(id)?;
+        let fixed = StateVector::new(
+            body.body().geodetic_to_body_fixed(coordinates),
+            DecimalVector3d::zero(),
+        );
+        self.transform(
+            &fixed,
+            ReferenceFrame::BodyFixed { id },
+            ReferenceFrame::Global,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{BodyDynamics, StaticBodyDynamics};
+    use crate::sin_cos::dbig_to_f64;
+    use crate::units::{Mass, Time};
+
+    fn earth() -> Body {
+        Body {
+            flattening: Some(f64_to_dbig(1.0 / 298.257223563)),
+            rotation_period: Time::from_seconds(f64_to_dbig(86164.1)),
+            ..Body::new(
+                "earth",
+                Mass::from_kilograms(f64_to_dbig(5.972e24)),
+                Length::from_meters(f64_to_dbig(6378137.0)),
+                BodyDynamics::Static(StaticBodyDynamics {
+                    position: DecimalVector3d::zero(),
+                }),
+            )
+        }
+    }
+
+    #[test]
+    fn geodetic_round_trip() {
+        let earth = earth();
+        for (lat, lon, alt) in [(0.7, 2.1, 350.0), (-1.5, -0.3, 12000.0), (0.0, 3.0, -50.0)] {
+            let coordinates = GeodeticCoordinates::new(
+                Angle::from_radians(f64_to_dbig(lat)),
+                Angle::from_radians(f64_to_dbig(lon)),
+                Length::from_meters(f64_to_dbig(alt)),
+            );
+            let fixed = earth.geodetic_to_body_fixed(&coordinates);
+            let back = earth.body_fixed_to_geodetic(&fixed);
+            assert!((dbig_to_f64(&back.latitude.to_radians()) - lat).abs() < 1e-12);
+            assert!((dbig_to_f64(&back.longitude.to_radians()) - lon).abs() < 1e-12);
+            assert!((dbig_to_f64(&back.altitude.to_meters()) - alt).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn poles_sit_on_the_polar_radius() {
+        let earth = earth();
+        let pole = earth.body_fixed_to_geodetic(&DecimalVector3d::from_f64(0.0, 6356752.3142, 0.0));
+        assert!(
+            (dbig_to_f64(&pole.latitude.to_radians()) - std::f64::consts::FRAC_PI_2).abs() < 1e-12
+        );
+        assert!(dbig_to_f64(&pole.altitude.to_meters()).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,438 @@
+//! Instants on the UTC, TAI, TT and TDB time scales, as Julian dates or ISO 8601 timestamps.
+//!
+//! Every scale counts seconds since J2000.0, JD 2451545.0 on that scale. UTC counts like Unix
+//! time and skips leap seconds, so the count of an inserted `23:59:60` equals that of the
+//! following midnight, timestamps keep them apart.
+
+use crate::simulation::Simulation;
+use crate::sin_cos::{dbig_to_f64, f64_to_dbig, precise};
+use crate::units::Time;
+use dashu_float::DBig;
+use std::str::FromStr;
+
+pub const J2000_JULIAN_DATE: u32 = 2451545;
+pub const SECONDS_PER_DAY: u32 = 86400;
+/// Julian date of MJD 0, 1858-11-17T00:00.
+const MODIFIED_JULIAN_DATE_OFFSET: &str = "2400000.5";
+/// TT - TAI, in seconds.
+const TT_MINUS_TAI: &str = "32.184";
+/// Days from 1970-01-01 to 2000-01-01.
+const J2000_DAY: i64 = 10957;
+/// Seconds from midnight to noon, J2000.0 is at noon.
+const HALF_DAY: i64 = 43200;
+
+/// TAI - UTC in seconds from the first of the month on, as announced by the IERS up to Bulletin
+/// C 70 (no leap second after 2016-12-31). Earlier UTC is taken as TAI - 10 s, the rubber
+/// seconds before 1972 are not modelled.
+const LEAP_SECONDS: [(i64, u32, i64); 28] = [
+    (1972, 1, 10),
+    (1972, 7, 11),
+    (1973, 1, 12),
+    (1974, 1, 13),
+    (1975, 1, 14),
+    (1976, 1, 15),
+    (1977, 1, 16),
+    (1978, 1, 17),
+    (1979, 1, 18),
+    (1980, 1, 19),
+    (1981, 7, 20),
+    (1982, 7, 21),
+    (1983, 7, 22),
+    (1985, 7, 23),
+    (1988, 1, 24),
+    (1990, 1, 25),
+    (1991, 1, 26),
+    (1992, 7, 27),
+    (1993, 7, 28),
+    (1994, 7, 29),
+    (1996, 1, 30),
+    (1997, 7, 31),
+    (1999, 1, 32),
+    (2006, 1, 33),
+    (2009, 1, 34),
+    (2012, 7, 35),
+    (2015, 7, 36),
+    (2017, 1, 37),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeScale {
+    /// Coordinated Universal Time, civil time kept within a second of the Earth's rotation.
+    Utc,
+    /// International Atomic Time.
+    Tai,
+    /// Terrestrial Time, TAI + 32.184 s.
+    Tt,
+    /// Barycentric Dynamical Time, the scale of JPL ephemerides and of simulation time.
+    Tdb,
+}
+
+/// An instant, stored as TT seconds since J2000.0.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Epoch {
+    tt: DBig,
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Proleptic Gregorian date of a day counted from 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    let next = if month == 12 {
+        days_from_civil(year + 1, 1, 1)
+    } else {
+        days_from_civil(year, month + 1, 1)
+    };
+    (next - days_from_civil(year, month, 1)) as u32
+}
+
+fn floor_to_i64(value: &DBig) -> i64 {
+    value.floor().to_string().parse().unwrap()
+}
+
+fn constant(text: &str) -> DBig {
+    precise(DBig::from_str(text).unwrap())
+}
+
+/// TAI - UTC on a day counted from 1970-01-01.
+fn tai_minus_utc(day: i64) -> i64 {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|&&(year, month, _)| day >= days_from_civil(year, month, 1))
+        .map_or(10, |&(_, _, delta)| delta)
+}
+
+/// Seconds since J2000.0 of a day counted from 1970-01-01 plus seconds into it.
+fn calendar_count(day: i64, seconds_of_day: DBig) -> DBig {
+    DBig::from((day - J2000_DAY) * SECONDS_PER_DAY as i64 - HALF_DAY) + seconds_of_day
+}
+
+/// Day counted from 1970-01-01 and seconds into it of a count since J2000.0.
+fn split_count(count: &DBig) -> (i64, DBig) {
+    let shifted = precise(count.clone()) + DBig::from(HALF_DAY);
+    let days = floor_to_i64(&(shifted.clone() / DBig::from(SECONDS_PER_DAY)));
+    (
+        days + J2000_DAY,
+        shifted - DBig::from(days * SECONDS_PER_DAY as i64),
+    )
+}
+
+/// TDB - TT, periodic terms of the Earth's orbit good to about 30 microseconds, in seconds.
+fn tdb_minus_tt(tt: &DBig) -> DBig {
+    let days = dbig_to_f64(tt) / SECONDS_PER_DAY as f64;
+    let anomaly = (357.53 + 0.98560028 * days).to_radians();
+    f64_to_dbig(0.001657 * anomaly.sin() + 0.000014 * (2.0 * anomaly).sin())
+}
+
+impl Epoch {
+    /// J2000.0, 2000-01-01T12:00:00 TT.
+    pub fn j2000() -> Epoch {
+        Epoch {
+            tt: precise(DBig::ZERO),
+        }
+    }
+
+    pub fn from_seconds_since_j2000(seconds: DBig, scale: TimeScale) -> Epoch {
+        let seconds = precise(seconds);
+        let tt = match scale {
+            TimeScale::Tt => seconds,
+            TimeScale::Tai => seconds + constant(TT_MINUS_TAI),
+            TimeScale::Tdb => seconds.clone() - tdb_minus_tt(&seconds),
+            TimeScale::Utc => {
+                let (day, _) = split_count(&seconds);
+                seconds + DBig::from(tai_minus_utc(day)) + constant(TT_MINUS_TAI)
+            }
+        };
+        Epoch { tt }
+    }
+
+    pub fn seconds_since_j2000(&self, scale: TimeScale) -> DBig {
+        match scale {
+            TimeScale::Tt => self.tt.clone(),
+            TimeScale::Tai => self.tt.clone() - constant(TT_MINUS_TAI),
+            TimeScale::Tdb => self.tt.clone() + tdb_minus_tt(&self.tt),
+            TimeScale::Utc => {
+                let (day, seconds_of_day) = self.utc_day();
+                calendar_count(day, seconds_of_day)
+            }
+        }
+    }
+
+    /// Day counted from 1970-01-01 and UTC seconds into it, beyond 86400 during a leap second.
+    fn utc_day(&self) -> (i64, DBig) {
+        let tai = self.seconds_since_j2000(TimeScale::Tai);
+        for (index, &(year, month, delta)) in LEAP_SECONDS.iter().enumerate().rev() {
+            let day = days_from_civil(year, month, 1);
+            let start = calendar_count(day, DBig::from(delta));
+            let inserted = delta - index.checked_sub(1).map_or(delta, |i| LEAP_SECONDS[i].2);
+            if tai >= start {
+                return split_count(&(tai - DBig::from(delta)));
+            }
+            let leap_start = start - DBig::from(inserted);
+            if tai >= leap_start {
+                return (day - 1, DBig::from(SECONDS_PER_DAY) + (tai - leap_start));
+            }
+        }
+        split_count(&(tai - DBig::from(LEAP_SECONDS[0].2)))
+    }
+
+    pub fn from_julian_date(julian_date: DBig, scale: TimeScale) -> Epoch {
+        let days = precise(julian_date) - DBig::from(J2000_JULIAN_DATE);
+        Epoch::from_seconds_since_j2000(days * DBig::from(SECONDS_PER_DAY), scale)
+    }
+
+    pub fn julian_date(&self, scale: TimeScale) -> DBig {
+        DBig::from(J2000_JULIAN_DATE)
+            + self.seconds_since_j2000(scale) / DBig::from(SECONDS_PER_DAY)
+    }
+
+    pub fn from_modified_julian_date(modified_julian_date: DBig, scale: TimeScale) -> Epoch {
+        Epoch::from_julian_date(
+            modified_julian_date + constant(MODIFIED_JULIAN_DATE_OFFSET),
+            scale,
+        )
+    }
+
+    pub fn modified_julian_date(&self, scale: TimeScale) -> DBig {
+        self.julian_date(scale) - constant(MODIFIED_JULIAN_DATE_OFFSET)
+    }
+
+    /// Seconds of `scale` from `other` to this epoch.
+    pub fn seconds_since(&self, other: &Epoch, scale: TimeScale) -> DBig {
+        self.seconds_since_j2000(scale) - other.seconds_since_j2000(scale)
+    }
+
+    /// This epoch moved by `seconds` of `scale`.
+    pub fn add_seconds(&self, seconds: DBig, scale: TimeScale) -> Epoch {
+        Epoch::from_seconds_since_j2000(self.seconds_since_j2000(scale) + seconds, scale)
+    }
+
+    /// `None` for an invalid date or time, `second` may reach 60 only on UTC.
+    pub fn from_calendar(
+        year: i64,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+        second: DBig,
+        scale: TimeScale,
+    ) -> Option<Epoch> {
+        let second_limit = if scale == TimeScale::Utc { 61 } else { 60 };
+        if !(1..=12).contains(&month)
+            || !(1..=days_in_month(year, month)).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second < DBig::ZERO
+            || second >= DBig::from(second_limit)
+        {
+            return None;
+        }
+        let day = days_from_civil(year, month, day);
+        let seconds_of_day = DBig::from(hour * 3600 + minute * 60) + precise(second);
+        Some(Epoch::from_calendar_day(day, seconds_of_day, scale))
+    }
+
+    fn from_calendar_day(day: i64, seconds_of_day: DBig, scale: TimeScale) -> Epoch {
+        let count = calendar_count(day, seconds_of_day);
+        match scale {
+            // the day decides TAI - UTC, not the count, which is ambiguous in a leap second
+            TimeScale::Utc => Epoch {
+                tt: count + DBig::from(tai_minus_utc(day)) + constant(TT_MINUS_TAI),
+            },
+            _ => Epoch::from_seconds_since_j2000(count, scale),
+        }
+    }
+
+    /// Reads `2000-01-01`, `2000-01-01T12:00`, `2000-01-01T12:00:00.5` or with a space instead
+    /// of the `T`, on `scale`. A trailing `Z` is accepted, a `+01:00` like offset is subtracted.
+    pub fn parse_iso8601(text: &str, scale: TimeScale) -> Option<Epoch> {
+        let text = text.trim();
+        let (date, time) = match text.split_once(['T', ' ']) {
+            Some((date, time)) => (date, time),
+            None => (text, "00:00"),
+        };
+        let (sign, date) = match date.strip_prefix('-') {
+            Some(date) => (-1, date),
+            None => (1, date),
+        };
+        let mut date_parts = date.split('-');
+        let year = sign * date_parts.next()?.parse::<i64>().ok()?;
+        let month = date_parts.next()?.parse().ok()?;
+        let day = date_parts.next()?.parse().ok()?;
+        if date_parts.next().is_some() {
+            return None;
+        }
+
+        let time = time.strip_suffix('Z').unwrap_or(time);
+        let (time, offset) = match time.find(['+', '-']) {
+            Some(index) => {
+                let (hours, minutes) = time[index + 1..].split_once(':')?;
+                let minutes = hours.parse::<i64>().ok()? * 60 + minutes.parse::<i64>().ok()?;
+                let sign = if time[index..].starts_with('-') {
+                    -1
+                } else {
+                    1
+                };
+                (&time[..index], sign * minutes * 60)
+            }
+            None => (time, 0),
+        };
+        let mut time_parts = time.split(':');
+        let hour = time_parts.next()?.parse().ok()?;
+        let minute = time_parts.next()?.parse().ok()?;
+        let second = match time_parts.next() {
+            Some(second) => DBig::from_str(second).ok()?,
+            None => DBig::ZERO,
+        };
+        if time_parts.next().is_some() {
+            return None;
+        }
+        let epoch = Epoch::from_calendar(year, month, day, hour, minute, second, scale)?;
+        match offset {
+            0 => Some(epoch),
+            offset => Some(epoch.add_seconds(DBig::from(-offset), scale)),
+        }
+    }
+
+    /// `2000-01-01T12:00:00.000` on `scale` to the millisecond, with a `Z` for UTC.
+    pub fn to_iso8601(&self, scale: TimeScale) -> String {
+        let (mut day, seconds_of_day) = match scale {
+            TimeScale::Utc => self.utc_day(),
+            _ => split_count(&self.seconds_since_j2000(scale)),
+        };
+        let leap = seconds_of_day >= DBig::from(SECONDS_PER_DAY);
+        let mut milliseconds = floor_to_i64(&(seconds_of_day * DBig::from(1000) + constant("0.5")));
+        if !leap && milliseconds >= SECONDS_PER_DAY as i64 * 1000 {
+            day += 1;
+            milliseconds -= SECONDS_PER_DAY as i64 * 1000;
+        }
+        // a leap second stays at 23:59:60
+        let hour = (milliseconds / 3_600_000).min(23);
+        let minute = ((milliseconds - hour * 3_600_000) / 60_000).min(59);
+        let millisecond = milliseconds - hour * 3_600_000 - minute * 60_000;
+        let (year, month, day) = civil_from_days(day);
+        format!(
+            "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{:02}.{:03}{}",
+            millisecond / 1000,
+            millisecond % 1000,
+            if scale == TimeScale::Utc { "Z" } else { "" }
+        )
+    }
+}
+
+impl Simulation {
+    /// Instant of simulation time `time`, TDB since [`Simulation::epoch`].
+    pub fn epoch_at(&self, time: &Time) -> Epoch {
+        self.epoch().add_seconds(time.to_seconds(), TimeScale::Tdb)
+    }
+
+    /// Simulation time of `epoch`, the inverse of [`Simulation::epoch_at`].
+    pub fn time_of(&self, epoch: &Epoch) -> Time {
+        Time::from_seconds(epoch.seconds_since(self.epoch(), TimeScale::Tdb))
+    }
+
+    /// Time since [`Epoch::j2000`] of simulation time `time`, the time ephemeris segments
+    /// count in, see [`ChebyshevBody::start`](crate::chebyshev::ChebyshevBody::start).
+    pub(crate) fn ephemeris_time(&self, time: &Time) -> Time {
+        time + &Time::from_seconds(self.epoch().seconds_since(&Epoch::j2000(), TimeScale::Tdb))
+    }
+
+    /// Instant of the last [`Simulation::update`].
+    pub fn current_epoch(&self) -> Epoch {
+        self.epoch_at(self.time())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(value: &DBig) -> f64 {
+        dbig_to_f64(value)
+    }
+
+    #[test]
+    fn converts_between_scales() {
+        let j2000 = Epoch::j2000();
+        assert_eq!(seconds(&j2000.julian_date(TimeScale::Tt)), 2451545.0);
+        assert_eq!(seconds(&j2000.modified_julian_date(TimeScale::Tt)), 51544.5);
+        assert_eq!(j2000.to_iso8601(TimeScale::Tt), "2000-01-01T12:00:00.000");
+        assert_eq!(j2000.to_iso8601(TimeScale::Tai), "2000-01-01T11:59:27.816");
+        assert_eq!(j2000.to_iso8601(TimeScale::Utc), "2000-01-01T11:58:55.816Z");
+        let tdb = seconds(&j2000.seconds_since_j2000(TimeScale::Tdb));
+        assert!(tdb.abs() < 1e-4, "{tdb}");
+
+        let epoch = Epoch::from_julian_date(f64_to_dbig(2460000.25), TimeScale::Tdb);
+        let back = seconds(&epoch.julian_date(TimeScale::Tdb));
+        assert!((back - 2460000.25).abs() < 1e-9, "{back}");
+        let difference = seconds(&epoch.seconds_since_j2000(TimeScale::Tt))
+            - seconds(&epoch.seconds_since_j2000(TimeScale::Utc));
+        assert!((difference - 69.184).abs() < 1e-6, "{difference}");
+    }
+
+    #[test]
+    fn parses_and_formats_leap_seconds() {
+        let before = Epoch::parse_iso8601("2016-12-31T23:59:59Z", TimeScale::Utc).unwrap();
+        let leap = Epoch::parse_iso8601("2016-12-31T23:59:60.5", TimeScale::Utc).unwrap();
+        let after = Epoch::parse_iso8601("2017-01-01 00:00", TimeScale::Utc).unwrap();
+        assert_eq!(seconds(&leap.seconds_since(&before, TimeScale::Tai)), 1.5);
+        assert_eq!(seconds(&after.seconds_since(&before, TimeScale::Tai)), 2.0);
+        assert_eq!(leap.to_iso8601(TimeScale::Utc), "2016-12-31T23:59:60.500Z");
+        assert_eq!(after.to_iso8601(TimeScale::Utc), "2017-01-01T00:00:00.000Z");
+        assert_eq!(
+            seconds(&after.seconds_since_j2000(TimeScale::Tai))
+                - seconds(&after.seconds_since_j2000(TimeScale::Utc)),
+            37.0
+        );
+
+        let offset = Epoch::parse_iso8601("2017-01-01T01:30:00+01:00", TimeScale::Utc).unwrap();
+        assert_eq!(
+            offset.to_iso8601(TimeScale::Utc),
+            "2017-01-01T00:30:00.000Z"
+        );
+        let old = Epoch::parse_iso8601("1969-07-20T20:17:40", TimeScale::Utc).unwrap();
+        assert_eq!(old.to_iso8601(TimeScale::Utc), "1969-07-20T20:17:40.000Z");
+        assert_eq!(Epoch::parse_iso8601("2017-02-29", TimeScale::Utc), None);
+        assert_eq!(
+            Epoch::parse_iso8601("2017-01-01T10:00:60", TimeScale::Tt),
+            None
+        );
+
+        let mut sim = Simulation::new();
+        sim.set_epoch(after.clone());
+        let time = sim.time_of(&Epoch::parse_iso8601("2017-01-02", TimeScale::Utc).unwrap());
+        // a TDB day is a few tens of microseconds longer than a TT day in January
+        assert!((seconds(&time.to_seconds()) - 86400.0).abs() < 1e-4);
+        assert_eq!(
+            sim.current_epoch().to_iso8601(TimeScale::Utc),
+            "2017-01-01T00:00:00.000Z"
+        );
+    }
+}
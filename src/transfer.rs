@@ -0,0 +1,295 @@
+//! Transfer planning between orbits around a common central body: the classic Hohmann and
+//! bi-elliptic transfers between circular coplanar orbits, and porkchop grids of Lambert
+//! transfers between two bodies of a [`Simulation`] over ranges of departure and arrival times.
+
+use crate::lambert::solve_lambert;
+use crate::simulation::Simulation;
+use crate::sin_cos::{precise, PI};
+use crate::units::{Length, Time, Velocity};
+use dashu_float::ops::{Abs, SquareRoot};
+use dashu_float::DBig;
+use std::io::{self, Write};
+
+/// Transfer made of instantaneous burns.
+#[derive(Debug, Clone)]
+pub struct ImpulsiveTransfer {
+    /// Size of each burn, in order.
+    pub burns: Vec<Velocity>,
+    pub time_of_flight: Time,
+}
+
+impl ImpulsiveTransfer {
+    pub fn total_delta_v(&self) -> Velocity {
+        self.burns
+            .iter()
+            .fold(Velocity::zero(), |total, burn| total + burn)
+    }
+}
+
+/// Speed at distance `radius` on an orbit with semi-major axis `semi_major_axis`.
+fn vis_viva(mu: &DBig, radius: &DBig, semi_major_axis: &DBig) -> DBig {
+    precise(DBig::from(2) * mu / radius - mu.clone() / semi_major_axis).sqrt()
+}
+
+/// Time half an orbit with semi-major axis `semi_major_axis` takes.
+fn half_period(mu: &DBig, semi_major_axis: &DBig) -> DBig {
+    PI.clone() * (semi_major_axis.clone() * semi_major_axis * semi_major_axis / mu).sqrt()
+}
+
+/// Transfer between circular coplanar orbits of radius `from` and `to` along half an ellipse
+/// touching both, around a central body with gravitational parameter `gravitational_parameter`.
+pub fn hohmann(from: &Length, to: &Length, gravitational_parameter: &DBig) -> ImpulsiveTransfer {
+    let mu = precise(gravitational_parameter.clone());
+    let (r1, r2) = (from.to_meters(), to.to_meters());
+    let transfer = (r1.clone() + &r2) / DBig::from(2);
+    let first = vis_viva(&mu, &r1, &transfer) - vis_viva(&mu, &r1, &r1);
+    let second = vis_viva(&mu, &r2, &r2) - vis_viva(&mu, &r2, &transfer);
+    ImpulsiveTransfer {
+        burns: vec![
+            Velocity::from_meters_per_second(first.abs()),
+            Velocity::from_meters_per_second(second.abs()),
+        ],
+        time_of_flight: Time::from_seconds(half_period(&mu, &transfer)),
+    }
+}
+
+/// Transfer between circular coplanar orbits of radius `from` and `to` over two half ellipses
+/// meeting at distance `apoapsis`, cheaper than [`hohmann`] when the radii differ by more than
+/// a factor of about 15.6 and `apoapsis` is far enough out.
+pub fn bi_elliptic(
+    from: &Length,
+    to: &Length,
+    apoapsis: &Length,
+    gravitational_parameter: &DBig,
+) -> ImpulsiveTransfer {
+    let mu = precise(gravitational_parameter.clone());
+    let (r1, r2, rb) = (from.to_meters(), to.to_meters(), apoapsis.to_meters());
+    let first_transfer = (r1.clone() + &rb) / DBig::from(2);
+    let second_transfer = (r2.clone() + &rb) / DBig::from(2);
+    let burns = [
+        vis_viva(&mu, &r1, &first_transfer) - vis_viva(&mu, &r1, &r1),
+        vis_viva(&mu, &rb, &second_transfer) - vis_viva(&mu, &rb, &first_transfer),
+        vis_viva(&mu, &r2, &second_transfer) - vis_viva(&mu, &r2, &r2),
+    ];
+    ImpulsiveTransfer {
+        burns: burns
+            .into_iter()
+            .map(|burn| Velocity::from_meters_per_second(burn.abs()))
+            .collect(),
+        time_of_flight: Time::from_seconds(
+            half_period(&mu, &first_transfer) + half_period(&mu, &second_transfer),
+        ),
+    }
+}
+
+/// Direct prograde transfer between two bodies leaving and arriving at given times.
+#[derive(Debug, Clone)]
+pub struct PorkchopCell {
+    pub departure: Time,
+    pub arrival: Time,
+    /// Square of `departure_excess`, in m^2/s^2.
+    pub c3: DBig,
+    /// Hyperbolic excess speed leaving the departure body.
+    pub departure_excess: Velocity,
+    /// Hyperbolic excess speed approaching the arrival body.
+    pub arrival_excess: Velocity,
+}
+
+impl PorkchopCell {
+    /// Delta-v of departing and matching the arrival body's velocity, both counted from and to
+    /// the bodies' own motion without their gravity wells.
+    pub fn total_delta_v(&self) -> Velocity {
+        self.departure_excess.clone() + &self.arrival_excess
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Porkchop {
+    /// One cell per departure and later arrival time with a transfer, departures outermost.
+    pub cells: Vec<PorkchopCell>,
+}
+
+impl Porkchop {
+    /// Cell with the lowest total delta-v.
+    pub fn best(&self) -> Option<&PorkchopCell> {
+        self.cells
+            .iter()
+            .min_by(|a, b| a.total_delta_v().partial_cmp(&b.total_delta_v()).unwrap())
+    }
+
+    /// One row per cell, times in seconds, C3 in m^2/s^2 and speeds in m/s.
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(
+            writer,
+            "departure,arrival,c3,departure_excess,arrival_excess,total_delta_v"
+        )?;
+        for cell in &self.cells {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                cell.departure.to_seconds(),
+                cell.arrival.to_seconds(),
+                cell.c3,
+                cell.departure_excess.to_meters_per_second(),
+                cell.arrival_excess.to_meters_per_second(),
+                cell.total_delta_v().to_meters_per_second()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Simulation {
+    /// Scans direct prograde Lambert transfers from body `from` to body `to` for every pair of
+    /// `departures` and later `arrivals`. Both bodies have to orbit the same parent, whose
+    /// [`Simulation::central_mass`] the transfers are computed around; `None` otherwise.
+    pub fn porkchop(
+        &self,
+        from: i32,
+        to: i32,
+        departures: &[Time],
+        arrivals: &[Time],
+    ) -> Option<Porkchop> {
+        let parent = self.get_body_by_id(from)?.parent()?;
+        if self.get_body_by_id(to)?.parent()? != parent {
+            return None;
+        }
+        let mu = self.constants().gm_from_mass(&self.central_mass(parent)?);
+
+        let mut cells = vec![];
+        for departure in departures {
+            let start = self.relative_state_at(departure, from);
+            for arrival in arrivals.iter().filter(|arrival| *arrival > departure) {
+                let end = self.relative_state_at(arrival, to);
+                let time_of_flight = arrival - departure;
+                let transfers = solve_lambert(
+                    &start.position,
+                    &end.position,
+                    &time_of_flight,
+                    &mu,
+                    0,
+                    true,
+                );
+                let Some(transfer) = transfers.first() else {
+                    continue;
+                };
+                let departure_excess = (&transfer.departure_velocity - &start.velocity).length();
+                let arrival_excess = (&transfer.arrival_velocity - &end.velocity).length();
+                cells.push(PorkchopCell {
+                    departure: departure.clone(),
+                    arrival: arrival.clone(),
+                    c3: departure_excess.clone() * &departure_excess,
+                    departure_excess: Velocity::from_meters_per_second(departure_excess),
+                    arrival_excess: Velocity::from_meters_per_second(arrival_excess),
+                });
+            }
+        }
+        Some(Porkchop { cells })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Body, BodyDynamics, OrbitingBodyDynamics, StaticBodyDynamics};
+    use crate::constants::Constants;
+    use crate::decimal_vector_3d::DecimalVector3d;
+    use crate::sin_cos::{dbig_to_f64, f64_to_dbig};
+    use crate::units::{Angle, Mass};
+
+    #[test]
+    fn hohmann_and_bi_elliptic_transfers() {
+        let gm = f64_to_dbig(1.32712440018e20);
+        let earth = Length::from_au(DBig::ONE);
+        let mars = Length::from_au(f64_to_dbig(1.524));
+        let transfer = hohmann(&earth, &mars, &gm);
+        let burns: Vec<f64> = transfer
+            .burns
+            .iter()
+            .map(|burn| dbig_to_f64(&burn.to_kilometers_per_second()))
+            .collect();
+        assert!((burns[0] - 2.945).abs() < 0.005, "{burns:?}");
+        assert!((burns[1] - 2.649).abs() < 0.005, "{burns:?}");
+        let days = dbig_to_f64(&transfer.time_of_flight.to_days());
+        assert!((days - 259.0).abs() < 0.5, "{days}");
+
+        let far = Length::from_au(DBig::from(20));
+        let direct = hohmann(&earth, &far, &gm).total_delta_v();
+        let around = bi_elliptic(&earth, &far, &Length::from_au(DBig::from(100)), &gm);
+        assert_eq!(around.burns.len(), 3);
+        assert!(around.total_delta_v() < direct);
+        let back = hohmann(&far, &earth, &gm).total_delta_v();
+        assert!((dbig_to_f64(&(back - direct).to_meters_per_second())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn porkchop_finds_the_hohmann_window() {
+        let mut sim = Simulation::new();
+        let gm = 1.32712440018e20;
+        let planet = |name: &str, au: f64, phase: f64| {
+            let radius = au * 1.495978707e11;
+            let period = 2.0 * std::f64::consts::PI * (radius.powi(3) / gm).sqrt();
+            Body::new(
+                name,
+                Mass::from_kilograms(f64_to_dbig(6e23)),
+                Length::from_meters(f64_to_dbig(3.4e6)),
+                BodyDynamics::Orbiting(OrbitingBodyDynamics {
+                    orbit_radius: Length::from_meters(f64_to_dbig(radius)),
+                    orbit_plane_normal: DecimalVector3d::from_f64(0.0, 1.0, 0.0),
+                    orbit_period: Time::from_seconds(f64_to_dbig(period)),
+                    orbit_phase: Angle::from_degrees(f64_to_dbig(phase)),
+                }),
+            )
+        };
+        let window = hohmann(
+            &Length::from_au(DBig::ONE),
+            &Length::from_au(f64_to_dbig(1.524)),
+            &f64_to_dbig(gm),
+        );
+        let flight = dbig_to_f64(&window.time_of_flight.to_seconds());
+        let mars_period =
+            2.0 * std::f64::consts::PI * ((1.524 * 1.495978707e11f64).powi(3) / gm).sqrt();
+        // Mars has to lead by what it covers during the flight short of half a turn
+        let lead = 180.0 - 360.0 * flight / mars_period;
+        let sun = sim.add_hierarchy(
+            Body {
+                satellites: vec![planet("Earth", 1.0, 0.0), planet("Mars", 1.524, lead)],
+                ..Body::new(
+                    "Sun",
+                    Constants::default().mass_from_gm(&f64_to_dbig(gm)),
+                    Length::from_meters(f64_to_dbig(6.9634e8)),
+                    BodyDynamics::Static(StaticBodyDynamics {
+                        position: DecimalVector3d::zero(),
+                    }),
+                )
+            },
+            None,
+        );
+        let (earth, mars) = (sun + 1, sun + 2);
+
+        let days = |day: f64| Time::from_days(f64_to_dbig(day));
+        let departures: Vec<Time> = [-20.0, 0.0, 20.0].into_iter().map(days).collect();
+        let hohmann_days = flight / 86400.0;
+        // exactly half a turn leaves the transfer plane undefined, so arrive a day early
+        let arrivals: Vec<Time> = [hohmann_days - 20.0, hohmann_days - 1.0, hohmann_days + 20.0]
+            .into_iter()
+            .map(days)
+            .collect();
+        let porkchop = sim.porkchop(earth, mars, &departures, &arrivals).unwrap();
+        assert_eq!(porkchop.cells.len(), 9);
+        let best = porkchop.best().unwrap();
+        assert_eq!(best.departure, days(0.0));
+        assert_eq!(best.arrival, days(hohmann_days - 1.0));
+        let total = dbig_to_f64(&best.total_delta_v().to_meters_per_second());
+        let expected = dbig_to_f64(&window.total_delta_v().to_meters_per_second());
+        assert!((total / expected - 1.0).abs() < 0.01, "{total} {expected}");
+        let c3 = dbig_to_f64(&best.c3);
+        assert!((c3.sqrt() / 2945.0 - 1.0).abs() < 0.02, "{c3}");
+
+        let mut csv = vec![];
+        porkchop.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 10);
+        assert!(sim.porkchop(sun, mars, &departures, &arrivals).is_none());
+    }
+}
@@ -0,0 +1,357 @@
+//! Dimensioned quantities wrapping `DBig` in SI units, so passing a period where a distance is
+//! expected, or days where seconds are, does not compile.
+//!
+//! Quantities of one kind add and subtract, scale by a bare `DBig` and divide into a bare ratio.
+//! Constructors raise the value to the working precision, see [`precise`].
+
+use crate::au::AU_METERS;
+use crate::sin_cos::{precise, PI};
+use core::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use dashu_float::ops::Abs;
+use dashu_float::DBig;
+use std::fmt;
+
+const METERS_PER_KILOMETER: u32 = 1000;
+const METERS_PER_LIGHT_SECOND: u32 = 299792458;
+/// Astronomical units per parsec, 648000 / pi.
+pub(crate) const AU_PER_PARSEC_NUMERATOR: u32 = 648000;
+const SECONDS_PER_MINUTE: u32 = 60;
+const SECONDS_PER_HOUR: u32 = 3600;
+const SECONDS_PER_DAY: u32 = 86400;
+/// Julian year of 365.25 days, in seconds.
+const SECONDS_PER_JULIAN_YEAR: u32 = 31557600;
+
+macro_rules! quantity {
+    ($(#[$meta:meta])* $name:ident, $from_base:ident, $to_base:ident, $unit:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, PartialOrd)]
+        pub struct $name(DBig);
+
+        impl $name {
+            pub fn $from_base(value: DBig) -> $name {
+                $name(precise(value))
+            }
+
+            pub fn $to_base(&self) -> DBig {
+                self.0.clone()
+            }
+
+            pub fn zero() -> $name {
+                $name(precise(DBig::ZERO))
+            }
+
+            pub fn abs(&self) -> $name {
+                $name(self.0.clone().abs())
+            }
+
+            pub fn max(self, other: $name) -> $name {
+                if other > self {
+                    other
+                } else {
+                    self
+                }
+            }
+
+            pub fn min(self, other: $name) -> $name {
+                if other < self {
+                    other
+                } else {
+                    self
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{} {}", self.0, $unit)
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> $name {
+                $name(-self.0)
+            }
+        }
+
+        quantity_binop!(impl Add, add for $name);
+        quantity_binop!(impl Sub, sub for $name);
+
+        impl AddAssign<&$name> for $name {
+            fn add_assign(&mut self, other: &$name) {
+                self.0 += &other.0;
+            }
+        }
+
+        impl SubAssign<&$name> for $name {
+            fn sub_assign(&mut self, other: &$name) {
+                self.0 -= &other.0;
+            }
+        }
+
+        quantity_scale!(impl Mul, mul for $name);
+        quantity_scale!(impl Div, div for $name);
+
+        /// Ratio of two quantities of the same kind.
+        impl Div<&$name> for &$name {
+            type Output = DBig;
+
+            fn div(self, other: &$name) -> DBig {
+                self.0.clone() / &other.0
+            }
+        }
+    };
+}
+
+macro_rules! quantity_binop {
+    (impl $imp:ident, $method:ident for $t:ident) => {
+        impl $imp<$t> for $t {
+            type Output = $t;
+
+            fn $method(self, other: $t) -> $t {
+                $t($imp::$method(self.0, other.0))
+            }
+        }
+        impl $imp<&$t> for $t {
+            type Output = $t;
+
+            fn $method(self, other: &$t) -> $t {
+                $t($imp::$method(self.0, &other.0))
+            }
+        }
+        impl $imp<&$t> for &$t {
+            type Output = $t;
+
+            fn $method(self, other: &$t) -> $t {
+                $t($imp::$method(&self.0, &other.0))
+            }
+        }
+    };
+}
+
+macro_rules! quantity_scale {
+    (impl $imp:ident, $method:ident for $t:ident) => {
+        impl $imp<DBig> for $t {
+            type Output = $t;
+
+            fn $method(self, other: DBig) -> $t {
+                $t($imp::$method(self.0, other))
+            }
+        }
+        impl $imp<&DBig> for $t {
+            type Output = $t;
+
+            fn $method(self, other: &DBig) -> $t {
+                $t($imp::$method(self.0, other))
+            }
+        }
+        impl $imp<&DBig> for &$t {
+            type Output = $t;
+
+            fn $method(self, other: &DBig) -> $t {
+                $t($imp::$method(&self.0, other))
+            }
+        }
+    };
+}
+
+quantity!(
+    /// A distance, in meters.
+    Length,
+    from_meters,
+    to_meters,
+    "m"
+);
+quantity!(
+    /// A duration or a point in simulation time, in seconds.
+    Time,
+    from_seconds,
+    to_seconds,
+    "s"
+);
+quantity!(
+    /// A mass, in kilograms.
+    Mass,
+    from_kilograms,
+    to_kilograms,
+    "kg"
+);
+quantity!(
+    /// A speed, in meters per second.
+    Velocity,
+    from_meters_per_second,
+    to_meters_per_second,
+    "m/s"
+);
+quantity!(
+    /// An angle, in radians.
+    Angle,
+    from_radians,
+    to_radians,
+    "rad"
+);
+
+fn meters_per_parsec() -> DBig {
+    AU_METERS.clone() * DBig::from(AU_PER_PARSEC_NUMERATOR) / PI.clone()
+}
+
+impl Length {
+    pub fn from_kilometers(kilometers: DBig) -> Length {
+        Length::from_meters(kilometers * DBig::from(METERS_PER_KILOMETER))
+    }
+
+    pub fn to_kilometers(&self) -> DBig {
+        self.to_meters() / DBig::from(METERS_PER_KILOMETER)
+    }
+
+    /// In IAU 2012 astronomical units, see
+    /// [`Constants::length_from_au`](crate::constants::Constants::length_from_au) for other sets.
+    pub fn from_au(au: DBig) -> Length {
+        Length::from_meters(au * AU_METERS.clone())
+    }
+
+    pub fn to_au(&self) -> DBig {
+        self.to_meters() / AU_METERS.clone()
+    }
+
+    /// Distance light travels in `light_seconds` seconds.
+    pub fn from_light_seconds(light_seconds: DBig) -> Length {
+        Length::from_meters(light_seconds * DBig::from(METERS_PER_LIGHT_SECOND))
+    }
+
+    pub fn to_light_seconds(&self) -> DBig {
+        self.to_meters() / DBig::from(METERS_PER_LIGHT_SECOND)
+    }
+
+    /// In parsecs of the IAU 2012 au, see
+    /// [`Constants::parsec`](crate::constants::Constants::parsec) for other sets.
+    pub fn from_parsecs(parsecs: DBig) -> Length {
+        Length::from_meters(parsecs * meters_per_parsec())
+    }
+
+    pub fn to_parsecs(&self) -> DBig {
+        self.to_meters() / meters_per_parsec()
+    }
+}
+
+impl Time {
+    pub fn from_minutes(minutes: DBig) -> Time {
+        Time::from_seconds(minutes * DBig::from(SECONDS_PER_MINUTE))
+    }
+
+    pub fn to_minutes(&self) -> DBig {
+        self.to_seconds() / DBig::from(SECONDS_PER_MINUTE)
+    }
+
+    pub fn from_hours(hours: DBig) -> Time {
+        Time::from_seconds(hours * DBig::from(SECONDS_PER_HOUR))
+    }
+
+    pub fn to_hours(&self) -> DBig {
+        self.to_seconds() / DBig::from(SECONDS_PER_HOUR)
+    }
+
+    pub fn from_days(days: DBig) -> Time {
+        Time::from_seconds(days * DBig::from(SECONDS_PER_DAY))
+    }
+
+    pub fn to_days(&self) -> DBig {
+        self.to_seconds() / DBig::from(SECONDS_PER_DAY)
+    }
+
+    pub fn from_julian_years(years: DBig) -> Time {
+        Time::from_seconds(years * DBig::from(SECONDS_PER_JULIAN_YEAR))
+    }
+
+    pub fn to_julian_years(&self) -> DBig {
+        self.to_seconds() / DBig::from(SECONDS_PER_JULIAN_YEAR)
+    }
+}
+
+impl Velocity {
+    pub fn from_kilometers_per_second(kilometers_per_second: DBig) -> Velocity {
+        Velocity::from_meters_per_second(kilometers_per_second * DBig::from(METERS_PER_KILOMETER))
+    }
+
+    pub fn to_kilometers_per_second(&self) -> DBig {
+        self.to_meters_per_second() / DBig::from(METERS_PER_KILOMETER)
+    }
+
+    pub fn from_au_per_day(au_per_day: DBig) -> Velocity {
+        &Length::from_au(au_per_day) / &Time::from_days(DBig::ONE)
+    }
+
+    pub fn to_au_per_day(&self) -> DBig {
+        (self * &Time::from_days(DBig::ONE)).to_au()
+    }
+}
+
+impl Angle {
+    pub fn from_degrees(degrees: DBig) -> Angle {
+        Angle::from_radians(degrees * PI.clone() / DBig::from(180))
+    }
+
+    pub fn to_degrees(&self) -> DBig {
+        self.to_radians() * DBig::from(180) / PI.clone()
+    }
+}
+
+impl Div<&Time> for &Length {
+    type Output = Velocity;
+
+    fn div(self, time: &Time) -> Velocity {
+        Velocity::from_meters_per_second(self.to_meters() / time.to_seconds())
+    }
+}
+
+impl Div<&Velocity> for &Length {
+    type Output = Time;
+
+    fn div(self, velocity: &Velocity) -> Time {
+        Time::from_seconds(self.to_meters() / velocity.to_meters_per_second())
+    }
+}
+
+impl Mul<&Time> for &Velocity {
+    type Output = Length;
+
+    fn mul(self, time: &Time) -> Length {
+        Length::from_meters(self.to_meters_per_second() * time.to_seconds())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sin_cos::{dbig_to_f64, f64_to_dbig};
+
+    #[test]
+    fn converts_units() {
+        let year = Time::from_julian_years(DBig::ONE);
+        assert_eq!(dbig_to_f64(&year.to_days()), 365.25);
+        assert_eq!(
+            dbig_to_f64(&Time::from_hours(f64_to_dbig(1.5)).to_minutes()),
+            90.0
+        );
+        let parsec = Length::from_parsecs(DBig::ONE);
+        assert!((dbig_to_f64(&parsec.to_au()) - 206264.806247).abs() < 1e-6);
+        let light_year =
+            &Velocity::from_meters_per_second(DBig::from(METERS_PER_LIGHT_SECOND)) * &year;
+        assert!((dbig_to_f64(&light_year.to_parsecs()) - 0.306601).abs() < 1e-6);
+        assert_eq!(
+            dbig_to_f64(&Length::from_kilometers(f64_to_dbig(1.5)).to_meters()),
+            1500.0
+        );
+        let speed = &Length::from_au(DBig::ONE) / &Time::from_days(DBig::ONE);
+        assert!((dbig_to_f64(&speed.to_kilometers_per_second()) - 1731.46).abs() < 0.01);
+        assert!((dbig_to_f64(&speed.to_au_per_day()) - 1.0).abs() < 1e-12);
+        let angle = Angle::from_degrees(DBig::from(90));
+        assert!((dbig_to_f64(&angle.to_radians()) - std::f64::consts::FRAC_PI_2).abs() < 1e-15);
+        assert!(Length::from_meters(DBig::ONE) < Length::from_kilometers(DBig::ONE));
+        assert_eq!(
+            (Time::from_days(DBig::ONE) - Time::from_hours(DBig::from(36))).abs(),
+            Time::from_hours(DBig::from(12))
+        );
+    }
+}